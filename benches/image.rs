@@ -0,0 +1,89 @@
+//! Measures sending a notification with a 512x512 RGBA image.
+//!
+//! Needs a session bus without a notification server, a minimal one is started:
+//!
+//! ```sh
+//! dbus-run-session -- cargo bench --features images --bench image
+//! DBUSRS=1 dbus-run-session -- cargo bench --features d,d_vendored,images --bench image
+//! ```
+
+use futures_lite::stream::StreamExt;
+use notify_rust::*;
+use std::time::Instant;
+
+const SIZE: i32 = 512;
+const ROUNDS: u32 = 20;
+
+/// Answers every `Notify` call without looking at its arguments,
+/// so the time it takes to deserialize the image does not count.
+fn start_server() -> zbus::Connection {
+    let connection = zbus::block_on(async {
+        let connection = zbus::Connection::session().await?;
+        connection
+            .request_name("org.freedesktop.Notifications")
+            .await?;
+        zbus::Result::Ok(connection)
+    })
+    .unwrap();
+    let server = connection.clone();
+    std::thread::spawn(move || {
+        zbus::block_on(async {
+            let mut messages = zbus::MessageStream::from(&server);
+            let mut id = 0u32;
+            while let Some(Ok(message)) = messages.next().await {
+                let header = message.header();
+                match header.member().map(|member| member.as_str()) {
+                    Some("Notify") => {
+                        id += 1;
+                        server.reply(&header, &id).await.unwrap();
+                    }
+                    // asked for the spec version, which decides the name of the image hint
+                    Some("GetServerInformation") => {
+                        let information = ("bench", "notify-rust", "0", "1.2");
+                        server.reply(&header, &information).await.unwrap();
+                    }
+                    _ => {}
+                }
+            }
+        })
+    });
+    connection
+}
+
+fn measure(name: &str, mut run: impl FnMut()) {
+    run();
+    let started = Instant::now();
+    for _ in 0..ROUNDS {
+        run();
+    }
+    println!("{name:<40} {:>10.2?}", started.elapsed() / ROUNDS);
+}
+
+fn main() {
+    // `cargo test --benches` runs this without `--bench`, there is nothing to test
+    if !std::env::args().any(|arg| arg == "--bench") {
+        return;
+    }
+    let _server = start_server();
+    let notifier = Notifier::new().unwrap();
+    println!("with {:?}", notifier.dbus_stack());
+
+    let pixels = (0..SIZE * SIZE * 4).map(|i| i as u8).collect();
+    let image = Image::from_rgba(SIZE, SIZE, pixels).unwrap();
+    let plain = Notification::new().summary("image").finalize();
+    let with_image = plain.clone().image_data(image.clone()).finalize();
+
+    measure("Image::clone()", || {
+        drop(std::hint::black_box(image.clone()))
+    });
+    measure("Notifier::show() without image", || {
+        notifier.show(&plain).unwrap();
+    });
+    measure("Notifier::show() with image", || {
+        notifier.show(&with_image).unwrap();
+    });
+    let mut handle = notifier.show(&with_image).unwrap();
+    measure("NotificationHandle::update() with image", || {
+        handle.update().unwrap();
+    });
+}
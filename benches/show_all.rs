@@ -0,0 +1,63 @@
+//! Compares sending a burst of notifications one by one with `Notifier::show_all()`.
+//!
+//! Needs a session bus without a notification server, a fake one is started:
+//!
+//! ```sh
+//! dbus-run-session -- cargo bench --bench show_all
+//! DBUSRS=1 dbus-run-session -- cargo bench --features d,d_vendored --bench show_all
+//! ```
+
+use notify_rust::*;
+use std::time::{Duration, Instant};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+const BURST: usize = 30;
+const ROUNDS: u32 = 10;
+
+fn measure(name: &str, mut send: impl FnMut(&[Notification])) {
+    let notifications: Vec<Notification> = (0..BURST)
+        .map(|i| Notification::new().summary(&format!("#{i}")).finalize())
+        .collect();
+    send(&notifications);
+    let started = Instant::now();
+    for _ in 0..ROUNDS {
+        send(&notifications);
+    }
+    let per_burst = started.elapsed() / ROUNDS;
+    println!(
+        "{name:<32} {:>8.2?} per {BURST} notifications, {:>8.2?} each",
+        per_burst,
+        per_burst / BURST as u32
+    );
+}
+
+fn main() {
+    // `cargo test --benches` runs this without `--bench`, there is nothing to test
+    if !std::env::args().any(|arg| arg == "--bench") {
+        return;
+    }
+    let (_server, _shown) = common::start_server();
+    let notifier = Notifier::new().unwrap();
+    println!("with {:?}", notifier.dbus_stack());
+
+    measure("Notification::show()", |notifications| {
+        for notification in notifications {
+            notification.show().unwrap();
+        }
+    });
+    measure("Notifier::show()", |notifications| {
+        for notification in notifications {
+            notifier.show(notification).unwrap();
+        }
+    });
+    measure("Notifier::show_all()", |notifications| {
+        for result in notifier.show_all(notifications) {
+            result.unwrap();
+        }
+    });
+
+    // the handles of the last round close their subscriptions in the background
+    std::thread::sleep(Duration::from_millis(100));
+}
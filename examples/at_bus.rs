@@ -5,7 +5,7 @@ fn main() {
 
 #[cfg(all(unix, not(target_os = "macos",)))]
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    use notify_rust::Notification;
+    use notify_rust::{Notification, NotificationBus};
     std::env::set_var(
         "RUST_LOG",
         "simple=trace,zbus=trace,server=trace,notify_rust=trace",
@@ -14,8 +14,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     #[cfg(feature = "env_logger")]
     let _ = env_logger::builder().is_test(true).try_init();
 
-    #[allow(deprecated)]
-    Notification::at_bus("example")
+    let bus = NotificationBus::new(
+        "de.hoodie.Notification.example",
+        "/org/freedesktop/Notifications",
+    )?;
+
+    Notification::new()
+        .bus(bus)
         .summary("Critical Error")
         .body("Just <b>kidding</b>, this is just the notification (example).")
         .icon("dialog-error")
@@ -0,0 +1,31 @@
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn main() {
+    println!("this is an xdg only feature")
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    use notify_rust::{Notification, Notifier};
+
+    // one connection for everything below
+    let notifier = Notifier::new()?;
+    let info = notifier.server_information()?;
+    println!("connected to {} {}", info.name, info.version);
+
+    for i in 1..=5 {
+        notifier.show(
+            Notification::new()
+                .summary("Notifier")
+                .body(&format!("message #{i} over a shared connection")),
+        )?;
+    }
+
+    let handle = notifier.show(
+        Notification::new()
+            .summary("Notifier")
+            .body("this one goes away in two seconds"),
+    )?;
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    notifier.close(handle.id())?;
+    Ok(())
+}
@@ -30,6 +30,7 @@ fn main() {
             .wait_for_action_async(|action| {
                 println!("action invoked: {action:?}");
             })
-            .await;
+            .await
+            .unwrap();
     })
 }
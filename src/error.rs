@@ -2,7 +2,7 @@
 
 #[cfg(all(feature = "images_no_default_features", unix, not(target_os = "macos")))]
 use crate::image::ImageError;
-use std::{fmt, num};
+use std::{fmt, io, num};
 /// Convenient wrapper around `std::Result`.
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -39,6 +39,9 @@ pub enum ErrorKind {
 
     Parse(num::ParseIntError),
 
+    /// Reading from the file system failed.
+    Io(io::Error),
+
     SpecVersion(String),
 
     Conversion(String),
@@ -47,6 +50,148 @@ pub enum ErrorKind {
     Image(ImageError),
 
     ImplementationMissing,
+
+    /// Not supported on this platform or with the selected D-Bus stack.
+    Unsupported(String),
+
+    /// A D-Bus method call got no reply within the configured call timeout.
+    CallTimeout,
+
+    /// No notification server owns the bus name notifications are sent to.
+    ServerUnavailable,
+
+    /// The notification server that showed a notification is gone.
+    ServerVanished,
+}
+
+/// What went wrong, in terms an application can act on, see [`Error::category()`].
+///
+/// Unlike [`ErrorKind`] this is the same on every platform and with every D-Bus stack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCategory {
+    /// No notification server is running, or none owns the name notifications are sent to.
+    ServerUnavailable,
+
+    /// The notification server that showed a notification is gone.
+    ServerVanished,
+
+    /// A call got no reply in time.
+    Timeout,
+
+    /// The bus or the notification server refused the call.
+    AccessDenied,
+
+    /// An argument was rejected, e.g. an invalid bus name or a value the server does not accept.
+    InvalidArgument,
+
+    /// An image could not be opened or converted, or it is too big.
+    Image,
+
+    /// Not supported on this platform, with the selected D-Bus stack or by the notification server.
+    Unsupported,
+
+    /// Connecting to the bus failed, or the connection broke.
+    Connection,
+
+    /// Anything else.
+    Other,
+}
+
+impl Error {
+    /// Returns `true` if a D-Bus method call got no reply within the configured call timeout.
+    pub fn is_call_timeout(&self) -> bool {
+        matches!(self.kind, ErrorKind::CallTimeout)
+    }
+
+    /// Classifies the error, e.g. to tell a missing notification server from other failures.
+    ///
+    /// Errors of the D-Bus libraries are classified by the name of the D-Bus error where they have one.
+    ///
+    /// ```no_run
+    /// # use notify_rust::{error::ErrorCategory, Notification};
+    /// match Notification::new().summary("Backup done").show() {
+    ///     Ok(_) => {}
+    ///     // nobody to tell, that is fine
+    ///     Err(error) if error.category() == ErrorCategory::ServerUnavailable => {}
+    ///     Err(error) => eprintln!("could not notify: {error}"),
+    /// }
+    /// ```
+    pub fn category(&self) -> ErrorCategory {
+        match self.kind {
+            #[cfg(all(feature = "dbus", unix, not(target_os = "macos")))]
+            ErrorKind::Dbus(ref e) => e.name().map_or(ErrorCategory::Other, dbus_error_category),
+
+            #[cfg(all(feature = "zbus", unix, not(target_os = "macos")))]
+            ErrorKind::Zbus(ref e) => zbus_error_category(e),
+
+            #[cfg(target_os = "macos")]
+            ErrorKind::MacNotificationSys(_) => ErrorCategory::Other,
+
+            #[cfg(all(target_os = "macos", feature = "preview-macos-un"))]
+            ErrorKind::MacUserNotifications(_) => ErrorCategory::Other,
+
+            ErrorKind::Parse(_) | ErrorKind::Conversion(_) => ErrorCategory::InvalidArgument,
+            ErrorKind::Io(ref e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                ErrorCategory::AccessDenied
+            }
+            ErrorKind::Io(_) => ErrorCategory::Other,
+            ErrorKind::Msg(_) | ErrorKind::SpecVersion(_) => ErrorCategory::Other,
+            #[cfg(all(feature = "images_no_default_features", unix, not(target_os = "macos")))]
+            ErrorKind::Image(_) => ErrorCategory::Image,
+            ErrorKind::ImplementationMissing | ErrorKind::Unsupported(_) => {
+                ErrorCategory::Unsupported
+            }
+            ErrorKind::CallTimeout => ErrorCategory::Timeout,
+            ErrorKind::ServerUnavailable => ErrorCategory::ServerUnavailable,
+            ErrorKind::ServerVanished => ErrorCategory::ServerVanished,
+        }
+    }
+}
+
+/// Classifies a D-Bus error reply by its name.
+#[cfg(all(
+    any(feature = "dbus", feature = "zbus"),
+    unix,
+    not(target_os = "macos")
+))]
+fn dbus_error_category(name: &str) -> ErrorCategory {
+    let Some(name) = name.strip_prefix("org.freedesktop.DBus.Error.") else {
+        return ErrorCategory::Other;
+    };
+    match name {
+        // the bus could not start a server on demand either
+        "ServiceUnknown" | "NameHasNoOwner" => ErrorCategory::ServerUnavailable,
+        _ if name.starts_with("Spawn.") => ErrorCategory::ServerUnavailable,
+        "NoReply" | "Timeout" | "TimedOut" => ErrorCategory::Timeout,
+        "AccessDenied" | "AuthFailed" | "InteractiveAuthorizationRequired" => {
+            ErrorCategory::AccessDenied
+        }
+        "InvalidArgs" | "InvalidSignature" | "MatchRuleInvalid" => ErrorCategory::InvalidArgument,
+        "UnknownMethod" | "UnknownInterface" | "UnknownObject" | "UnknownProperty"
+        | "NotSupported" => ErrorCategory::Unsupported,
+        "Disconnected" | "NoServer" | "NoNetwork" | "BadAddress" | "FileNotFound" | "IOError" => {
+            ErrorCategory::Connection
+        }
+        _ => ErrorCategory::Other,
+    }
+}
+
+#[cfg(all(feature = "zbus", unix, not(target_os = "macos")))]
+fn zbus_error_category(error: &zbus::Error) -> ErrorCategory {
+    use zbus::DBusError;
+    match error {
+        zbus::Error::MethodError(name, ..) => dbus_error_category(name.as_str()),
+        zbus::Error::FDO(error) => dbus_error_category(error.name().as_str()),
+        zbus::Error::InputOutput(_)
+        | zbus::Error::Connection(..)
+        | zbus::Error::Address(_)
+        | zbus::Error::Handshake(_)
+        | zbus::Error::InvalidGUID => ErrorCategory::Connection,
+        zbus::Error::Names(_) => ErrorCategory::InvalidArgument,
+        zbus::Error::Unsupported => ErrorCategory::Unsupported,
+        _ => ErrorCategory::Other,
+    }
 }
 
 impl fmt::Display for Error {
@@ -65,10 +210,23 @@ impl fmt::Display for Error {
             ErrorKind::MacUserNotifications(ref e) => write!(f, "{e}"),
 
             ErrorKind::Parse(ref e) => write!(f, "Parsing Error: {e}"),
+            ErrorKind::Io(ref e) => write!(f, "{e}"),
             ErrorKind::Conversion(ref e) => write!(f, "Conversion Error: {e}"),
-            ErrorKind::SpecVersion(ref e) | ErrorKind::Msg(ref e) => write!(f, "{e}"),
+            ErrorKind::SpecVersion(ref e)
+            | ErrorKind::Msg(ref e)
+            | ErrorKind::Unsupported(ref e) => {
+                write!(f, "{e}")
+            }
             #[cfg(all(feature = "images_no_default_features", unix, not(target_os = "macos")))]
             ErrorKind::Image(ref e) => write!(f, "{}", e),
+            ErrorKind::CallTimeout => write!(f, "D-Bus method call timed out"),
+            ErrorKind::ServerUnavailable => write!(f, "no notification server is running"),
+            ErrorKind::ServerVanished => {
+                write!(
+                    f,
+                    "the notification server that showed this notification is gone"
+                )
+            }
             ErrorKind::ImplementationMissing => write!(
                 f,
                 r#"No Dbus implementation available, please compile with either feature ="z" or feature="d""#
@@ -77,7 +235,29 @@ impl fmt::Display for Error {
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.kind {
+            #[cfg(all(feature = "dbus", unix, not(target_os = "macos")))]
+            ErrorKind::Dbus(ref e) => Some(e),
+
+            #[cfg(all(feature = "zbus", unix, not(target_os = "macos")))]
+            ErrorKind::Zbus(ref e) => Some(e),
+
+            #[cfg(target_os = "macos")]
+            ErrorKind::MacNotificationSys(ref e) => Some(e),
+
+            #[cfg(all(target_os = "macos", feature = "preview-macos-un"))]
+            ErrorKind::MacUserNotifications(ref e) => Some(e),
+
+            ErrorKind::Parse(ref e) => Some(e),
+            ErrorKind::Io(ref e) => Some(e),
+            #[cfg(all(feature = "images_no_default_features", unix, not(target_os = "macos")))]
+            ErrorKind::Image(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl From<&str> for Error {
     fn from(e: &str) -> Error {
@@ -99,6 +279,12 @@ impl From<dbus::Error> for Error {
 #[cfg(all(feature = "zbus", unix, not(target_os = "macos")))]
 impl From<zbus::Error> for Error {
     fn from(e: zbus::Error) -> Error {
+        // zbus reports an expired method timeout as an IO error
+        if let zbus::Error::InputOutput(ref io) = e {
+            if io.kind() == io::ErrorKind::TimedOut {
+                return ErrorKind::CallTimeout.into();
+            }
+        }
         Error {
             kind: ErrorKind::Zbus(e),
         }
@@ -140,6 +326,14 @@ impl From<num::ParseIntError> for Error {
     }
 }
 
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error {
+            kind: ErrorKind::Io(e),
+        }
+    }
+}
+
 impl From<ErrorKind> for Error {
     fn from(kind: ErrorKind) -> Error {
         Error { kind }
@@ -10,11 +10,12 @@ pub(crate) mod message;
 use crate::image::Image;
 
 #[cfg(all(feature = "images_no_default_features", feature = "zbus", unix, not(target_os = "macos")))]
-use crate::image::image_spec_str;
+use crate::image::{image_spec_str, ImagePayload};
 use crate::Urgency;
 
 #[cfg(all(feature = "zbus", unix, not(target_os = "macos")))] use crate::notification::Notification;
 #[cfg(all(feature = "zbus", unix, not(target_os = "macos")))] use std::collections::HashMap;
+#[cfg(all(feature = "zbus", unix, not(target_os = "macos")))] use crate::miniver::Version;
 
 mod constants;
 
@@ -159,20 +160,87 @@ fn test_hints_to_map() {
         .hint(Hint::Custom("f00".into(), "bar3".into()))
         .finalize();
 
-     assert_eq!(hints_to_map(&n1), maplit::hashmap!{
-         "foo" => zvariant::Value::Str("bar2".into()),
-         "f00" => zvariant::Value::Str("bar3".into())
+     assert_eq!(hints_to_map(&n1, crate::xdg::FALLBACK_SPEC_VERSION), maplit::hashmap!{
+         "foo" => HintValue::Value(zvariant::Value::Str("bar2".into())),
+         "f00" => HintValue::Value(zvariant::Value::Str("bar3".into()))
      });
 }
 
+#[cfg(all(feature = "zbus", feature = "images_no_default_features", unix, not(target_os = "macos")))]
+#[test]
+fn test_image_data_serialization() {
+    use zvariant::{serialized::Context, to_bytes, LE};
+
+    let image = Image::from_rgb(2, 1, vec![1, 2, 3, 4, 5, 6]).unwrap();
+    let hint = Hint::ImageData(image);
+    let n1 = Notification::new().hint(hint.clone()).finalize();
+
+    // the borrowed image has to end up exactly like the copied `Value` did
+    let (key, value): (&str, zvariant::Value) = (&hint).into();
+    let ctxt = Context::new_dbus(LE, 0);
+    assert_eq!(
+        to_bytes(ctxt, &hints_to_map(&n1, crate::xdg::FALLBACK_SPEC_VERSION)).unwrap().bytes(),
+        to_bytes(ctxt, &maplit::hashmap!{ key => value }).unwrap().bytes(),
+    );
+}
+
+#[cfg(all(feature = "zbus", feature = "images_no_default_features", unix, not(target_os = "macos")))]
+#[test]
+fn test_image_data_key_follows_spec_version() {
+    let image = Image::from_rgb(1, 1, vec![0, 0, 0]).unwrap();
+    let n1 = Notification::new().hint(Hint::ImageData(image)).finalize();
+
+    for (version, key) in [((1, 0), "icon_data"), ((1, 1), "image_data"), ((1, 2), "image-data")] {
+        let map = hints_to_map(&n1, Version::new(version.0, version.1));
+        assert_eq!(map.keys().collect::<Vec<_>>(), [&key]);
+    }
+}
+
+/// The value of a hint in the `a{sv}` argument of `Notify`.
+///
+/// Image data is serialized straight from the [`Image`], the pixels are not copied into a [`zvariant::Value`].
 #[cfg(all(feature = "zbus", unix, not(target_os = "macos")))]
-pub(crate) fn hints_to_map(notification: &Notification) -> HashMap::<&str, zvariant::Value<'_>> {
+#[derive(Debug, PartialEq)]
+pub(crate) enum HintValue<'a> {
+    Value(zvariant::Value<'a>),
+    #[cfg(feature = "images_no_default_features")]
+    Image(&'a Image),
+}
+
+#[cfg(all(feature = "zbus", unix, not(target_os = "macos")))]
+impl zvariant::Type for HintValue<'_> {
+    const SIGNATURE: &'static zvariant::Signature = &zvariant::Signature::Variant;
+}
+
+#[cfg(all(feature = "zbus", unix, not(target_os = "macos")))]
+impl serde::Serialize for HintValue<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            HintValue::Value(value) => value.serialize(serializer),
+            #[cfg(feature = "images_no_default_features")]
+            HintValue::Image(image) => zvariant::as_value::Serialize(&ImagePayload(image)).serialize(serializer),
+        }
+    }
+}
+
+/// The hints of `notification` as the `a{sv}` argument of `Notify`, for a server following `spec_version`.
+#[cfg(all(feature = "zbus", unix, not(target_os = "macos")))]
+#[cfg_attr(not(feature = "images_no_default_features"), allow(unused_variables))]
+pub(crate) fn hints_to_map(notification: &Notification, spec_version: Version) -> HashMap::<&str, HintValue<'_>> {
     notification
         .get_hints()
-        .map(Into::into)
+        .map(|hint| match hint {
+            #[cfg(feature = "images_no_default_features")]
+            Hint::ImageData(image) => (image_spec_str(spec_version), HintValue::Image(image)),
+            hint => {
+                let (key, value) = hint.into();
+                (key, HintValue::Value(value))
+            }
+        })
         .collect()
 }
 
+/// Image data ends up as one value per pixel byte, notifications are sent without this conversion.
 #[cfg(all(feature = "zbus", unix, not(target_os = "macos")))]
 impl<'a> From<&'a Hint> for (&'a str, zvariant::Value<'a>) {
     fn from(val: &'a Hint) -> Self {
@@ -182,13 +250,11 @@ impl<'a> From<&'a Hint> for (&'a str, zvariant::Value<'a>) {
             Hint::Category(value)          => (CATEGORY       , zvariant::Value::Str(value.as_str().into())),
             Hint::DesktopEntry(value)      => (DESKTOP_ENTRY  , zvariant::Value::Str(value.as_str().into())),
 
+            // without a server to ask, see `hints_to_map()` for the negotiated name
             #[cfg(all(feature = "zbus", feature = "images_no_default_features", unix, not(target_os = "macos")))]
-            //Hint::ImageData(image)         => (image_spec(*crate::SPEC_VERSION).as_str(), ImagePayload::from(*image).into()),
             Hint::ImageData(image)         => (
-                image_spec_str(*crate::SPEC_VERSION),
-                zvariant::Value::Structure(
-                    image.to_tuple().into()
-                )
+                image_spec_str(crate::xdg::FALLBACK_SPEC_VERSION),
+                zvariant::Value::Structure(image.to_structure())
             ),
 
 
@@ -20,7 +20,9 @@ use crate::image::*;
 
 use std::collections::{HashMap, HashSet};
 #[cfg(feature = "dbus")]
-use dbus::arg::{messageitem::MessageItem, RefArg};
+use dbus::arg::{messageitem::MessageItem, IterAppend, RefArg, Variant};
+#[cfg(feature = "dbus")]
+use crate::miniver::Version;
 
 /// All currently implemented `Hints` that can be sent.
 ///
@@ -28,12 +30,6 @@ use dbus::arg::{messageitem::MessageItem, RefArg};
 #[derive(Eq, PartialEq, Hash, Clone, Debug)]
 pub(crate) struct HintMessage(Hint);
 
-#[cfg(feature = "dbus")]
-impl HintMessage {
-    pub fn wrap_hint(hint: Hint) -> (MessageItem, MessageItem) {
-        Self::from(hint).into()
-    }
-}
 
 impl From<Hint> for HintMessage {
     fn from(hint: Hint) -> Self {
@@ -84,15 +80,23 @@ impl<'a, A: RefArg> From<(&'a String, &'a A)> for HintMessage {
 }
 
 #[cfg(feature = "dbus")]
-impl From<HintMessage> for (MessageItem, MessageItem) {
-    fn from(hint: HintMessage) -> Self {
+impl HintMessage {
+    /// Appends the hint as a dict entry, image data under the name `spec_version` knows it by.
+    ///
+    /// Image data goes in as one byte array, not as one item per byte.
+    #[cfg_attr(not(feature = "images_no_default_features"), allow(unused_variables))]
+    pub fn append_entry(&self, spec_version: Version, entry: &mut IterAppend) {
 
-        let (key, value): (String, MessageItem) = match hint.0 {
+        let (key, value): (String, MessageItem) = match self.0 {
             Hint::ActionIcons(value)       => (ACTION_ICONS   .to_owned(), MessageItem::Bool(value)), // bool
             Hint::Category(ref value)      => (CATEGORY       .to_owned(), MessageItem::Str(value.clone())),
             Hint::DesktopEntry(ref value)  => (DESKTOP_ENTRY  .to_owned(), MessageItem::Str(value.clone())),
             #[cfg(all(feature = "images_no_default_features", unix, not(target_os ="macos")))]
-            Hint::ImageData(image)         => (image_spec(*crate::SPEC_VERSION), ImageMessage::from(image).into()),
+            Hint::ImageData(ref image)     => {
+                entry.append(image_spec(spec_version));
+                entry.append(Variant(ImageMessage::from(image.clone())));
+                return;
+            }
             Hint::ImagePath(ref value)     => (IMAGE_PATH     .to_owned(), MessageItem::Str(value.clone())),
             Hint::Resident(value)          => (RESIDENT       .to_owned(), MessageItem::Bool(value)), // bool
             Hint::SoundFile(ref value)     => (SOUND_FILE     .to_owned(), MessageItem::Str(value.clone())),
@@ -107,7 +111,8 @@ impl From<HintMessage> for (MessageItem, MessageItem) {
             Hint::Invalid                  => ("invalid"      .to_owned(), MessageItem::Str("Invalid".to_owned()))
         };
 
-        (MessageItem::Str(key), MessageItem::Variant(Box::new(value)))
+        entry.append(MessageItem::Str(key));
+        entry.append(MessageItem::Variant(Box::new(value)));
     }
 }
 
@@ -49,7 +49,7 @@ fn imagedata_hint_to_item() {
     let hint = &Hint::ImageData(Image::from_rgb(1, 1, vec![0, 0, 0]).unwrap());
     let item: MessageItem = hint.into();
     let test_item = Item::DictEntry(
-        Box::new(Item::Str(image_spec(*::SPEC_VERSION))),
+        Box::new(Item::Str(image_spec(crate::xdg::FALLBACK_SPEC_VERSION))),
         Box::new(Item::Variant(Box::new(Item::Struct(vec![
             Item::Int32(1),
             Item::Int32(1),
@@ -1,5 +1,7 @@
 #[cfg(feature = "dbus")]
-use dbus::arg::messageitem::{MessageItem, MessageItemArray};
+use dbus::arg::{Append, Arg, ArgType, IterAppend};
+#[cfg(feature = "dbus")]
+use dbus::Signature;
 pub use image::DynamicImage;
 
 use std::cmp::Ordering;
@@ -7,6 +9,7 @@ use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
 use std::path::Path;
+use std::sync::Arc;
 
 use crate::miniver::Version;
 
@@ -25,7 +28,8 @@ pub struct Image {
     alpha: bool,
     bits_per_sample: i32,
     channels: i32,
-    data: Vec<u8>,
+    /// Shared, cloning an image or a notification carrying it does not copy the pixels.
+    data: Arc<[u8]>,
 }
 
 impl Image {
@@ -50,7 +54,7 @@ impl Image {
                 height,
                 bits_per_sample,
                 channels,
-                data,
+                data: data.into(),
                 rowstride: width * channels,
                 alpha,
             })
@@ -77,8 +81,11 @@ impl Image {
         Image::try_from(dyn_img)
     }
 
+    /// The `(iiibiiay)` structure as a value, which holds every pixel byte as a value of its own.
+    ///
+    /// Sending goes through [`ImagePayload`] instead, which writes the pixels as they are.
     #[cfg(all(feature = "images_no_default_features", feature = "zbus"))]
-    pub(crate) fn to_tuple(&self) -> (i32, i32, i32, bool, i32, i32, Vec<u8>) {
+    pub(crate) fn to_structure(&self) -> zbus::zvariant::Structure<'static> {
         (
             self.width,
             self.height,
@@ -86,8 +93,9 @@ impl Image {
             self.alpha,
             self.bits_per_sample,
             self.channels,
-            self.data.clone(),
+            zbus::zvariant::Array::from(&self.data[..]),
         )
+            .into()
     }
 }
 
@@ -209,21 +217,64 @@ impl std::ops::Deref for ImageMessage {
     }
 }
 
+/// Appends the pixels as one fixed size array.
+#[cfg(feature = "dbus")]
+impl Append for ImageMessage {
+    fn append_by_ref(&self, args: &mut IterAppend) {
+        args.append_struct(|fields| {
+            fields.append(self.width);
+            fields.append(self.height);
+            fields.append(self.rowstride);
+            fields.append(self.alpha);
+            fields.append(self.bits_per_sample);
+            fields.append(self.channels);
+            fields.append(&self.data[..]);
+        });
+    }
+}
+
 #[cfg(feature = "dbus")]
-impl From<ImageMessage> for MessageItem {
-    fn from(img_msg: ImageMessage) -> Self {
-        let img = img_msg.0;
-
-        let bytes = img.data.into_iter().map(MessageItem::Byte).collect();
-
-        MessageItem::Struct(vec![
-            MessageItem::Int32(img.width),
-            MessageItem::Int32(img.height),
-            MessageItem::Int32(img.rowstride),
-            MessageItem::Bool(img.alpha),
-            MessageItem::Int32(img.bits_per_sample),
-            MessageItem::Int32(img.channels),
-            MessageItem::Array(MessageItemArray::new(bytes, "ay".into()).unwrap()),
-        ])
+impl Arg for ImageMessage {
+    const ARG_TYPE: ArgType = ArgType::Struct;
+
+    fn signature() -> Signature<'static> {
+        Signature::from("(iiibiiay)")
+    }
+}
+
+/// Serializes an image as the `(iiibiiay)` structure of the image data hint, borrowing the pixels.
+#[cfg(feature = "zbus")]
+pub(crate) struct ImagePayload<'a>(pub &'a Image);
+
+#[cfg(feature = "zbus")]
+impl zbus::zvariant::Type for ImagePayload<'_> {
+    const SIGNATURE: &'static zbus::zvariant::Signature =
+        <(i32, i32, i32, bool, i32, i32, Vec<u8>) as zbus::zvariant::Type>::SIGNATURE;
+}
+
+#[cfg(feature = "zbus")]
+impl serde::Serialize for ImagePayload<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeTuple;
+
+        /// Written as one array, not element by element.
+        struct Bytes<'a>(&'a [u8]);
+
+        impl serde::Serialize for Bytes<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+
+        let image = self.0;
+        let mut fields = serializer.serialize_tuple(7)?;
+        fields.serialize_element(&image.width)?;
+        fields.serialize_element(&image.height)?;
+        fields.serialize_element(&image.rowstride)?;
+        fields.serialize_element(&image.alpha)?;
+        fields.serialize_element(&image.bits_per_sample)?;
+        fields.serialize_element(&image.channels)?;
+        fields.serialize_element(&Bytes(&image.data))?;
+        fields.end()
     }
 }
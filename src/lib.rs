@@ -89,23 +89,27 @@
 //! | `fn image_path(...)`         | ✔︎        | ✔︎                             | ✔︎                                  | ✔︎       |
 //! | `fn hint(...)`               | ✔︎        | -                             | -                                  | -       |
 //! | `fn timeout(...)`            | ✔︎        | ignored                       | ✔︎                                  | ✔︎       |
+//! | `fn enforce_timeout(...)`    | ✔︎        | -                             | -                                  | -       |
 //! | `fn urgency(...)`            | ✔︎        | -                             | ✔︎ (→ `InterruptionLevel`)          | ✔︎       |
 //! | `fn interruption_level(...)` |          |                               | ✔︎                                  |         |
 //! | `fn action(...)`             | ✔︎        | ⚠︎ (labels only)               | ✔︎                                  |         |
 //! | `fn id(...)`                 | ✔︎ (u32)  | ignored                       | ✔︎                                  |         |
 //! | `fn show(...)`               | ✔︎        | ✔︎                             | ✔︎                                  | ✔︎       |
 //! | `fn show_async(...)`         | ✔︎        |                               | ✔︎                                  |         |
+//! | `fn show_scoped(...)`        | ✔︎        |                               |                                    |         |
 //! | `fn schedule(...)`           |          | ✔︎                             | ✔︎                                  |         |
 //!
 //! ## `NotificationHandle`
 //!
-//! | method                    | XDG | macOS (`NSUserNotifictation`) | macOS (`UNUserNotification`) | windows |
-//! |---------------------------|-----|-------------------------------|------------------------------|---------|
-//! | `fn wait_for_action(...)` | ✔︎   | ✔︎                             | ✔︎                            | ✔︎       |
-//! | `fn on_close(...)`        | ✔︎   | ✔︎                             | ✔︎                            | ✔︎       |
-//! | `fn close(...)`           | ✔︎   |                               | ✔︎                            |         |
-//! | `fn update(...)`          | ✔︎   |                               | ✔︎                            |         |
-//! | `fn id(...)`              | ✔︎   |                               | ✔︎                            |         |
+//! | method                              | XDG | macOS (`NSUserNotifictation`) | macOS (`UNUserNotification`) | windows |
+//! |-------------------------------------|-----|-------------------------------|------------------------------|---------|
+//! | `fn wait_for_action(...)`           | ✔︎   | ✔︎                             | ✔︎                            | ✔︎       |
+//! | `fn on_close(...)`                  | ✔︎   | ✔︎                             | ✔︎                            | ✔︎       |
+//! | `fn wait_for_response_timeout(...)` | ✔︎   |                               |                              |         |
+//! | `fn close(...)`                     | ✔︎   |                               | ✔︎                            |         |
+//! | `fn update(...)`                    | ✔︎   |                               | ✔︎                            |         |
+//! | `fn id(...)`                        | ✔︎   |                               | ✔︎                            |         |
+//! | `fn split(...)`                     | ✔︎   |                               |                              |         |
 //!
 //! ## Functions
 //!
@@ -113,6 +117,7 @@
 //! |--------------------------------------------|-----|-------|---------|
 //! | `fn get_capabilities(...)`                 | ✔︎   |   -   |  -      |
 //! | `fn get_server_information(...)`           | ✔︎   |   -   |  -      |
+//! | `struct Notifier`                          | ✔︎   |   -   |  -      |
 //! | `fn set_application(...)`                  | -   |   ✔︎   |  -      |
 //! | `fn get_bundle_identifier_or_default(...)` | -   |   ✔︎   |  -      |
 //!
@@ -198,8 +203,24 @@ pub use macos::{
     not(target_os = "macos")
 ))]
 pub use crate::xdg::{
-    dbus_stack, get_capabilities, get_server_information, handle_action, DbusStack,
-    NotificationHandle,
+    dbus_stack, get_capabilities, get_capabilities_at_bus, get_server_information,
+    get_server_information_at_bus, handle_action, set_dbus_stack, show_to_all_sessions,
+    show_to_user, user_sessions, ActionContext, BusAddress, DbusStack, NotificationBus,
+    NotificationController, NotificationEvents, NotificationEventsIter, NotificationHandle,
+    NotificationService, Notifier, NotifierBuilder, ResponseWaiter, ScopedNotification,
+    ServerRestartPolicy, ServiceEvent, Ticket, UserSession,
+};
+
+#[cfg(all(
+    any(feature = "zbus", all(feature = "dbus", feature = "async")),
+    unix,
+    not(target_os = "macos")
+))]
+pub use crate::xdg::{
+    blocking_mode, get_capabilities_async, get_capabilities_at_bus_async,
+    get_server_information_async, get_server_information_at_bus_async, handle_action_async,
+    set_blocking_mode, show_to_all_sessions_async, show_to_user_async, user_sessions_async,
+    BlockingMode,
 };
 
 // Cross-platform response types (available on all platforms).
@@ -221,13 +242,25 @@ pub use crate::urgency::Urgency;
 pub use crate::{notification::Notification, timeout::Timeout};
 
 #[cfg(all(feature = "images_no_default_features", unix, not(target_os = "macos")))]
-lazy_static! {
-    /// Read once at runtime. Needed for images.
-    pub static ref SPEC_VERSION: miniver::Version =
-        get_server_information()
-        .and_then(|info| info.spec_version.parse::<miniver::Version>())
-        .unwrap_or_else(|_| miniver::Version::new(1,1));
+#[allow(deprecated)] // the expansion of `lazy_static!` refers to the static itself
+mod spec_version {
+    use super::{get_server_information, miniver};
+
+    lazy_static! {
+        /// Read once at runtime, from the default bus.
+        ///
+        /// No longer used by this crate, the name of the image data hint is negotiated
+        /// with the server each notification is sent to.
+        #[deprecated(note = "the spec version is negotiated per server when sending image data")]
+        pub static ref SPEC_VERSION: miniver::Version =
+            get_server_information()
+            .and_then(|info| info.spec_version.parse::<miniver::Version>())
+            .unwrap_or_else(|_| miniver::Version::new(1,1));
+    }
 }
+#[cfg(all(feature = "images_no_default_features", unix, not(target_os = "macos")))]
+#[allow(deprecated)]
+pub use spec_version::SPEC_VERSION;
 
 /// Return value of [`get_server_information()`](crate::get_server_information).
 #[derive(Debug)]
@@ -22,14 +22,17 @@ use crate::{error::*, timeout::Timeout};
 use std::collections::{HashMap, HashSet};
 
 // Returns the name of the current executable, used as a default for `Notification.appname`.
+//
+// Falls back to the name it was started with, and to an empty name, which the spec allows.
 fn exe_name() -> String {
     std::env::current_exe()
-        .unwrap()
-        .file_name()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_owned()
+        .ok()
+        .or_else(|| std::env::args_os().next().map(Into::into))
+        .and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_default()
 }
 
 /// Desktop notification.
@@ -79,6 +82,10 @@ pub struct Notification {
     /// See [`Notification::actions()`] and [`Notification::action()`].
     pub actions: Vec<String>,
 
+    /// See [`Notification::action_with()`].
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub(crate) action_callbacks: xdg::ActionCallbacks,
+
     #[cfg(target_os = "macos")]
     pub(crate) sound_name: Option<String>,
 
@@ -100,6 +107,10 @@ pub struct Notification {
     /// Lifetime of the notification in ms. Often not respected by the server.
     pub timeout: Timeout, // both gnome and galago want allow for -1
 
+    /// See [`Notification::enforce_timeout()`].
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub(crate) enforce_timeout: bool,
+
     /// Interruption level (macOS only; has effect with the `preview-macos-un` feature).
     #[cfg(all(target_os = "macos", feature = "preview-macos-un"))]
     pub(crate) interruption_level: Option<InterruptionLevel>,
@@ -127,17 +138,34 @@ impl Notification {
     /// This is for testing purposes only and will not work with actual implementations.
     #[cfg(all(unix, not(target_os = "macos")))]
     #[doc(hidden)]
-    #[deprecated(note = "this is a test only feature")]
+    #[deprecated(note = "use `Notification::bus()` with a `NotificationBus` instead")]
     pub fn at_bus(sub_bus: &str) -> Notification {
-        let bus = xdg::NotificationBus::custom(sub_bus)
-            .ok_or("invalid subpath")
-            .unwrap();
+        let bus = xdg::NotificationBus::custom(sub_bus).unwrap_or_else(|| {
+            log::warn!("{sub_bus:?} does not make a valid bus name, using the default bus");
+            xdg::NotificationBus::default()
+        });
         Notification {
             bus,
             ..Notification::default()
         }
     }
 
+    /// Sends this notification to a server at another well-known name or object path.
+    ///
+    /// ```no_run
+    /// # use notify_rust::*;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bus = NotificationBus::new("org.example.Notifications", "/org/example/Notifications")?;
+    /// Notification::new().summary("side by side").bus(bus).show()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn bus(&mut self, bus: xdg::NotificationBus) -> &mut Notification {
+        self.bus = bus;
+        self
+    }
+
     /// Overwrite the `appname` field used for the notification.
     ///
     /// # Platform Support
@@ -322,6 +350,16 @@ impl Notification {
         self.hints.iter().chain(self.hints_unique.values())
     }
 
+    /// Whether the notification carries an image, whose hint is named after the spec version of the server.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub(crate) fn has_image_data(&self) -> bool {
+        self.get_hints().any(|hint| match hint {
+            #[cfg(feature = "images_no_default_features")]
+            Hint::ImageData(_) => true,
+            _ => false,
+        })
+    }
+
     /// Set the `timeout`.
     ///
     /// Accepts multiple types that implement `Into<Timeout>`.
@@ -363,6 +401,33 @@ impl Notification {
         self
     }
 
+    /// Closes the notification once its [`timeout`](Self::timeout) passed, if the server did not.
+    ///
+    /// GNOME Shell and others ignore the timeout, the notification stays on screen or piles up in their tray.
+    /// With this, a notification with a [`Timeout::Milliseconds`] the server has not closed in time
+    /// is closed by a call to `CloseNotification`, and waiting for it reports [`CloseReason::Expired`](crate::CloseReason::Expired).
+    /// Servers known to honor the timeout are left to it, and showing the notification again starts over.
+    ///
+    /// One helper thread per connection waits for the timeouts, it does not keep the process alive.
+    ///
+    /// ```no_run
+    /// # use notify_rust::*;
+    /// Notification::new()
+    ///     .summary("Saved")
+    ///     .timeout(Timeout::Milliseconds(3000))
+    ///     .enforce_timeout(true)
+    ///     .show()
+    ///     .unwrap();
+    /// ```
+    ///
+    /// # Platform support
+    /// This only works on XDG Desktops.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn enforce_timeout(&mut self, enforce: bool) -> &mut Notification {
+        self.enforce_timeout = enforce;
+        self
+    }
+
     /// Set the `urgency`.
     ///
     /// Pick between Low, Normal, and Critical.
@@ -454,6 +519,38 @@ impl Notification {
         self
     }
 
+    /// Add an action with a callback that is called every time the user invokes it.
+    ///
+    /// The callback runs on a background thread that listens for the responses to all notifications
+    /// shown over the same connection, see [`Notifier`](crate::Notifier).
+    /// It is dropped once the notification is closed, or once its last handle is dropped:
+    /// some servers never close notifications the user ignored, so keep the handle as long as you care.
+    /// Use `"default"` as `identifier` to react to the user activating the notification itself.
+    ///
+    /// ```no_run
+    /// # use notify_rust::*;
+    /// let handle = Notification::new()
+    ///     .summary("New message")
+    ///     .action_with("reply", "Reply", |ctx| println!("replying to #{}", ctx.id()))
+    ///     .show()
+    ///     .unwrap();
+    /// // the callback is called until the notification is closed or `handle` is dropped
+    /// ```
+    ///
+    /// (XDG only)
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn action_with(
+        &mut self,
+        identifier: &str,
+        label: &str,
+        callback: impl Fn(&xdg::ActionContext) + Send + Sync + 'static,
+    ) -> &mut Notification {
+        self.action(identifier, label);
+        self.action_callbacks
+            .push(identifier, std::sync::Arc::new(callback));
+        self
+    }
+
     /// Set an id ahead of time.
     ///
     /// Setting the id ahead of time allows overriding a known other notification.
@@ -516,16 +613,35 @@ impl Notification {
     ///
     /// Returns a handle to the notification.
     #[cfg(all(unix, not(target_os = "macos")))]
-    #[cfg(feature = "zbus")]
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
     pub async fn show_async(&self) -> Result<xdg::NotificationHandle> {
         xdg::show_notification_async(self).await
     }
 
+    /// Sends the notification to D-Bus and closes it again once the returned guard is dropped.
+    ///
+    /// See [`ScopedNotification`](xdg::ScopedNotification).
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn show_scoped(&self) -> Result<xdg::ScopedNotification> {
+        self.show().map(xdg::NotificationHandle::close_on_drop)
+    }
+
+    /// Async version of [`show_scoped()`](Self::show_scoped).
+    ///
+    /// Dropping the guard does not block, the notification is closed in the background.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn show_scoped_async(&self) -> Result<xdg::ScopedNotification> {
+        self.show_async()
+            .await
+            .map(xdg::NotificationHandle::close_on_drop_in_background)
+    }
+
     /// Sends the notification to D-Bus at the given sub-bus path.
     ///
     /// Returns a handle to the notification.
     #[cfg(all(unix, not(target_os = "macos")))]
-    #[cfg(feature = "zbus")]
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
     // #[cfg(test)]
     pub async fn show_async_at_bus(&self, sub_bus: &str) -> Result<xdg::NotificationHandle> {
         let bus = xdg::NotificationBus::custom(sub_bus).ok_or("invalid subpath")?;
@@ -581,7 +697,9 @@ impl Default for Notification {
             hints: HashSet::new(),
             hints_unique: HashMap::new(),
             actions: Vec::new(),
+            action_callbacks: Default::default(),
             timeout: Timeout::Default,
+            enforce_timeout: false,
             bus: Default::default(),
             id: None,
         }
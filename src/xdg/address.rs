@@ -0,0 +1,40 @@
+/// Where a [`Notifier`](super::Notifier) connects to.
+///
+/// The default is the session bus, which is where desktop notification servers live.
+///
+/// ```no_run
+/// # use notify_rust::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// // a daemon socket that was bind-mounted into a container
+/// let notifier = Notifier::connect(BusAddress::Address(
+///     "unix:path=/run/host/notifications/bus".into(),
+/// ))?;
+/// notifier.show(Notification::new().summary("hello from inside the box"))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BusAddress {
+    /// The user's session bus, located via `$DBUS_SESSION_BUS_ADDRESS`.
+    #[default]
+    Session,
+
+    /// The system-wide bus.
+    System,
+
+    /// A message bus at an explicit [D-Bus address](https://dbus.freedesktop.org/doc/dbus-specification.html#addresses),
+    /// e.g. `unix:path=/run/user/1000/bus`.
+    Address(String),
+
+    /// A direct peer-to-peer connection to a notification daemon listening at the given D-Bus address.
+    ///
+    /// There is no bus daemon in between, so the bus name of the [`Notification`](crate::Notification) is not used for routing.
+    PeerToPeer(String),
+}
+
+impl BusAddress {
+    /// Returns `true` if there is no message bus between us and the notification daemon.
+    pub fn is_peer_to_peer(&self) -> bool {
+        matches!(self, BusAddress::PeerToPeer(_))
+    }
+}
@@ -0,0 +1,257 @@
+//! How the blocking API waits for the async D-Bus connections, without getting in the way of an async runtime it may be called from.
+
+#[cfg(feature = "tokio")]
+use std::sync::OnceLock;
+use std::{
+    cell::Cell,
+    future::Future,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        mpsc, Mutex,
+    },
+    thread,
+};
+
+use crate::response::{NotificationResponse, ResponseHandler};
+
+/// Where the blocking API waits for D-Bus, see [`set_blocking_mode()`].
+///
+/// The blocking API drives the async connections by blocking the calling thread until the call is done.
+/// Within a task of an async runtime that stalls the executor thread,
+/// and with the `tokio` feature it panics because it starts a runtime from within a runtime.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BlockingMode {
+    /// Waits on the calling thread, with the I/O where it does not depend on an executor the caller may run.
+    ///
+    /// The connections do their I/O on threads of their own.
+    /// With the `tokio` feature, calls made within a tokio runtime use a runtime of notify-rust
+    /// with one long-lived thread instead of starting one within the caller's.
+    #[default]
+    Auto,
+
+    /// Always waits on a helper thread.
+    ///
+    /// Costs a thread per call.
+    HelperThread,
+
+    /// Always waits on the calling thread.
+    CallingThread,
+}
+
+/// The mode set by [`set_blocking_mode()`].
+static BLOCKING_MODE: AtomicU8 = AtomicU8::new(0);
+
+/// Selects where the blocking API waits for D-Bus, for the whole process.
+///
+/// The calling thread is blocked either way,
+/// with a helper thread the I/O does not depend on the executor the caller may be running on.
+///
+/// ```no_run
+/// # use notify_rust::*;
+/// # async fn plugin() -> Result<(), Box<dyn std::error::Error>> {
+/// // called from async-std tasks
+/// set_blocking_mode(BlockingMode::HelperThread);
+/// Notification::new().summary("from a plugin").show()?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn set_blocking_mode(mode: BlockingMode) {
+    let mode = match mode {
+        BlockingMode::Auto => 0,
+        BlockingMode::HelperThread => 1,
+        BlockingMode::CallingThread => 2,
+    };
+    BLOCKING_MODE.store(mode, Ordering::SeqCst);
+}
+
+/// The mode set by [`set_blocking_mode()`].
+pub fn blocking_mode() -> BlockingMode {
+    match BLOCKING_MODE.load(Ordering::SeqCst) {
+        1 => BlockingMode::HelperThread,
+        2 => BlockingMode::CallingThread,
+        _ => BlockingMode::Auto,
+    }
+}
+
+thread_local! {
+    /// Set on the threads of notify-rust that run no executor, see [`BlockingMode::Auto`].
+    static OFF_EXECUTOR: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Tells [`BlockingMode::Auto`] that the calling thread runs no executor, for the rest of its life.
+pub(crate) fn mark_off_executor() {
+    OFF_EXECUTOR.with(|off| off.set(true));
+}
+
+/// Runs `future` to completion, on a helper thread if the [`BlockingMode`] asks for it.
+pub(crate) fn block_on<F>(future: F) -> F::Output
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    match blocking_mode() {
+        BlockingMode::Auto if OFF_EXECUTOR.with(Cell::get) => block_on_calling_thread(future),
+        BlockingMode::Auto => block_on_auto(future),
+        BlockingMode::HelperThread => block_on_helper_thread(future),
+        BlockingMode::CallingThread => block_on_calling_thread(future),
+    }
+}
+
+/// [`BlockingMode::CallingThread`], with the executor of the enabled runtime.
+fn block_on_calling_thread<F: Future>(future: F) -> F::Output {
+    #[cfg(feature = "zbus")]
+    return zbus::block_on(future);
+    #[cfg(not(feature = "zbus"))]
+    async_io::block_on(future)
+}
+
+/// [`BlockingMode::Auto`] on a thread that may run an executor.
+#[cfg(not(feature = "tokio"))]
+fn block_on_auto<F>(future: F) -> F::Output
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    // the connections do their I/O on their own threads, the executor of the caller is not needed
+    block_on_calling_thread(future)
+}
+
+/// [`BlockingMode::Auto`] on a thread that may run an executor.
+#[cfg(feature = "tokio")]
+fn block_on_auto<F>(future: F) -> F::Output
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    if tokio::runtime::Handle::try_current().is_err() {
+        return block_on_calling_thread(future);
+    }
+    let Some(runtime) = io_runtime() else {
+        return block_on_helper_thread(future);
+    };
+    // connections opened meanwhile do their I/O on the runtime of notify-rust, not on the blocked one
+    let _io = runtime.enter();
+    futures_lite::future::block_on(future)
+}
+
+/// The runtime calls made within a tokio runtime do their I/O on, `None` if it cannot be started.
+#[cfg(feature = "tokio")]
+fn io_runtime() -> Option<&'static tokio::runtime::Runtime> {
+    static IO_RUNTIME: OnceLock<Option<tokio::runtime::Runtime>> = OnceLock::new();
+    IO_RUNTIME
+        .get_or_init(|| {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .thread_name("notify-rust io")
+                .enable_all()
+                .build();
+            runtime
+                .inspect_err(|error| log::warn!("failed to start the I/O runtime: {error}"))
+                .ok()
+        })
+        .as_ref()
+}
+
+/// [`BlockingMode::HelperThread`], waits for `future` on a thread started for it.
+fn block_on_helper_thread<F>(future: F) -> F::Output
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    // kept out of the helper, so it can still be waited for here if there is no helper
+    let future = Mutex::new(Some(future));
+    let run = || {
+        let future = future.lock().unwrap_or_else(|e| e.into_inner()).take();
+        future.map(block_on_calling_thread)
+    };
+    let output = thread::scope(|scope| {
+        match thread::Builder::new()
+            .name("notify-rust blocking".into())
+            .spawn_scoped(scope, run)
+        {
+            Ok(helper) => helper
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+            Err(error) => {
+                log::warn!(
+                    "waiting on the calling thread, failed to spawn a helper thread: {error}"
+                );
+                run()
+            }
+        }
+    });
+    output.expect("the future is taken exactly once")
+}
+
+/// Like [`block_on()`], for a future that reports to a [`ResponseHandler`].
+///
+/// The future reports to a [`Recorder`], `handler` is called with what it recorded on the calling thread
+/// once the future is done, so it need not be `Send`.
+pub(crate) fn block_on_responding<Fut>(
+    wait: impl FnOnce(Recorder) -> Fut,
+    handler: impl ResponseHandler,
+) -> Fut::Output
+where
+    Fut: Future + Send,
+    Fut::Output: Send,
+{
+    let (recorder, recorded) = mpsc::channel();
+    let output = block_on(wait(Recorder(recorder)));
+    if let Ok(response) = recorded.try_recv() {
+        handler.call(&response);
+    }
+    output
+}
+
+/// Keeps the response for [`block_on_responding()`].
+pub(crate) struct Recorder(mpsc::Sender<NotificationResponse>);
+
+impl ResponseHandler for Recorder {
+    fn call(self, response: &NotificationResponse) {
+        let _ = self.0.send(response.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{block_on, block_on_helper_thread, mark_off_executor};
+    use std::thread;
+
+    fn waiting_thread() -> Option<String> {
+        block_on(async { thread::current().name().map(String::from) })
+    }
+
+    #[test]
+    fn auto_waits_on_the_calling_thread() {
+        for off_executor in [false, true] {
+            let name = format!("off executor: {off_executor}");
+            thread::Builder::new()
+                .name(name.clone())
+                .spawn(move || {
+                    if off_executor {
+                        mark_off_executor();
+                    }
+                    assert_eq!(waiting_thread(), Some(name));
+                })
+                .unwrap()
+                .join()
+                .unwrap();
+        }
+        assert_eq!(
+            block_on_helper_thread(async { thread::current().name().map(String::from) }).as_deref(),
+            Some("notify-rust blocking")
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn auto_spawns_onto_a_runtime_of_its_own_within_tokio() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        // the task could never run on the runtime blocked by the call
+        let spawned =
+            runtime.block_on(async { block_on(async { tokio::spawn(async { 7 }).await }) });
+        assert_eq!(spawned.unwrap(), 7);
+    }
+}
@@ -1,4 +1,5 @@
-use crate::xdg::NOTIFICATION_DEFAULT_BUS;
+use crate::error::*;
+use crate::xdg::{NOTIFICATION_DEFAULT_BUS, NOTIFICATION_OBJECTPATH};
 
 fn skip_first_slash(s: &str) -> &str {
     if let Some('/') = s.chars().next() {
@@ -11,28 +12,19 @@ fn skip_first_slash(s: &str) -> &str {
 use std::path::PathBuf;
 
 type BusNameType = std::borrow::Cow<'static, str>;
+type ObjectPathType = std::borrow::Cow<'static, str>;
 
+/// The well-known bus name and object path a notification server is reached at.
+///
+/// The default is `org.freedesktop.Notifications` at `/org/freedesktop/Notifications`.
 #[derive(Clone, Debug)]
-pub struct NotificationBus(BusNameType);
+pub struct NotificationBus(BusNameType, ObjectPathType);
 
 impl Default for NotificationBus {
-    #[cfg(feature = "zbus")]
     fn default() -> Self {
         Self(
-            zbus::names::WellKnownName::from_static_str(NOTIFICATION_DEFAULT_BUS)
-                .unwrap()
-                .to_string()
-                .into(),
-        )
-    }
-
-    #[cfg(all(feature = "dbus", not(feature = "zbus")))]
-    fn default() -> Self {
-        Self(
-            dbus::strings::BusName::from_slice(NOTIFICATION_DEFAULT_BUS)
-                .unwrap()
-                .to_string()
-                .into(),
+            NOTIFICATION_DEFAULT_BUS.into(),
+            NOTIFICATION_OBJECTPATH.into(),
         )
     }
 }
@@ -49,20 +41,48 @@ impl NotificationBus {
         .into()
     }
 
-    #[cfg(feature = "zbus")]
-    pub fn custom(custom_path: &str) -> Option<Self> {
-        let name =
-            zbus::names::WellKnownName::try_from(Self::namespaced_custom(custom_path)?).ok()?;
-        Some(Self(name.to_string().into()))
+    /// Targets the well-known `name` at the object `path`.
+    ///
+    /// Both are validated by every enabled stack, since either may end up sending the message.
+    pub fn new(name: &str, path: &str) -> Result<Self> {
+        #[cfg(feature = "zbus")]
+        {
+            zbus::names::WellKnownName::try_from(name).map_err(zbus::Error::from)?;
+            zbus::zvariant::ObjectPath::try_from(path).map_err(zbus::Error::from)?;
+        }
+        #[cfg(feature = "dbus")]
+        {
+            let name = dbus::strings::BusName::new(name).map_err(ErrorKind::Conversion)?;
+            if name.starts_with(':') {
+                return Err(
+                    ErrorKind::Conversion(format!("{name:?} is not a well-known name")).into(),
+                );
+            }
+            dbus::strings::Path::new(path).map_err(ErrorKind::Conversion)?;
+        }
+        Ok(Self(name.to_owned().into(), path.to_owned().into()))
     }
 
-    #[cfg(all(feature = "dbus", not(feature = "zbus")))]
+    /// Targets `de.hoodie.Notification.<custom_path>` at the default object path.
     pub fn custom(custom_path: &str) -> Option<Self> {
-        let name = dbus::strings::BusName::new(Self::namespaced_custom(custom_path)?).ok()?;
-        Some(Self(name.to_string().into()))
+        Self::new(
+            &Self::namespaced_custom(custom_path)?,
+            NOTIFICATION_OBJECTPATH,
+        )
+        .ok()
+    }
+
+    /// The well-known bus name.
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// The object path.
+    pub fn path(&self) -> &str {
+        &self.1
     }
 
-    pub fn into_name(self) -> BusNameType {
-        self.0
+    pub(crate) fn into_parts(self) -> (BusNameType, ObjectPathType) {
+        (self.0, self.1)
     }
 }
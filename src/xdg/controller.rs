@@ -0,0 +1,214 @@
+//! Controlling a notification from one place while waiting for its responses in another.
+
+use std::{sync::Arc, time::Duration};
+
+#[cfg(feature = "zbus")]
+use super::blocking::block_on;
+#[cfg(feature = "dbus")]
+use super::dbus_rs;
+#[cfg(feature = "zbus")]
+use super::zbus_rs;
+
+use super::{NotificationEvents, NotificationHandle};
+use crate::{
+    error::Result,
+    notification::Notification,
+    response::{CloseHandler, NotificationResponse, ResponseHandler},
+};
+
+/// Updates and closes a shown notification from any thread.
+///
+/// Returned by [`NotificationHandle::split()`], together with the [`ResponseWaiter`].
+/// Clones control the same notification.
+///
+/// ```no_run
+/// # use notify_rust::*;
+/// # use std::{thread, time::Duration};
+/// let (controller, waiter) = Notification::new()
+///     .summary("Downloading")
+///     .body("0%")
+///     .show()
+///     .unwrap()
+///     .split();
+///
+/// let progress = thread::spawn(move || {
+///     for percent in (10..=100).step_by(10) {
+///         thread::sleep(Duration::from_secs(1));
+///         controller
+///             .update(|notification| {
+///                 notification.body(&format!("{percent}%"));
+///             })
+///             .unwrap();
+///     }
+///     controller.close().unwrap();
+/// });
+///
+/// waiter.wait_for_response(|response: &NotificationResponse| println!("{response:?}")).unwrap();
+/// progress.join().unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct NotificationController {
+    inner: Arc<NotificationControllerInner>,
+}
+
+#[derive(Debug)]
+enum NotificationControllerInner {
+    #[cfg(feature = "dbus")]
+    Dbus(dbus_rs::DbusController),
+
+    #[cfg(feature = "zbus")]
+    Zbus(zbus_rs::ZbusController),
+}
+
+impl NotificationController {
+    /// Returns the notification's id.
+    ///
+    /// This follows the notification if it is shown again, e.g. after the server restarted.
+    pub fn id(&self) -> u32 {
+        match *self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationControllerInner::Dbus(ref inner) => inner.id(),
+            #[cfg(feature = "zbus")]
+            NotificationControllerInner::Zbus(ref inner) => inner.id(),
+        }
+    }
+
+    /// Applies `change` to the notification and replaces the shown one with it.
+    ///
+    /// Changes made through clones of this controller accumulate.
+    pub fn update(&self, change: impl FnOnce(&mut Notification)) -> Result<()> {
+        match *self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationControllerInner::Dbus(ref inner) => inner.update(change),
+            #[cfg(feature = "zbus")]
+            NotificationControllerInner::Zbus(ref inner) => {
+                // `change` need not be `Send`, it is applied here rather than where the I/O happens
+                let changed = inner.change(change);
+                block_on(inner.show_again(changed))
+            }
+        }
+    }
+
+    /// Async version of [`update()`](Self::update).
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn update_async(&self, change: impl FnOnce(&mut Notification)) -> Result<()> {
+        match *self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationControllerInner::Dbus(ref inner) => inner.update_async(change).await,
+            #[cfg(feature = "zbus")]
+            NotificationControllerInner::Zbus(ref inner) => inner.update(change).await,
+        }
+    }
+
+    /// Closes the notification.
+    ///
+    /// The [`ResponseWaiter`] then receives [`CloseReason::CloseAction`](crate::CloseReason::CloseAction).
+    pub fn close(&self) -> Result<()> {
+        match *self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationControllerInner::Dbus(ref inner) => inner.close(),
+            #[cfg(feature = "zbus")]
+            NotificationControllerInner::Zbus(ref inner) => block_on(inner.close()),
+        }
+    }
+
+    /// Async version of [`close()`](Self::close).
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn close_async(&self) -> Result<()> {
+        match *self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationControllerInner::Dbus(ref inner) => inner.close_async().await,
+            #[cfg(feature = "zbus")]
+            NotificationControllerInner::Zbus(ref inner) => inner.close().await,
+        }
+    }
+}
+
+/// Waits for the responses to a shown notification.
+///
+/// Returned by [`NotificationHandle::split()`], together with the [`NotificationController`].
+/// It keeps the connection of the original handle alive.
+#[derive(Debug)]
+pub struct ResponseWaiter {
+    handle: NotificationHandle,
+}
+
+impl ResponseWaiter {
+    /// Returns the notification's id.
+    pub fn id(&self) -> u32 {
+        self.handle.id()
+    }
+
+    /// See [`NotificationHandle::wait_for_response()`].
+    pub fn wait_for_response(self, handler: impl ResponseHandler) -> Result<()> {
+        self.handle.wait_for_response(handler)
+    }
+
+    /// See [`NotificationHandle::wait_for_response_timeout()`].
+    pub fn wait_for_response_timeout(&self, timeout: Duration) -> Option<NotificationResponse> {
+        self.handle.wait_for_response_timeout(timeout)
+    }
+
+    /// See [`NotificationHandle::wait_for_response_async()`].
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn wait_for_response_async(&self) -> Result<Option<NotificationResponse>> {
+        self.handle.wait_for_response_async().await
+    }
+
+    /// See [`NotificationHandle::wait_for_response_timeout_async()`].
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn wait_for_response_timeout_async(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<NotificationResponse>> {
+        self.handle.wait_for_response_timeout_async(timeout).await
+    }
+
+    /// See [`NotificationHandle::close_on_timeout()`].
+    pub fn close_on_timeout(&mut self, close: bool) -> &mut ResponseWaiter {
+        self.handle.close_on_timeout(close);
+        self
+    }
+
+    /// See [`NotificationHandle::events()`].
+    pub fn events(&self) -> NotificationEvents {
+        self.handle.events()
+    }
+
+    /// See [`NotificationHandle::on_close()`].
+    pub fn on_close<A>(self, handler: impl CloseHandler<A>) {
+        self.handle.on_close(handler);
+    }
+}
+
+impl NotificationHandle {
+    /// Splits the handle into a [`NotificationController`] that can be cloned and sent to other threads,
+    /// and a [`ResponseWaiter`] that waits for the user.
+    ///
+    /// Waiting consumes or borrows the handle, so it cannot be updated or closed at the same time;
+    /// after splitting, one thread can wait while others keep the notification up to date.
+    pub fn split(self) -> (NotificationController, ResponseWaiter) {
+        let inner = match self.inner {
+            #[cfg(feature = "dbus")]
+            super::NotificationHandleInner::Dbus(ref inner) => {
+                NotificationControllerInner::Dbus(inner.controller())
+            }
+            #[cfg(feature = "zbus")]
+            super::NotificationHandleInner::Zbus(ref inner) =>
+            {
+                #[cfg(feature = "zbus")]
+                NotificationControllerInner::Zbus(inner.controller())
+            }
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            super::NotificationHandleInner::DbusAsync(ref inner) => {
+                NotificationControllerInner::Dbus(inner.controller())
+            }
+        };
+        (
+            NotificationController {
+                inner: Arc::new(inner),
+            },
+            ResponseWaiter { handle: self },
+        )
+    }
+}
@@ -0,0 +1,1421 @@
+//! The async API on top of dbus-rs.
+//!
+//! A [`dbus::nonblock::SyncConnection`] is driven by a thread of its own, which waits for its socket
+//! with [`mio`] and hands replies and signals over to the tasks waiting for them.
+//! None of this depends on the executor the tasks run on.
+
+use dbus::{
+    arg::messageitem::MessageItem,
+    channel::{Channel, MatchingReceiver, Sender, Token},
+    message::{MatchRule, MessageType},
+    nonblock::{NonblockReply, Process, SyncConnection},
+    Message,
+};
+
+use std::{
+    fmt, io,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        mpsc, Arc, Mutex, OnceLock, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use super::{
+    blocking,
+    bus::NotificationBus,
+    dbus_rs::{
+        self, DbusController, DbusNotificationHandle, OwnerChange, Restart, SharedConnection,
+        Signal, DEFAULT_CALL_TIMEOUT,
+    },
+    dispatcher::Dispatcher,
+    events::ActivationTokenSlot,
+    expiry::{Expiries, ExpiringServer},
+    queue::Queue,
+    session::{self, LogindSession},
+    timer, BusAddress, ServerInformation, ServerRestartPolicy, SpecVersions,
+    NOTIFICATION_INTERFACE,
+};
+use crate::{
+    error::*,
+    miniver::Version,
+    notification::Notification,
+    response::{CloseReason, NotificationResponse, ResponseHandler},
+    xdg,
+};
+
+/// A connection whose I/O happens on a thread of its own, so it can be awaited from any executor.
+///
+/// Clones share the connection, the thread stops once the last clone is dropped.
+#[derive(Clone)]
+pub(crate) struct AsyncConnection {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    connection: Arc<SyncConnection>,
+    shared: Arc<Shared>,
+    address: BusAddress,
+    /// Routes the responses to the notifications with callbacks, started once the first one is shown.
+    dispatcher: OnceLock<Arc<Dispatcher>>,
+}
+
+/// What the tasks and the driver thread of a connection share.
+#[derive(Default)]
+struct Shared {
+    /// Gets the driver thread out of its wait, set once it is running.
+    waker: OnceLock<mio::Waker>,
+    stopped: AtomicBool,
+    /// Closed once the connection is gone, so nobody waits for a reply or signal forever.
+    queues: Mutex<Vec<Weak<Queue<Message>>>>,
+    /// The match rules added so far, they are kept as long as the connection, the bus drops them along with it.
+    rules: Mutex<Vec<String>>,
+    spec_versions: SpecVersions,
+    expiries: Arc<Expiries>,
+}
+
+impl Shared {
+    fn wake(&self) {
+        if let Some(waker) = self.waker.get() {
+            if let Err(error) = waker.wake() {
+                log::warn!("failed to wake the dbus thread: {error}");
+            }
+        }
+    }
+
+    fn track(&self, queue: &Arc<Queue<Message>>) {
+        let mut queues = self.queues.lock().unwrap_or_else(|e| e.into_inner());
+        queues.retain(|queue| queue.strong_count() > 0);
+        queues.push(Arc::downgrade(queue));
+    }
+
+    fn close_queues(&self) {
+        let queues = std::mem::take(&mut *self.queues.lock().unwrap_or_else(|e| e.into_inner()));
+        for queue in queues.iter().filter_map(Weak::upgrade) {
+            queue.close();
+        }
+    }
+}
+
+impl AsyncConnection {
+    /// Connects to `address` from a new thread, which then drives the connection.
+    pub(crate) async fn connect(address: &BusAddress) -> Result<AsyncConnection> {
+        let (address, opened) = (address.clone(), address.clone());
+        let shared = Arc::new(Shared::default());
+        let ready = Arc::new(Queue::default());
+
+        let (driver, connected) = (shared.clone(), ready.clone());
+        std::thread::Builder::new()
+            .name("notify-rust dbus".into())
+            .spawn(move || match open(&address, &driver) {
+                Ok((connection, mut poll)) => {
+                    connected.push(Ok(connection.clone()));
+                    drive(&connection, &mut poll, &driver);
+                }
+                Err(error) => {
+                    connected.push(Err(error));
+                }
+            })
+            .map_err(|e| ErrorKind::Msg(e.to_string()))?;
+
+        let connection = ready
+            .pop()
+            .await
+            .ok_or_else(|| ErrorKind::Msg("dbus thread failed to start".into()))??;
+        Ok(AsyncConnection {
+            inner: Arc::new(Inner {
+                connection,
+                shared,
+                address: opened,
+                dispatcher: OnceLock::new(),
+            }),
+        })
+    }
+
+    pub(crate) fn address(&self) -> &BusAddress {
+        &self.inner.address
+    }
+
+    /// Closes the notifications shown over this connection that enforce their timeout.
+    pub(crate) fn expiries(&self) -> &Arc<Expiries> {
+        &self.inner.shared.expiries
+    }
+
+    /// `false` for peer-to-peer connections, which have no bus and no unique name.
+    pub(crate) fn is_bus(&self) -> bool {
+        self.channel().unique_name().is_some()
+    }
+
+    fn channel(&self) -> &Channel {
+        (*self.inner.connection).as_ref()
+    }
+
+    /// Sends a method call and waits until the reply arrives or `timeout` has passed.
+    ///
+    /// Dropping the future before that forgets about the reply.
+    pub(crate) async fn call(&self, message: Message, timeout: Duration) -> Result<Message> {
+        Ok(self.call_raw(message, timeout).await??)
+    }
+
+    /// Like [`call()`](Self::call), but leaves error replies to the caller.
+    async fn call_raw(
+        &self,
+        message: Message,
+        timeout: Duration,
+    ) -> Result<std::result::Result<Message, dbus::Error>> {
+        let reply = Arc::new(Queue::default());
+        self.inner.shared.track(&reply);
+        let slot = reply.clone();
+        let token = self
+            .inner
+            .connection
+            .send_with_reply(
+                message,
+                Box::new(move |message, _| {
+                    slot.push(message);
+                }),
+            )
+            .map_err(|()| disconnected())?;
+        let _pending = PendingReply {
+            connection: &self.inner.connection,
+            token,
+        };
+        let mut reply = match timer::timeout(timeout, reply.pop()).await {
+            Some(Some(reply)) => reply,
+            Some(None) => return Err(disconnected().into()),
+            None => return Err(ErrorKind::CallTimeout.into()),
+        };
+        Ok(match reply.as_result().err() {
+            Some(error) => Err(error),
+            None => Ok(reply),
+        })
+    }
+
+    /// Sends a message without waiting for a reply.
+    pub(crate) fn send(&self, message: Message) -> Result<()> {
+        self.inner
+            .connection
+            .send(message)
+            .map(drop)
+            .map_err(|()| disconnected().into())
+    }
+
+    /// Subscribes to the signals matching `rule`, the bus is asked for them once per connection.
+    ///
+    /// `local` picks the signals for the returned [`Signals`] out of everything the connection receives,
+    /// it is usually wider than `rule`, which the bus matches senders against by their well-known name too.
+    pub(crate) async fn subscribe(
+        &self,
+        rule: String,
+        local: MatchRule<'static>,
+    ) -> Result<Signals> {
+        let queue = Arc::new(Queue::default());
+        self.inner.shared.track(&queue);
+        let sink = queue.clone();
+        // receiving before the bus is asked to send anything, so nothing gets lost
+        let token = self
+            .inner
+            .connection
+            .start_receive(local, Box::new(move |message, _| sink.push(message)));
+        let signals = Signals {
+            connection: self.clone(),
+            token,
+            queue,
+        };
+        self.add_match(rule).await?;
+        Ok(signals)
+    }
+
+    /// Asks the bus for the signals matching `rule`, unless that was done already.
+    async fn add_match(&self, rule: String) -> Result<()> {
+        // peer-to-peer connections have no unique name and receive every signal anyway
+        if !self.is_bus() || self.has_match(&rule) {
+            return Ok(());
+        }
+        self.call(
+            dbus_rs::bus_message("AddMatch", &rule)?,
+            DEFAULT_CALL_TIMEOUT,
+        )
+        .await?;
+        let mut rules = self
+            .inner
+            .shared
+            .rules
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if !rules.contains(&rule) {
+            rules.push(rule);
+        }
+        Ok(())
+    }
+
+    fn has_match(&self, rule: &str) -> bool {
+        self.inner
+            .shared
+            .rules
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .any(|added| added == rule)
+    }
+
+    /// The dispatcher for the notifications with callbacks shown on this connection.
+    async fn dispatcher(&self) -> Result<Arc<Dispatcher>> {
+        if let Some(dispatcher) = self.inner.dispatcher.get() {
+            return Ok(dispatcher.clone());
+        }
+        let dispatcher = spawn_dispatcher(self).await?;
+        // somebody else may have been quicker, theirs is used then
+        Ok(self.inner.dispatcher.get_or_init(|| dispatcher).clone())
+    }
+
+    /// Returns the unique name of the current owner of `name`, if any.
+    async fn name_owner(&self, name: &str, timeout: Duration) -> Result<Option<String>> {
+        if !self.is_bus() {
+            return Ok(None);
+        }
+        let reply = self
+            .call_raw(dbus_rs::bus_message("GetNameOwner", name)?, timeout)
+            .await?;
+        dbus_rs::name_owner_reply(reply)
+    }
+}
+
+impl fmt::Debug for AsyncConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncConnection")
+            .field("unique_name", &self.channel().unique_name())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        self.shared.wake();
+    }
+}
+
+fn disconnected() -> dbus::Error {
+    dbus::Error::new_custom(
+        "org.freedesktop.DBus.Error.Disconnected",
+        "the connection is gone",
+    )
+}
+
+/// How long the thread of a [`Following`] waits for a response before it looks for calls to make.
+const FOLLOWING_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Tells the socket of the connection from the waker of [`Shared`].
+const SOCKET: mio::Token = mio::Token(0);
+const WAKER: mio::Token = mio::Token(1);
+
+/// Opens the connection on the driver thread, along with what the thread waits with.
+fn open(address: &BusAddress, shared: &Arc<Shared>) -> Result<(Arc<SyncConnection>, mio::Poll)> {
+    let poll = mio::Poll::new().map_err(|e| ErrorKind::Msg(e.to_string()))?;
+    let waker =
+        mio::Waker::new(poll.registry(), WAKER).map_err(|e| ErrorKind::Msg(e.to_string()))?;
+    let _ = shared.waker.set(waker);
+    let mut channel = dbus_rs::open_channel(address)?;
+    channel.set_watch_enabled(true);
+    let mut connection = SyncConnection::from(channel);
+    let driver = shared.clone();
+    // called when a message could not be written right away
+    connection.set_waker(Some(Box::new(move || {
+        driver.wake();
+        Ok(())
+    })));
+    // every subscription gets its own copy of a signal, not just the first one that matches
+    connection.set_signal_match_mode(true);
+    Ok((Arc::new(connection), poll))
+}
+
+/// Reads and writes until the connection is dropped or lost.
+fn drive(connection: &SyncConnection, poll: &mut mio::Poll, shared: &Shared) {
+    let channel: &Channel = connection.as_ref();
+    let socket = channel.watch().fd;
+    let mut events = mio::Events::with_capacity(2);
+    let mut registered = false;
+    while !shared.stopped.load(Ordering::SeqCst) {
+        if channel.read_write(Some(Duration::ZERO)).is_err() {
+            break;
+        }
+        connection.process_all();
+        let interest = if channel.has_messages_to_send() {
+            mio::Interest::READABLE | mio::Interest::WRITABLE
+        } else {
+            mio::Interest::READABLE
+        };
+        // registering again reports the socket right away if libdbus left something unread
+        let mut source = mio::unix::SourceFd(&socket);
+        let watched = if registered {
+            poll.registry().reregister(&mut source, SOCKET, interest)
+        } else {
+            poll.registry().register(&mut source, SOCKET, interest)
+        };
+        if let Err(error) = watched {
+            log::warn!("failed to watch the dbus socket: {error}");
+            break;
+        }
+        registered = true;
+        match poll.poll(&mut events, None) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => {
+                log::warn!("failed to wait for the dbus socket: {error}");
+                break;
+            }
+        }
+    }
+    if registered {
+        let _ = poll
+            .registry()
+            .deregister(&mut mio::unix::SourceFd(&socket));
+    }
+    // sends what was left, e.g. the close of a dropped notification
+    channel.flush();
+    shared.close_queues();
+}
+
+/// Forgets about a reply nobody waits for anymore.
+struct PendingReply<'a> {
+    connection: &'a SyncConnection,
+    token: Token,
+}
+
+impl Drop for PendingReply<'_> {
+    fn drop(&mut self) {
+        self.connection.cancel_reply(self.token);
+    }
+}
+
+/// Signals received for a subscription, the match rule stays with the connection once this is dropped.
+pub(crate) struct Signals {
+    connection: AsyncConnection,
+    token: Token,
+    queue: Arc<Queue<Message>>,
+}
+
+impl Signals {
+    /// The next signal, `None` once the connection is gone.
+    pub(crate) async fn next(&mut self) -> Option<Message> {
+        self.queue.pop().await
+    }
+}
+
+impl fmt::Debug for Signals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signals").finish_non_exhaustive()
+    }
+}
+
+impl Drop for Signals {
+    fn drop(&mut self) {
+        // in case the driver is just handing it a signal and puts the filter back afterwards
+        self.queue.close();
+        self.connection.inner.connection.stop_receive(self.token);
+    }
+}
+
+/// Subscribes to the signals from the object path of `bus`, see [`dbus_rs::responses_rule()`].
+async fn subscribe_responses(
+    connection: &AsyncConnection,
+    bus: &NotificationBus,
+) -> Result<Signals> {
+    connection
+        .subscribe(dbus_rs::responses_rule(bus), dbus_rs::responses_match(bus))
+        .await
+}
+
+/// Subscribes to owner changes of the well-known `name`.
+async fn subscribe_owner_changes(connection: &AsyncConnection, name: &str) -> Result<Signals> {
+    connection
+        .subscribe(
+            dbus_rs::owner_changes_rule(name),
+            dbus_rs::owner_changes_match(),
+        )
+        .await
+}
+
+/// Subscribes to the responses of the server, then sends the notification.
+///
+/// Signals the server sends right away, e.g. because of a tiny timeout, are queued
+/// instead of getting lost before anyone listens.
+async fn send_subscribed(
+    notification: &Notification,
+    id: u32,
+    connection: &AsyncConnection,
+    bus: &NotificationBus,
+    timeout: Duration,
+) -> Result<(u32, Option<String>, Signals)> {
+    let responses = subscribe_responses(connection, bus).await?;
+    let (id, server) = notify(notification, id, connection, bus, timeout).await?;
+    Ok((id, server, responses))
+}
+
+/// Sends the notification, returns the id the server handed out and the unique name of the server.
+async fn notify(
+    notification: &Notification,
+    id: u32,
+    connection: &AsyncConnection,
+    bus: &NotificationBus,
+    timeout: Duration,
+) -> Result<(u32, Option<String>)> {
+    let spec_version = spec_version(notification, connection, bus, timeout).await;
+    let message = dbus_rs::notify_message(notification, id, bus.clone(), spec_version)?;
+    Ok(dbus_rs::notify_reply(
+        &connection.call(message, timeout).await?,
+    ))
+}
+
+/// The spec version of the server at `bus`, which decides the name of the image data hint.
+///
+/// Only asked for when `notification` carries image data.
+async fn spec_version(
+    notification: &Notification,
+    connection: &AsyncConnection,
+    bus: &NotificationBus,
+    timeout: Duration,
+) -> Version {
+    if !notification.has_image_data() {
+        return xdg::FALLBACK_SPEC_VERSION;
+    }
+    let ask = server_information(connection, bus.clone(), timeout);
+    // a peer is the server, on a bus the name may have changed hands since
+    let server = match connection.name_owner(bus.name(), timeout).await {
+        Ok(Some(owner)) => owner,
+        Ok(None) if !connection.is_bus() => String::new(),
+        Ok(None) => return xdg::negotiated_spec_version(ask.await),
+        Err(error) => return xdg::negotiated_spec_version(Err(error)),
+    };
+    let versions = &connection.inner.shared.spec_versions;
+    match versions.get(&server) {
+        Some(version) => version,
+        None => versions.negotiate(server, ask.await),
+    }
+}
+
+/// The id to show a notification again with, see [`dbus_rs::replaces_id()`].
+async fn replaces_id(
+    connection: &AsyncConnection,
+    bus: &NotificationBus,
+    id: u32,
+    server: Option<&str>,
+    restart_policy: ServerRestartPolicy,
+    timeout: Duration,
+) -> Result<u32> {
+    let Some(server) = server else {
+        return Ok(id);
+    };
+    let owner = connection.name_owner(bus.name(), timeout).await?;
+    if owner.as_deref() == Some(server) {
+        return Ok(id);
+    }
+    match restart_policy {
+        ServerRestartPolicy::Vanish => Err(ErrorKind::ServerVanished.into()),
+        ServerRestartPolicy::Reshow => Ok(0),
+    }
+}
+
+pub(crate) async fn connect_and_send_notification(
+    notification: &Notification,
+) -> Result<NonblockNotificationHandle> {
+    let bus = notification.bus.clone();
+    connect_and_send_notification_at_bus(notification, bus).await
+}
+
+pub(crate) async fn connect_and_send_notification_at_bus(
+    notification: &Notification,
+    bus: NotificationBus,
+) -> Result<NonblockNotificationHandle> {
+    let mut notification = notification.clone();
+    notification.bus = bus;
+    let connection = if notification.action_callbacks.is_empty() {
+        AsyncConnection::connect(&BusAddress::Session).await?
+    } else {
+        session_connection().await?
+    };
+    show(
+        &connection,
+        &notification,
+        DEFAULT_CALL_TIMEOUT,
+        Default::default(),
+    )
+    .await
+}
+
+/// Sends the notification over `connection`, which the returned handle keeps using.
+pub(crate) async fn show(
+    connection: &AsyncConnection,
+    notification: &Notification,
+    call_timeout: Duration,
+    restart_policy: ServerRestartPolicy,
+) -> Result<NonblockNotificationHandle> {
+    let dispatcher = if notification.action_callbacks.is_empty() {
+        None
+    } else {
+        Some(connection.dispatcher().await?)
+    };
+    let (id, server, responses) = send_subscribed(
+        notification,
+        notification.id.unwrap_or(0),
+        connection,
+        &notification.bus,
+        call_timeout,
+    )
+    .await?;
+    if let Some(ref dispatcher) = dispatcher {
+        dispatcher.register(id, notification, server.clone());
+    }
+    Ok(NonblockNotificationHandle::new(
+        id,
+        connection.clone(),
+        notification.clone(),
+        server,
+        call_timeout,
+        restart_policy,
+        dispatcher,
+        responses,
+    ))
+}
+
+/// The session bus connection shared by all notifications with callbacks that are shown asynchronously.
+static SESSION_CONNECTION: Mutex<Option<AsyncConnection>> = Mutex::new(None);
+
+async fn session_connection() -> Result<AsyncConnection> {
+    if let Some(ref shared) = *SESSION_CONNECTION.lock().unwrap_or_else(|e| e.into_inner()) {
+        return Ok(shared.clone());
+    }
+    let connection = AsyncConnection::connect(&BusAddress::Session).await?;
+    Ok(SESSION_CONNECTION
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        // somebody else may have been quicker
+        .get_or_insert(connection)
+        .clone())
+}
+
+/// Routes the responses to all notifications on `connection` to the returned [`Dispatcher`].
+///
+/// The callbacks run on the driver thread of the connection.
+async fn spawn_dispatcher(connection: &AsyncConnection) -> Result<Arc<Dispatcher>> {
+    let dispatcher = Arc::new(Dispatcher::default());
+    let routed = Arc::downgrade(&dispatcher);
+    connection.inner.connection.start_receive(
+        MatchRule::new()
+            .with_type(MessageType::Signal)
+            .with_interface(NOTIFICATION_INTERFACE),
+        Box::new(move |message, _| match routed.upgrade() {
+            Some(dispatcher) => {
+                dbus_rs::dispatch(&dispatcher, &message);
+                true
+            }
+            None => false,
+        }),
+    );
+    for member in ["ActionInvoked", "ActivationToken", "NotificationClosed"] {
+        connection
+            .add_match(format!(
+                "interface='{NOTIFICATION_INTERFACE}',member='{member}'"
+            ))
+            .await?;
+    }
+    Ok(dispatcher)
+}
+
+pub(crate) async fn close(
+    connection: &AsyncConnection,
+    id: u32,
+    bus: NotificationBus,
+    timeout: Duration,
+) -> Result<()> {
+    connection
+        .call(dbus_rs::close_message(id, bus)?, timeout)
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn capabilities(
+    connection: &AsyncConnection,
+    bus: NotificationBus,
+    timeout: Duration,
+) -> Result<Vec<String>> {
+    let message = dbus_rs::build_message("GetCapabilities", bus)?;
+    Ok(dbus_rs::capabilities_reply(
+        &connection.call(message, timeout).await?,
+    ))
+}
+
+pub(crate) async fn server_information(
+    connection: &AsyncConnection,
+    bus: NotificationBus,
+    timeout: Duration,
+) -> Result<ServerInformation> {
+    let message = dbus_rs::build_message("GetServerInformation", bus)?;
+    Ok(dbus_rs::server_information_reply(
+        &connection.call(message, timeout).await?,
+    ))
+}
+
+pub(crate) async fn get_capabilities_at_bus(bus: NotificationBus) -> Result<Vec<String>> {
+    let connection = AsyncConnection::connect(&BusAddress::Session).await?;
+    capabilities(&connection, bus, DEFAULT_CALL_TIMEOUT).await
+}
+
+pub(crate) async fn get_server_information_at_bus(
+    bus: NotificationBus,
+) -> Result<ServerInformation> {
+    let connection = AsyncConnection::connect(&BusAddress::Session).await?;
+    server_information(&connection, bus, DEFAULT_CALL_TIMEOUT).await
+}
+
+pub(crate) async fn list_graphical_sessions() -> Result<Vec<LogindSession>> {
+    let connection = AsyncConnection::connect(&BusAddress::System).await?;
+    let message = Message::new_method_call(
+        session::LOGIND_BUS,
+        session::LOGIND_OBJECTPATH,
+        session::LOGIND_MANAGER_INTERFACE,
+        "ListSessions",
+    )
+    .map_err(ErrorKind::Conversion)?;
+    let reply = connection.call(message, DEFAULT_CALL_TIMEOUT).await?;
+    let sessions: Vec<dbus_rs::SessionRow> = reply.read1().map_err(dbus::Error::from)?;
+
+    let mut graphical = Vec::new();
+    for (id, uid, user, _seat, path) in sessions {
+        let active: bool = session_property(&connection, &path, "Active").await?;
+        let session_type: String = session_property(&connection, &path, "Type").await?;
+        let class: String = session_property(&connection, &path, "Class").await?;
+        if session::is_graphical(active, &session_type, &class) {
+            graphical.push(LogindSession { id, uid, user });
+        }
+    }
+    Ok(graphical)
+}
+
+/// A property of the logind session at `path`.
+async fn session_property<T>(
+    connection: &AsyncConnection,
+    path: &dbus::Path<'static>,
+    name: &str,
+) -> Result<T>
+where
+    T: for<'a> dbus::arg::Get<'a> + dbus::arg::Arg,
+{
+    let message = Message::new_method_call(
+        session::LOGIND_BUS,
+        path.clone(),
+        "org.freedesktop.DBus.Properties",
+        "Get",
+    )
+    .map_err(ErrorKind::Conversion)?
+    .append2(session::LOGIND_SESSION_INTERFACE, name);
+    let reply = connection.call(message, DEFAULT_CALL_TIMEOUT).await?;
+    let value: dbus::arg::Variant<T> = reply.read1().map_err(dbus::Error::from)?;
+    Ok(value.0)
+}
+
+/// Waits until `name` has an owner, `false` if none showed up within `timeout`.
+pub(crate) async fn wait_for_name_owner(
+    connection: &AsyncConnection,
+    name: &str,
+    timeout: Duration,
+) -> Result<bool> {
+    if !connection.is_bus() {
+        return Ok(true);
+    }
+    // subscribed before asking, so an owner showing up in between is not missed
+    let mut owner_changes = subscribe_owner_changes(connection, name).await?;
+    if connection.name_owner(name, timeout).await?.is_some() {
+        return Ok(true);
+    }
+    let appeared = async {
+        while let Some(message) = owner_changes.next().await {
+            if let [MessageItem::Str(changed), _, MessageItem::Str(new_owner)] =
+                message.get_items().as_slice()
+            {
+                if changed == name && !new_owner.is_empty() {
+                    return true;
+                }
+            }
+        }
+        false
+    };
+    Ok(timer::timeout(timeout, appeared).await.unwrap_or(false))
+}
+
+/// Like [`dbus_rs::has_name_owner()`], without blocking.
+pub(crate) async fn has_name_owner(
+    connection: &AsyncConnection,
+    name: &str,
+    timeout: Duration,
+) -> Result<bool> {
+    Ok(!connection.is_bus() || connection.name_owner(name, timeout).await?.is_some())
+}
+
+pub(crate) async fn handle_action(id: u32, func: impl ResponseHandler) -> Result<()> {
+    let connection = AsyncConnection::connect(&BusAddress::Session).await?;
+    wait_for_action_signal(&connection, &Default::default(), id, func).await
+}
+
+/// Calls `handler` with the first action or close of the notification with the given id.
+pub(crate) async fn wait_for_action_signal(
+    connection: &AsyncConnection,
+    bus: &NotificationBus,
+    id: u32,
+    handler: impl ResponseHandler,
+) -> Result<()> {
+    let id = Arc::new(AtomicU32::new(id));
+    wait_for_response(ResponseListener::new(connection, bus, id, None), handler).await
+}
+
+/// A notification shown asynchronously through dbus-rs.
+///
+/// This keeps a connection alive to ensure actions work on certain desktops.
+#[derive(Debug)]
+pub(crate) struct NonblockNotificationHandle {
+    /// Shared with the listeners and the controller, which follow the notification when it is shown again.
+    pub(crate) id: Arc<AtomicU32>,
+    connection: AsyncConnection,
+    address: BusAddress,
+    pub(crate) notification: Notification,
+    /// Unique name of the server that showed the notification, `None` on peer-to-peer connections.
+    server: Option<String>,
+    call_timeout: Duration,
+    restart_policy: ServerRestartPolicy,
+    /// Routes the responses to the callbacks of the notification, if it has any.
+    dispatcher: Option<Arc<Dispatcher>>,
+    /// Subscribed to before sending, taken by the first listener.
+    responses: Mutex<Option<Signals>>,
+    /// Kept between calls to [`next_response()`](Self::next_response), so nothing gets lost in between.
+    waiting: Mutex<Option<ResponseListener>>,
+    /// Those of the connection, shared with the listeners and the controller.
+    expiries: Arc<Expiries>,
+    /// Filled in by the listeners.
+    pub(crate) activation_token: ActivationTokenSlot,
+}
+
+impl NonblockNotificationHandle {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        id: u32,
+        connection: AsyncConnection,
+        notification: Notification,
+        server: Option<String>,
+        call_timeout: Duration,
+        restart_policy: ServerRestartPolicy,
+        dispatcher: Option<Arc<Dispatcher>>,
+        responses: Signals,
+    ) -> NonblockNotificationHandle {
+        let id = Arc::new(AtomicU32::new(id));
+        if let Some(ref dispatcher) = dispatcher {
+            dispatcher.followed_by(id.load(Ordering::SeqCst), &id);
+        }
+        let expiries = connection.expiries().clone();
+        expiries.shown(&id, server.as_deref(), &notification, || ExpiringAt {
+            connection: connection.clone(),
+            bus: notification.bus.clone(),
+        });
+        NonblockNotificationHandle {
+            id,
+            address: connection.address().clone(),
+            connection,
+            notification,
+            server,
+            call_timeout,
+            restart_policy,
+            dispatcher,
+            responses: Mutex::new(Some(responses)),
+            waiting: Mutex::new(None),
+            expiries,
+            activation_token: Default::default(),
+        }
+    }
+
+    pub(crate) fn id(&self) -> u32 {
+        self.id.load(Ordering::SeqCst)
+    }
+
+    pub(crate) async fn wait_for_action(
+        &self,
+        invocation_closure: impl ResponseHandler,
+    ) -> Result<()> {
+        wait_for_response(self.listen(), invocation_closure).await
+    }
+
+    pub(crate) fn listen(&self) -> ResponseListener {
+        let listener = ResponseListener::new(
+            &self.connection,
+            &self.notification.bus,
+            self.id.clone(),
+            self.restart(),
+        )
+        .expiring(self.expiries.clone())
+        .recording_tokens(self.activation_token.clone());
+        match self
+            .responses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+        {
+            Some(responses) => listener.subscribed_with(responses),
+            None => listener,
+        }
+    }
+
+    fn restart(&self) -> Option<Restart> {
+        self.server.clone().map(|server| Restart {
+            server,
+            notification: self.notification.clone(),
+            policy: self.restart_policy,
+            call_timeout: self.call_timeout,
+        })
+    }
+
+    /// The next action or close, `None` if there was none within `timeout` or the notification is already closed.
+    ///
+    /// With `close_on_timeout` the notification is closed once `timeout` passes and reported as expired.
+    /// Dropping the future before it completes loses no response, the next call picks them up.
+    pub(crate) async fn next_response(
+        &self,
+        timeout: Option<Duration>,
+        close_on_timeout: bool,
+    ) -> Result<Option<NotificationResponse>> {
+        let taken = self
+            .waiting
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let mut parked = Parked {
+            slot: &self.waiting,
+            listener: Some(taken.unwrap_or_else(|| self.listen())),
+        };
+        let Some(listener) = parked.listener.as_mut() else {
+            return Ok(None);
+        };
+        // outside of the timeout, a short one would cancel the subscription over and over
+        listener.ready().await?;
+        let next = listener.next();
+        let Some(timeout) = timeout else {
+            return Ok(next.await);
+        };
+        Ok(match timer::timeout(timeout, next).await {
+            Some(response) => response,
+            None if close_on_timeout => {
+                self.close().await;
+                listener.close();
+                Some(NotificationResponse::Closed(CloseReason::Expired))
+            }
+            None => None,
+        })
+    }
+
+    pub(crate) async fn close_fallible(&self) -> Result<()> {
+        self.expiries.cancel(&self.id);
+        let message = dbus_rs::close_message(self.id(), self.notification.bus.clone())?;
+        self.connection.call(message, self.call_timeout).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn close(&self) {
+        let _ = self.close_fallible().await;
+    }
+
+    /// Closes the notification without waiting for the reply.
+    ///
+    /// The driver thread still sends it if this was the last handle on the connection.
+    pub(crate) fn close_in_background(&self) {
+        self.expiries.cancel(&self.id);
+        let id = self.id();
+        let sent = dbus_rs::close_message(id, self.notification.bus.clone())
+            .and_then(|message| self.connection.send(message));
+        if let Err(error) = sent {
+            log::warn!("failed to close notification {id}: {error}");
+        }
+    }
+
+    pub(crate) async fn update(&mut self) -> Result<()> {
+        let old_id = self.id();
+        let id = replaces_id(
+            &self.connection,
+            &self.notification.bus,
+            old_id,
+            self.server.as_deref(),
+            self.restart_policy,
+            self.call_timeout,
+        )
+        .await?;
+        let (id, server, responses) = send_subscribed(
+            &self.notification,
+            id,
+            &self.connection,
+            &self.notification.bus,
+            self.call_timeout,
+        )
+        .await?;
+        *self.responses.get_mut().unwrap_or_else(|e| e.into_inner()) = Some(responses);
+        if let Some(ref dispatcher) = self.dispatcher {
+            dispatcher.reregister(old_id, id, &self.notification, server.clone());
+        }
+        self.id.store(id, Ordering::SeqCst);
+        self.server = server;
+        self.expiries
+            .shown(&self.id, self.server.as_deref(), &self.notification, || {
+                ExpiringAt {
+                    connection: self.connection.clone(),
+                    bus: self.notification.bus.clone(),
+                }
+            });
+        Ok(())
+    }
+
+    /// A controller for this notification, see [`DbusNotificationHandle::controller()`].
+    pub(crate) fn controller(&self) -> DbusController {
+        DbusController::new(
+            self.id.clone(),
+            self.address.clone(),
+            &self.notification,
+            self.server.clone(),
+            self.call_timeout,
+            self.restart_policy,
+            self.dispatcher.clone(),
+            self.expiries.clone(),
+        )
+    }
+}
+
+impl DbusController {
+    /// The connection of the async API, opened once it is first used.
+    async fn nonblock(&self) -> Result<&AsyncConnection> {
+        if let Some(connection) = self.nonblock.get() {
+            return Ok(connection);
+        }
+        let connection = AsyncConnection::connect(&self.address).await?;
+        // somebody else may have been quicker
+        Ok(self.nonblock.get_or_init(|| connection))
+    }
+
+    /// Like [`update()`](Self::update), without blocking.
+    pub(crate) async fn update_async(&self, change: impl FnOnce(&mut Notification)) -> Result<()> {
+        // not held across an await, the state is written back once the notification is shown
+        let (notification, shown, mut server) = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            change(&mut state.notification);
+            (state.notification.clone(), state.id, state.server.clone())
+        };
+        let connection = self.nonblock().await?;
+        let current = self.id();
+        if current != shown {
+            // a listener showed it again after the server restarted
+            server = connection
+                .name_owner(self.bus.name(), self.call_timeout)
+                .await?;
+        }
+        let id = replaces_id(
+            connection,
+            &self.bus,
+            current,
+            server.as_deref(),
+            self.restart_policy,
+            self.call_timeout,
+        )
+        .await?;
+        let (id, server) =
+            notify(&notification, id, connection, &self.bus, self.call_timeout).await?;
+        if let Some(ref dispatcher) = self.dispatcher {
+            dispatcher.reregister(current, id, &notification, server.clone());
+        }
+        self.id.store(id, Ordering::SeqCst);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.id = id;
+        state.server = server;
+        self.expiries
+            .shown(&self.id, state.server.as_deref(), &notification, || {
+                ExpiringAt {
+                    connection: connection.clone(),
+                    bus: self.bus.clone(),
+                }
+            });
+        Ok(())
+    }
+
+    /// Like [`close()`](Self::close), without blocking.
+    pub(crate) async fn close_async(&self) -> Result<()> {
+        self.expiries.cancel(&self.id);
+        close(
+            self.nonblock().await?,
+            self.id(),
+            self.bus.clone(),
+            self.call_timeout,
+        )
+        .await
+    }
+}
+
+/// A call made by the thread of a [`Following`].
+type FollowingCall = Box<dyn FnOnce() + Send>;
+
+/// Follows a notification shown through the blocking API, so it can be awaited as well.
+///
+/// The listener of the blocking handle moves to a thread of its own, along with the responses it holds.
+/// The thread hands every response to the task waiting for the next one, so each is reported once,
+/// and makes the calls for the tasks over the connection of the handle in between.
+/// It stops once this is dropped.
+pub(crate) struct Following {
+    responses: Arc<Queue<NotificationResponse>>,
+    /// Set once the listener stopped because the server went away.
+    vanished: Arc<AtomicBool>,
+    calls: mpsc::Sender<FollowingCall>,
+    id: Arc<AtomicU32>,
+    connection: Arc<SharedConnection>,
+    bus: NotificationBus,
+    call_timeout: Duration,
+    expiries: Arc<Expiries>,
+}
+
+impl Following {
+    pub(crate) fn new(handle: &DbusNotificationHandle) -> Result<Following> {
+        let mut listener = handle.take_listener();
+        let responses = Arc::new(Queue::default());
+        let vanished = Arc::new(AtomicBool::new(false));
+        let (calls, received) = mpsc::channel::<FollowingCall>();
+        let (sink, gone) = (responses.clone(), vanished.clone());
+        std::thread::Builder::new()
+            .name("notify-rust following".into())
+            .spawn(move || loop {
+                if listener.is_closed() {
+                    gone.store(listener.vanished(), Ordering::SeqCst);
+                    sink.close();
+                    // the notification may still be shown again
+                    match received.recv() {
+                        Ok(call) => call(),
+                        Err(mpsc::RecvError) => return,
+                    }
+                    continue;
+                }
+                match received.try_recv() {
+                    Ok(call) => call(),
+                    Err(mpsc::TryRecvError::Empty) => {
+                        let deadline = Instant::now() + FOLLOWING_INTERVAL;
+                        if let Some(response) = listener.next_before(Some(deadline)) {
+                            sink.push(response);
+                        }
+                    }
+                    Err(mpsc::TryRecvError::Disconnected) => return,
+                }
+            })
+            .map_err(|e| ErrorKind::Msg(e.to_string()))?;
+        Ok(Following {
+            responses,
+            vanished,
+            calls,
+            id: handle.id.clone(),
+            connection: handle.connection.clone(),
+            bus: handle.notification.bus.clone(),
+            call_timeout: handle.call_timeout,
+            expiries: handle.expiries.clone(),
+        })
+    }
+
+    /// Makes `call` on the thread, between two waits for a response.
+    async fn call<T: Send + 'static>(
+        &self,
+        call: impl FnOnce() -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let result = Arc::new(Queue::default());
+        let sink = result.clone();
+        self.calls
+            .send(Box::new(move || {
+                sink.push(call());
+            }))
+            .map_err(|_| ErrorKind::Msg("the following thread is gone".into()))?;
+        result
+            .pop()
+            .await
+            .ok_or_else(|| ErrorKind::Msg("the following thread is gone".into()))?
+    }
+
+    pub(crate) async fn wait_for_action(&self, handler: impl ResponseHandler) -> Result<()> {
+        if let Some(response) = self.responses.pop().await {
+            handler.call(&response);
+            return Ok(());
+        }
+        if self.vanished.load(Ordering::SeqCst) {
+            return Err(ErrorKind::ServerVanished.into());
+        }
+        Ok(())
+    }
+
+    /// Like [`NonblockNotificationHandle::next_response()`].
+    pub(crate) async fn next_response(
+        &self,
+        timeout: Option<Duration>,
+        close_on_timeout: bool,
+    ) -> Result<Option<NotificationResponse>> {
+        let next = self.responses.pop();
+        let Some(timeout) = timeout else {
+            return Ok(next.await);
+        };
+        Ok(match timer::timeout(timeout, next).await {
+            Some(response) => response,
+            None if close_on_timeout => {
+                self.close().await;
+                self.responses.close();
+                Some(NotificationResponse::Closed(CloseReason::Expired))
+            }
+            None => None,
+        })
+    }
+
+    pub(crate) async fn close_fallible(&self) -> Result<()> {
+        self.expiries.cancel(&self.id);
+        let (id, connection) = (self.id.load(Ordering::SeqCst), self.connection.clone());
+        let (bus, call_timeout) = (self.bus.clone(), self.call_timeout);
+        self.call(move || {
+            dbus_rs::close_notification_via_connection_at_bus(id, &*connection, bus, call_timeout)
+        })
+        .await
+    }
+
+    pub(crate) async fn close(&self) {
+        let _ = self.close_fallible().await;
+    }
+
+    /// Shows the notification of `handle` again with the changes made to it.
+    pub(crate) async fn update(&self, handle: &mut DbusNotificationHandle) -> Result<()> {
+        let old_id = handle.id();
+        let shown = self.call(handle.show_again()).await?;
+        handle.shown_again(old_id, shown);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Following {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Following")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Where [`Expiries`] closes a notification shown asynchronously through dbus-rs.
+struct ExpiringAt {
+    connection: AsyncConnection,
+    bus: NotificationBus,
+}
+
+impl ExpiringServer for ExpiringAt {
+    fn server_information(&self) -> Result<ServerInformation> {
+        let message = dbus_rs::build_message("GetServerInformation", self.bus.clone())?;
+        let reply = blocking::block_on(self.connection.call(message, DEFAULT_CALL_TIMEOUT))?;
+        Ok(dbus_rs::server_information_reply(&reply))
+    }
+
+    fn close(&self, id: u32) -> Result<()> {
+        // the driver thread sends it, even if this was the last clone of the connection
+        self.connection
+            .send(dbus_rs::close_message(id, self.bus.clone())?)
+    }
+}
+
+/// Puts a listener back into its slot when dropped, even if that happens half way through an `await`.
+struct Parked<'a> {
+    slot: &'a Mutex<Option<ResponseListener>>,
+    listener: Option<ResponseListener>,
+}
+
+impl Drop for Parked<'_> {
+    fn drop(&mut self) {
+        *self.slot.lock().unwrap_or_else(|e| e.into_inner()) = self.listener.take();
+    }
+}
+
+/// Calls `handler` with the first action or close of the notification `listener` follows.
+async fn wait_for_response(
+    mut listener: ResponseListener,
+    handler: impl ResponseHandler,
+) -> Result<()> {
+    if let Some(response) = listener.next().await {
+        handler.call(&response);
+        return Ok(());
+    }
+    if listener.vanished() {
+        return Err(ErrorKind::ServerVanished.into());
+    }
+    Ok(())
+}
+
+/// Follows the signals of one notification until it is closed, like [`dbus_rs::ResponseListener`].
+#[derive(Debug)]
+pub(crate) struct ResponseListener {
+    connection: AsyncConnection,
+    bus: NotificationBus,
+    /// Shared with the handle, so it learns about the id the notification got when shown again
+    id: Arc<AtomicU32>,
+    restart: Option<Restart>,
+    /// `None` while the name has no owner
+    server: Option<String>,
+    responses: Option<Signals>,
+    owner_changes: Option<Signals>,
+    /// Those of the connection the notification was shown over, see [`Expiries`].
+    expiries: Option<Arc<Expiries>>,
+    activation_token: ActivationTokenSlot,
+    closed: bool,
+    /// Set once the server went away and took the notification with it.
+    vanished: bool,
+}
+
+impl ResponseListener {
+    fn new(
+        connection: &AsyncConnection,
+        bus: &NotificationBus,
+        id: Arc<AtomicU32>,
+        restart: Option<Restart>,
+    ) -> ResponseListener {
+        ResponseListener {
+            connection: connection.clone(),
+            bus: bus.clone(),
+            id,
+            server: restart.as_ref().map(|restart| restart.server.clone()),
+            restart,
+            responses: None,
+            owner_changes: None,
+            expiries: None,
+            activation_token: Default::default(),
+            closed: false,
+            vanished: false,
+        }
+    }
+
+    /// Uses the responses subscribed to before the notification was sent.
+    fn subscribed_with(mut self, responses: Signals) -> ResponseListener {
+        self.responses = Some(responses);
+        self
+    }
+
+    /// Reports the close by `expiries` as expired.
+    fn expiring(mut self, expiries: Arc<Expiries>) -> ResponseListener {
+        self.expiries = Some(expiries);
+        self
+    }
+
+    /// Keeps the activation tokens in `slot`, where the handle finds them.
+    pub(crate) fn recording_tokens(mut self, slot: ActivationTokenSlot) -> ResponseListener {
+        self.activation_token = slot;
+        self
+    }
+
+    async fn subscribe(&mut self) -> Result<()> {
+        if self.restart.is_some() && self.owner_changes.is_none() {
+            self.owner_changes =
+                Some(subscribe_owner_changes(&self.connection, self.bus.name()).await?);
+        }
+        if self.responses.is_none() {
+            self.responses = Some(subscribe_responses(&self.connection, &self.bus).await?);
+        }
+        Ok(())
+    }
+
+    /// Subscribes to the signals unless that was done already, closes the listener if it fails.
+    pub(crate) async fn ready(&mut self) -> Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.subscribe().await.inspect_err(|_| self.close())
+    }
+
+    /// The next response, `None` once the notification is closed.
+    pub(crate) async fn next(&mut self) -> Option<NotificationResponse> {
+        if let Err(error) = self.ready().await {
+            log::warn!("failed to subscribe to notification signals: {error}");
+        }
+        if self.closed {
+            return None;
+        }
+        let mut response = self.receive().await;
+        if let (Some(NotificationResponse::Closed(ref mut reason)), Some(expiries)) =
+            (&mut response, &self.expiries)
+        {
+            *reason = expiries.closed(&self.id, *reason);
+        }
+        if matches!(response, None | Some(NotificationResponse::Closed(_))) {
+            self.close();
+        }
+        response
+    }
+
+    /// Whether the listener stopped because the server went away.
+    pub(crate) fn vanished(&self) -> bool {
+        self.vanished
+    }
+
+    /// Gives up on the notification, the server that showed it is gone.
+    fn vanish(&mut self) -> Option<NotificationResponse> {
+        self.vanished = true;
+        self.close();
+        None
+    }
+
+    /// Drops the subscriptions, no more responses are reported.
+    pub(crate) fn close(&mut self) {
+        self.closed = true;
+        self.responses = None;
+        self.owner_changes = None;
+    }
+
+    async fn receive(&mut self) -> Option<NotificationResponse> {
+        loop {
+            let responses = self.responses.as_mut()?;
+            let message = match self.owner_changes {
+                Some(ref mut owner_changes) => {
+                    futures_lite::future::or(responses.next(), owner_changes.next()).await
+                }
+                None => responses.next().await,
+            }?;
+            let signal = dbus_rs::classify(
+                &message,
+                &self.bus,
+                self.id.load(Ordering::SeqCst),
+                self.server.as_deref(),
+                self.restart.is_some(),
+            );
+            let (old_owner, new_owner) = match signal {
+                Some(Signal::Response(response)) => return Some(response),
+                Some(Signal::ActivationToken(token)) => {
+                    self.activation_token.set(token);
+                    continue;
+                }
+                Some(Signal::OwnerChanged {
+                    old_owner,
+                    new_owner,
+                }) => (old_owner, new_owner),
+                None => continue,
+            };
+            let Some(ref restart) = self.restart else {
+                continue;
+            };
+            match restart.owner_changed(&mut self.server, &old_owner, &new_owner) {
+                OwnerChange::Vanished => {
+                    return self.vanish();
+                }
+                OwnerChange::Reshow => {
+                    let sent = send_subscribed(
+                        &restart.notification,
+                        0,
+                        &self.connection,
+                        &self.bus,
+                        restart.call_timeout,
+                    )
+                    .await;
+                    let Ok((new_id, new_server, responses)) = sent else {
+                        return self.vanish();
+                    };
+                    self.id.store(new_id, Ordering::SeqCst);
+                    self.server = new_server.or(Some(new_owner));
+                    self.responses = Some(responses);
+                }
+                OwnerChange::Unchanged => {}
+            }
+        }
+    }
+}
@@ -1,98 +1,198 @@
 use dbus::{
-    arg::messageitem::{MessageItem, MessageItemArray},
+    arg::{
+        messageitem::{MessageItem, MessageItemArray},
+        IterAppend,
+    },
+    blocking::{BlockingSender, SyncConnection},
+    channel::{Channel, MatchingReceiver, Token},
     ffidisp::{BusType, Connection, ConnectionItem},
+    message::{MatchRule, MessageType},
     Message,
 };
 
-use super::{bus::NotificationBus, NOTIFICATION_INTERFACE};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc, Arc, Mutex, OnceLock, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use super::{
+    bus::NotificationBus,
+    dispatcher::Dispatcher,
+    events::ActivationTokenSlot,
+    expiry::{Expiries, ExpiringServer},
+    session::{self, LogindSession},
+    BusAddress, ServerRestartPolicy, SpecVersions, NOTIFICATION_INTERFACE,
+};
 use crate::response::{CloseReason, NotificationResponse, ResponseHandler};
 
 use crate::{
     error::*,
     hints::message::HintMessage,
+    miniver::Version,
     notification::Notification,
-    xdg::{ServerInformation, NOTIFICATION_OBJECTPATH},
+    xdg::{self, ServerInformation},
 };
 
-pub mod bus {
-
-    use crate::xdg::NOTIFICATION_DEFAULT_BUS;
-
-    fn skip_first_slash(s: &str) -> &str {
-        if let Some('/') = s.chars().next() {
-            &s[1..]
-        } else {
-            s
-        }
-    }
-
-    use std::path::PathBuf;
-
-    type BusNameType = dbus::strings::BusName<'static>;
-
-    #[derive(Clone, Debug)]
-    pub struct NotificationBus(BusNameType);
-
-    impl Default for NotificationBus {
-        fn default() -> Self {
-            Self(dbus::strings::BusName::from_slice(NOTIFICATION_DEFAULT_BUS).unwrap())
-        }
-    }
-
-    impl NotificationBus {
-        fn namespaced_custom(custom_path: &str) -> Option<String> {
-            // abusing path for semantic join
-            skip_first_slash(
-                PathBuf::from("/de/hoodie/Notification")
-                    .join(custom_path)
-                    .to_str()?,
-            )
-            .replace('/', ".")
-            .into()
-        }
-
-        pub fn custom(custom_path: &str) -> Option<Self> {
-            let name = dbus::strings::BusName::new(Self::namespaced_custom(custom_path)?).ok()?;
-            Some(Self(name))
-        }
-
-        pub fn into_name(self) -> BusNameType {
-            self.0
-        }
-    }
-}
-
 /// A handle to a shown notification.
 ///
 /// This keeps a connection alive to ensure actions work on certain desktops.
 #[derive(Debug)]
 pub struct DbusNotificationHandle {
-    pub(crate) id: u32,
-    pub(crate) connection: Connection,
+    /// Shared with the listeners and the controller, which follow the notification when it is shown again.
+    pub(crate) id: Arc<AtomicU32>,
+    pub(crate) connection: Arc<SharedConnection>,
+    pub(crate) address: BusAddress,
     pub(crate) notification: Notification,
+    /// Unique name of the server that showed the notification, `None` on peer-to-peer connections.
+    pub(crate) server: Option<String>,
+    pub(crate) call_timeout: Duration,
+    pub(crate) restart_policy: ServerRestartPolicy,
+    /// Routes the responses to the callbacks of the notification, if it has any.
+    pub(crate) dispatcher: Option<Arc<Dispatcher>>,
+    /// Subscribed to before sending, taken by the first listener.
+    pub(crate) responses: Mutex<Option<Subscription>>,
+    /// Kept between calls to [`next_response()`](Self::next_response), so nothing gets lost in between.
+    waiting: Mutex<Option<ResponseListener>>,
+    /// Those of the connection, shared with the listeners and the controller.
+    pub(crate) expiries: Arc<Expiries>,
+    /// Filled in by the listeners.
+    pub(crate) activation_token: ActivationTokenSlot,
 }
 
 impl DbusNotificationHandle {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         id: u32,
-        connection: Connection,
+        connection: Arc<SharedConnection>,
+        address: BusAddress,
         notification: Notification,
+        server: Option<String>,
+        call_timeout: Duration,
+        restart_policy: ServerRestartPolicy,
+        dispatcher: Option<Arc<Dispatcher>>,
+        responses: Option<Subscription>,
     ) -> DbusNotificationHandle {
+        let id = Arc::new(AtomicU32::new(id));
+        if let Some(ref dispatcher) = dispatcher {
+            dispatcher.followed_by(id.load(Ordering::SeqCst), &id);
+        }
+        let expiries = connection.expiries().clone();
+        expiries.shown(&id, server.as_deref(), &notification, || ExpiringAt {
+            connection: connection.clone(),
+            bus: notification.bus.clone(),
+            call_timeout,
+        });
         DbusNotificationHandle {
             id,
             connection,
+            address,
             notification,
+            server,
+            call_timeout,
+            restart_policy,
+            dispatcher,
+            responses: Mutex::new(responses),
+            waiting: Mutex::new(None),
+            expiries,
+            activation_token: Default::default(),
         }
     }
 
+    pub(crate) fn id(&self) -> u32 {
+        self.id.load(Ordering::SeqCst)
+    }
+
     pub fn wait_for_action(self, invocation_closure: impl ResponseHandler) -> Result<()> {
-        wait_for_action_signal(&self.connection, self.id, invocation_closure)
+        wait_for_response(self.listen(), invocation_closure)
+    }
+
+    pub(crate) fn listen(&self) -> ResponseListener {
+        let listener = ResponseListener::new(
+            &self.connection,
+            &self.notification.bus,
+            self.id.clone(),
+            self.restart(),
+        )
+        .expiring(self.expiries.clone())
+        .recording_tokens(self.activation_token.clone());
+        let responses = self
+            .responses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        match responses {
+            Some(responses) => listener.subscribed_with(responses),
+            None => listener,
+        }
+    }
+
+    /// The listener the next wait continues with, it takes the responses that arrived so far along.
+    ///
+    /// Whoever follows the notification from another thread uses this, the handle starts over with a new one.
+    #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+    pub(crate) fn take_listener(&self) -> ResponseListener {
+        let waiting = self
+            .waiting
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        waiting.unwrap_or_else(|| self.listen())
+    }
+
+    fn restart(&self) -> Option<Restart> {
+        self.server.clone().map(|server| Restart {
+            server,
+            notification: self.notification.clone(),
+            policy: self.restart_policy,
+            call_timeout: self.call_timeout,
+        })
+    }
+
+    /// The next action or close, `None` if there was none within `timeout` or the notification is already closed.
+    ///
+    /// With `close_on_timeout` the notification is closed once `timeout` passes and reported as expired.
+    pub(crate) fn next_response(
+        &self,
+        timeout: Duration,
+        close_on_timeout: bool,
+    ) -> Option<NotificationResponse> {
+        let deadline = Instant::now() + timeout;
+        let taken = self
+            .waiting
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let mut listener = taken.unwrap_or_else(|| self.listen());
+        let response = match listener.next_before(Some(deadline)) {
+            None if close_on_timeout && !listener.closed => {
+                self.close_fallible().ok();
+                listener.close();
+                Some(NotificationResponse::Closed(CloseReason::Expired))
+            }
+            response => response,
+        };
+        *self.waiting.lock().unwrap_or_else(|e| e.into_inner()) = Some(listener);
+        response
+    }
+
+    pub(crate) fn close_fallible(&self) -> Result<()> {
+        self.expiries.cancel(&self.id);
+        close_notification_via_connection_at_bus(
+            self.id(),
+            &self.connection,
+            self.notification.bus.clone(),
+            self.call_timeout,
+        )
     }
 
     pub fn close(self) {
-        let mut message = build_message("CloseNotification", Default::default());
-        message.append_items(&[self.id.into()]);
-        let _ = self.connection.send(message); // If closing fails there's nothing we could do anyway
+        // If closing fails there's nothing we could do anyway
+        let _ = self.close_fallible();
     }
 
     pub fn on_close<F>(self, closure: F) -> Result<()>
@@ -107,27 +207,574 @@ impl DbusNotificationHandle {
     }
 
     pub fn update(&mut self) -> Result<()> {
-        self.id = send_notification_via_connection(&self.notification, self.id, &self.connection)?;
+        let old_id = self.id();
+        let show = self.show_again();
+        self.shown_again(old_id, show()?);
         Ok(())
     }
+
+    /// Shows the notification again when called, from any thread.
+    ///
+    /// Returns the id it got, the server that showed it and the responses subscribed to before,
+    /// for [`shown_again()`](Self::shown_again).
+    pub(crate) fn show_again(
+        &self,
+    ) -> impl FnOnce() -> Result<(u32, Option<String>, Subscription)> + Send + 'static {
+        let connection = self.connection.clone();
+        let notification = self.notification.clone();
+        let (old_id, server) = (self.id(), self.server.clone());
+        let (restart_policy, call_timeout) = (self.restart_policy, self.call_timeout);
+        move || {
+            let id = replaces_id(
+                &*connection,
+                &notification.bus,
+                old_id,
+                server.as_deref(),
+                restart_policy,
+                call_timeout,
+            )?;
+            send_subscribed(&connection, &notification.bus, || {
+                send_notification_via_connection_at_bus(
+                    &notification,
+                    id,
+                    &*connection,
+                    notification.bus.clone(),
+                    call_timeout,
+                )
+            })
+        }
+    }
+
+    /// Takes over what [`show_again()`](Self::show_again) returned for the notification that had `old_id`.
+    pub(crate) fn shown_again(
+        &mut self,
+        old_id: u32,
+        (id, server, responses): (u32, Option<String>, Subscription),
+    ) {
+        *self.responses.get_mut().unwrap_or_else(|e| e.into_inner()) = Some(responses);
+        if let Some(ref dispatcher) = self.dispatcher {
+            dispatcher.reregister(old_id, id, &self.notification, server.clone());
+        }
+        self.id.store(id, Ordering::SeqCst);
+        self.server = server;
+        self.expiries
+            .shown(&self.id, self.server.as_deref(), &self.notification, || {
+                ExpiringAt {
+                    connection: self.connection.clone(),
+                    bus: self.notification.bus.clone(),
+                    call_timeout: self.call_timeout,
+                }
+            });
+    }
+
+    /// A controller for this notification that can be used from any thread.
+    ///
+    /// The controller opens a connection of its own to the same address once it is first used,
+    /// so its calls do not queue up behind a thread waiting for responses on the handle's connection.
+    pub(crate) fn controller(&self) -> DbusController {
+        DbusController::new(
+            self.id.clone(),
+            self.address.clone(),
+            &self.notification,
+            self.server.clone(),
+            self.call_timeout,
+            self.restart_policy,
+            self.dispatcher.clone(),
+            self.expiries.clone(),
+        )
+    }
 }
 
-pub fn send_notification_via_connection(
-    notification: &Notification,
+/// Where [`Expiries`] closes a notification shown through dbus-rs.
+pub(crate) struct ExpiringAt {
+    pub connection: Arc<SharedConnection>,
+    pub bus: NotificationBus,
+    pub call_timeout: Duration,
+}
+
+impl ExpiringServer for ExpiringAt {
+    fn server_information(&self) -> Result<ServerInformation> {
+        get_server_information_via_connection_at_bus(
+            &*self.connection,
+            self.bus.clone(),
+            self.call_timeout,
+        )
+    }
+
+    fn close(&self, id: u32) -> Result<()> {
+        close_notification_via_connection_at_bus(
+            id,
+            &*self.connection,
+            self.bus.clone(),
+            self.call_timeout,
+        )
+    }
+}
+
+/// Updates and closes a notification from any thread, see [`DbusNotificationHandle::controller()`].
+pub(crate) struct DbusController {
+    pub(crate) id: Arc<AtomicU32>,
+    pub(crate) address: BusAddress,
+    connection: OnceLock<Arc<SharedConnection>>,
+    /// The connection of the async API, also opened once it is first used.
+    #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+    pub(crate) nonblock: OnceLock<super::dbus_nonblock::AsyncConnection>,
+    pub(crate) bus: NotificationBus,
+    pub(crate) call_timeout: Duration,
+    pub(crate) restart_policy: ServerRestartPolicy,
+    pub(crate) dispatcher: Option<Arc<Dispatcher>>,
+    pub(crate) expiries: Arc<Expiries>,
+    pub(crate) state: Mutex<Controlled>,
+}
+
+/// What a controller last sent.
+pub(crate) struct Controlled {
+    pub(crate) id: u32,
+    pub(crate) server: Option<String>,
+    pub(crate) notification: Notification,
+}
+
+impl DbusController {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        id: Arc<AtomicU32>,
+        address: BusAddress,
+        notification: &Notification,
+        server: Option<String>,
+        call_timeout: Duration,
+        restart_policy: ServerRestartPolicy,
+        dispatcher: Option<Arc<Dispatcher>>,
+        expiries: Arc<Expiries>,
+    ) -> DbusController {
+        DbusController {
+            state: Mutex::new(Controlled {
+                id: id.load(Ordering::SeqCst),
+                server,
+                notification: notification.clone(),
+            }),
+            id,
+            address,
+            connection: OnceLock::new(),
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            nonblock: OnceLock::new(),
+            bus: notification.bus.clone(),
+            call_timeout,
+            restart_policy,
+            dispatcher,
+            expiries,
+        }
+    }
+
+    pub(crate) fn id(&self) -> u32 {
+        self.id.load(Ordering::SeqCst)
+    }
+
+    fn connection(&self) -> Result<&Arc<SharedConnection>> {
+        if let Some(connection) = self.connection.get() {
+            return Ok(connection);
+        }
+        let connection = connect_shared(&self.address)?;
+        // somebody else may have been quicker
+        Ok(self.connection.get_or_init(|| connection))
+    }
+
+    /// Applies `change` to the notification and shows it again.
+    pub(crate) fn update(&self, change: impl FnOnce(&mut Notification)) -> Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        change(&mut state.notification);
+        let connection = self.connection()?;
+        let current = self.id();
+        if current != state.id {
+            // a listener showed it again after the server restarted
+            state.server = name_owner(connection, self.bus.name(), self.call_timeout)?;
+            state.id = current;
+        }
+        let id = replaces_id(
+            connection,
+            &self.bus,
+            current,
+            state.server.as_deref(),
+            self.restart_policy,
+            self.call_timeout,
+        )?;
+        let (id, server) = send_notification_via_connection_at_bus(
+            &state.notification,
+            id,
+            connection,
+            self.bus.clone(),
+            self.call_timeout,
+        )?;
+        if let Some(ref dispatcher) = self.dispatcher {
+            dispatcher.reregister(current, id, &state.notification, server.clone());
+        }
+        self.id.store(id, Ordering::SeqCst);
+        state.id = id;
+        state.server = server;
+        self.expiries.shown(
+            &self.id,
+            state.server.as_deref(),
+            &state.notification,
+            || ExpiringAt {
+                connection: connection.clone(),
+                bus: self.bus.clone(),
+                call_timeout: self.call_timeout,
+            },
+        );
+        Ok(())
+    }
+
+    pub(crate) fn close(&self) -> Result<()> {
+        self.expiries.cancel(&self.id);
+        close_notification_via_connection_at_bus(
+            self.id(),
+            self.connection()?,
+            self.bus.clone(),
+            self.call_timeout,
+        )
+    }
+}
+
+impl fmt::Debug for DbusController {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DbusController")
+            .field("id", &self.id())
+            .field("address", &self.address)
+            .field("bus", &self.bus)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The id to show a notification again with.
+///
+/// That is `id` while `server` still owns the bus name, otherwise it depends on the restart policy:
+/// the old id means nothing to a new server, or worse, something else.
+fn replaces_id(
+    connection: &impl Call,
+    bus: &NotificationBus,
     id: u32,
-    connection: &Connection,
+    server: Option<&str>,
+    restart_policy: ServerRestartPolicy,
+    timeout: Duration,
 ) -> Result<u32> {
-    send_notification_via_connection_at_bus(notification, id, connection, Default::default())
+    let Some(server) = server else {
+        return Ok(id);
+    };
+    let owner = name_owner(connection, bus.name(), timeout)?;
+    if owner.as_deref() == Some(server) {
+        return Ok(id);
+    }
+    match restart_policy {
+        ServerRestartPolicy::Vanish => Err(ErrorKind::ServerVanished.into()),
+        ServerRestartPolicy::Reshow => Ok(0),
+    }
+}
+
+/// The blocking method calls of this module, on either kind of connection.
+pub(crate) trait Call {
+    fn call_raw(
+        &self,
+        message: Message,
+        timeout: Duration,
+    ) -> std::result::Result<Message, dbus::Error>;
+
+    /// `false` for peer-to-peer connections, which have no bus and no unique name.
+    fn is_bus(&self) -> bool;
+
+    /// Where the spec versions of the servers are kept, `None` for connections that are not kept around.
+    fn spec_versions(&self) -> Option<&SpecVersions> {
+        None
+    }
+}
+
+impl Call for Connection {
+    fn call_raw(
+        &self,
+        message: Message,
+        timeout: Duration,
+    ) -> std::result::Result<Message, dbus::Error> {
+        self.send_with_reply_and_block(message, duration_ms(timeout))
+    }
+
+    fn is_bus(&self) -> bool {
+        !self.unique_name().is_empty()
+    }
+}
+
+impl<C: Call> Call for Arc<C> {
+    fn call_raw(
+        &self,
+        message: Message,
+        timeout: Duration,
+    ) -> std::result::Result<Message, dbus::Error> {
+        (**self).call_raw(message, timeout)
+    }
+
+    fn is_bus(&self) -> bool {
+        (**self).is_bus()
+    }
+
+    fn spec_versions(&self) -> Option<&SpecVersions> {
+        (**self).spec_versions()
+    }
+}
+
+impl Call for SyncConnection {
+    fn call_raw(
+        &self,
+        message: Message,
+        timeout: Duration,
+    ) -> std::result::Result<Message, dbus::Error> {
+        BlockingSender::send_with_reply_and_block(self, message, timeout)
+    }
+
+    fn is_bus(&self) -> bool {
+        self.channel().unique_name().is_some()
+    }
+}
+
+/// How long a thread waiting for signals holds on to the socket at a time.
+///
+/// A method call from another thread on the same connection may have to wait that long.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A blocking connection that handles and [`Subscription`]s can share between threads.
+///
+/// libdbus gives the reply to a blocking call to whoever takes it out of the incoming queue first,
+/// so messages are only taken out while holding `io`, and method calls hold it until their reply arrived.
+/// Waiting for the socket happens without it.
+pub(crate) struct SharedConnection {
+    connection: SyncConnection,
+    io: Mutex<()>,
+    /// The match rules added so far, they are kept as long as the connection, the bus drops them along with it.
+    rules: Mutex<Vec<String>>,
+    spec_versions: SpecVersions,
+    expiries: Arc<Expiries>,
+}
+
+impl SharedConnection {
+    fn new(connection: SyncConnection) -> SharedConnection {
+        // every subscription gets the signals it matches, not just the first one
+        connection.set_signal_match_mode(true);
+        SharedConnection {
+            connection,
+            io: Mutex::new(()),
+            rules: Mutex::new(Vec::new()),
+            spec_versions: SpecVersions::default(),
+            expiries: Default::default(),
+        }
+    }
+
+    /// Closes the notifications shown over this connection that enforce their timeout.
+    pub(crate) fn expiries(&self) -> &Arc<Expiries> {
+        &self.expiries
+    }
+
+    /// Asks the bus for the signals `rule` matches, unless that was done already.
+    fn add_match(&self, rule: &str) -> Result<()> {
+        // peer-to-peer connections have no unique name and receive every signal anyway
+        if !self.is_bus() || self.has_match(rule) {
+            return Ok(());
+        }
+        call(self, bus_message("AddMatch", rule)?, DEFAULT_CALL_TIMEOUT)?;
+        self.added_match(rule);
+        Ok(())
+    }
+
+    fn has_match(&self, rule: &str) -> bool {
+        self.rules
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .any(|added| added == rule)
+    }
+
+    fn added_match(&self, rule: &str) {
+        let mut rules = self.rules.lock().unwrap_or_else(|e| e.into_inner());
+        if !rules.iter().any(|added| added == rule) {
+            rules.push(rule.to_owned());
+        }
+    }
+
+    /// Hands the queued messages to the subscriptions, waits up to `timeout` for some if there were none.
+    pub(crate) fn process(&self, timeout: Duration) -> Result<()> {
+        if self.dispatch()? {
+            return Ok(());
+        }
+        self.connection
+            .channel()
+            .read_write(Some(timeout))
+            .map_err(|()| dbus::Error::new_failed("disconnected from D-Bus"))?;
+        self.dispatch()?;
+        Ok(())
+    }
+
+    /// Hands every queued message to the subscriptions, returns whether there were any.
+    fn dispatch(&self) -> Result<bool> {
+        let _io = self.io.lock().unwrap_or_else(|e| e.into_inner());
+        let mut dispatched = false;
+        while self.connection.process(Duration::ZERO)? {
+            dispatched = true;
+        }
+        Ok(dispatched)
+    }
+
+    /// Sends all `messages` before waiting for the first reply, returns the replies in the same order.
+    ///
+    /// Saves a round trip per message over calling them one by one.
+    /// A reply that did not arrive within `timeout` is reported as `NoReply`, like libdbus does.
+    pub(crate) fn call_all(
+        &self,
+        messages: Vec<Message>,
+        timeout: Duration,
+    ) -> Vec<std::result::Result<Message, dbus::Error>> {
+        let replies = Arc::new(Mutex::new(HashMap::new()));
+        let tokens = [MessageType::MethodReturn, MessageType::Error].map(|reply_type| {
+            let replies = replies.clone();
+            self.connection.start_receive(
+                MatchRule::new().with_type(reply_type),
+                Box::new(move |message: Message, _| {
+                    if let Some(serial) = message.get_reply_serial() {
+                        replies
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .insert(serial, message);
+                    }
+                    true
+                }),
+            )
+        });
+        let serials: Vec<_> = {
+            // no reply can be taken out before its serial is known
+            let _io = self.io.lock().unwrap_or_else(|e| e.into_inner());
+            messages
+                .into_iter()
+                .map(|message| self.connection.channel().send(message))
+                .collect()
+        };
+        let deadline = Instant::now() + timeout;
+        loop {
+            let missing = {
+                let replies = replies.lock().unwrap_or_else(|e| e.into_inner());
+                serials
+                    .iter()
+                    .flatten()
+                    .any(|serial| !replies.contains_key(serial))
+            };
+            let wait = deadline.saturating_duration_since(Instant::now());
+            if !missing || wait.is_zero() {
+                break;
+            }
+            if let Err(error) = self.process(wait.min(POLL_INTERVAL)) {
+                log::warn!("lost the connection while waiting for replies: {error}");
+                break;
+            }
+        }
+        for token in tokens {
+            self.connection.stop_receive(token);
+        }
+        let mut replies = std::mem::take(&mut *replies.lock().unwrap_or_else(|e| e.into_inner()));
+        serials
+            .into_iter()
+            .map(|serial| {
+                let serial =
+                    serial.map_err(|()| dbus::Error::new_failed("disconnected from D-Bus"))?;
+                let Some(mut reply) = replies.remove(&serial) else {
+                    return Err(dbus::Error::new_custom(
+                        "org.freedesktop.DBus.Error.NoReply",
+                        "no reply within the call timeout",
+                    ));
+                };
+                reply.as_result().map(|_| ())?;
+                Ok(reply)
+            })
+            .collect()
+    }
+}
+
+impl Call for SharedConnection {
+    fn call_raw(
+        &self,
+        message: Message,
+        timeout: Duration,
+    ) -> std::result::Result<Message, dbus::Error> {
+        let _io = self.io.lock().unwrap_or_else(|e| e.into_inner());
+        self.connection.call_raw(message, timeout)
+    }
+
+    fn is_bus(&self) -> bool {
+        self.connection.is_bus()
+    }
+
+    fn spec_versions(&self) -> Option<&SpecVersions> {
+        Some(&self.spec_versions)
+    }
+}
+
+impl fmt::Debug for SharedConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedConnection")
+            .field("unique_name", &self.connection.channel().unique_name())
+            .finish_non_exhaustive()
+    }
 }
 
+/// How long to wait for the reply to a method call, unless configured otherwise.
+pub(crate) const DEFAULT_CALL_TIMEOUT: Duration = Duration::from_millis(2000);
+
+/// Sends the notification, returns its id and the unique name of the server that showed it.
 pub fn send_notification_via_connection_at_bus(
     notification: &Notification,
     id: u32,
-    connection: &Connection,
+    connection: &impl Call,
     bus: NotificationBus,
-) -> Result<u32> {
-    let mut message = build_message("Notify", bus);
-    let timeout: i32 = notification.timeout.into();
+    timeout: Duration,
+) -> Result<(u32, Option<String>)> {
+    let spec_version = spec_version(notification, connection, &bus, timeout);
+    let message = notify_message(notification, id, bus, spec_version)?;
+    let reply = call(connection, message, timeout)?;
+    Ok(notify_reply(&reply))
+}
+
+/// The spec version of the server at `bus`, which decides the name of the image data hint.
+///
+/// Only asked for when `notification` carries image data.
+fn spec_version(
+    notification: &Notification,
+    connection: &impl Call,
+    bus: &NotificationBus,
+    timeout: Duration,
+) -> Version {
+    if !notification.has_image_data() {
+        return xdg::FALLBACK_SPEC_VERSION;
+    }
+    let ask = || get_server_information_via_connection_at_bus(connection, bus.clone(), timeout);
+    let Some(versions) = connection.spec_versions() else {
+        return xdg::negotiated_spec_version(ask());
+    };
+    // a peer is the server, on a bus the name may have changed hands since
+    let server = match name_owner(connection, bus.name(), timeout) {
+        Ok(Some(owner)) => owner,
+        Ok(None) if !connection.is_bus() => String::new(),
+        Ok(None) => return xdg::negotiated_spec_version(ask()),
+        Err(error) => return xdg::negotiated_spec_version(Err(error)),
+    };
+    match versions.get(&server) {
+        Some(version) => version,
+        None => versions.negotiate(server, ask()),
+    }
+}
+
+/// The `Notify` call for `notification`, replacing the notification `id` unless it is 0.
+///
+/// Hints are encoded for a server following `spec_version`.
+pub(crate) fn notify_message(
+    notification: &Notification,
+    id: u32,
+    bus: NotificationBus,
+    spec_version: Version,
+) -> Result<Message> {
+    let mut message = build_message("Notify", bus)?;
+    let expire_timeout: i32 = notification.timeout.into();
     message.append_items(&[
         notification.appname.to_owned().into(), // appname
         id.into(),                              // notification to update
@@ -135,18 +782,310 @@ pub fn send_notification_via_connection_at_bus(
         notification.summary.to_owned().into(), // summary (title)
         notification.body.to_owned().into(),    // body
         pack_actions(notification),             // actions
-        pack_hints(notification)?,              // hints
-        timeout.into(),                         // timeout
     ]);
+    let mut args = IterAppend::new(&mut message);
+    append_hints(notification, spec_version, &mut args); // hints
+    args.append(expire_timeout); // timeout
+    Ok(message)
+}
 
-    let reply = connection.send_with_reply_and_block(message, 2000)?;
-
+/// The id and the unique name of the server from the reply to `Notify`.
+pub(crate) fn notify_reply(reply: &Message) -> (u32, Option<String>) {
+    let server = reply.sender().map(|sender| sender.to_string());
     match reply.get_items().first() {
-        Some(MessageItem::UInt32(ref id)) => Ok(*id),
-        _ => Ok(0),
+        Some(MessageItem::UInt32(ref id)) => (*id, server),
+        _ => (0, server),
     }
 }
 
+/// [`SharedConnection::call_all()`] for messages that may have failed to build, which are not sent.
+fn pipeline(
+    connection: &SharedConnection,
+    messages: Vec<Result<Message>>,
+    timeout: Duration,
+) -> Vec<std::result::Result<Message, dbus::Error>> {
+    let mut built = Vec::new();
+    let slots: Vec<_> = messages
+        .into_iter()
+        .map(|message| message.map(|message| built.push(message)))
+        .collect();
+    let mut replies = connection.call_all(built, timeout).into_iter();
+    slots
+        .into_iter()
+        .map(|slot| match slot {
+            Ok(()) => replies
+                .next()
+                .unwrap_or_else(|| Err(dbus::Error::new_failed("missing reply"))),
+            Err(error) => Err(dbus::Error::new_failed(&error.to_string())),
+        })
+        .collect()
+}
+
+/// Like [`send_subscribed()`] for many notifications, with the calls of all of them pipelined.
+///
+/// Returns the results in the order of `notifications`.
+pub(crate) fn send_all_subscribed(
+    connection: &Arc<SharedConnection>,
+    notifications: &[&Notification],
+    timeout: Duration,
+) -> Vec<Result<(u32, Option<String>, Subscription)>> {
+    let started = Instant::now();
+    // the rules for buses the connection did not show anything on yet
+    let mut rules: Vec<String> = Vec::new();
+    let mut messages = Vec::new();
+    for notification in notifications {
+        let rule = responses_rule(&notification.bus);
+        if connection.is_bus() && !connection.has_match(&rule) && !rules.contains(&rule) {
+            messages.push(bus_message("AddMatch", &rule));
+            rules.push(rule);
+        }
+    }
+    let mut replies = pipeline(connection, messages, timeout).into_iter();
+    let mut next_reply = || {
+        replies
+            .next()
+            .unwrap_or_else(|| Err(dbus::Error::new_failed("missing reply")))
+    };
+    let mut failed_rules = Vec::new();
+    for rule in rules {
+        match next_reply() {
+            Ok(_) => connection.added_match(&rule),
+            Err(error) => failed_rules.push((rule, error)),
+        }
+    }
+    // the servers that get image data are asked once per batch at most, usually once per connection
+    let mut negotiated: Vec<(&str, Version)> = Vec::new();
+    let spec_versions: Vec<Version> = notifications
+        .iter()
+        .map(|notification| {
+            if !notification.has_image_data() {
+                return xdg::FALLBACK_SPEC_VERSION;
+            }
+            let name = notification.bus.name();
+            if let Some(&(_, version)) = negotiated.iter().find(|(asked, _)| *asked == name) {
+                return version;
+            }
+            let version = spec_version(notification, connection, &notification.bus, timeout);
+            negotiated.push((name, version));
+            version
+        })
+        .collect();
+
+    let mut messages = Vec::new();
+    let prepared: Vec<Result<_>> = notifications
+        .iter()
+        .zip(spec_versions)
+        .map(|(notification, spec_version)| {
+            let rule = responses_rule(&notification.bus);
+            if let Some((_, error)) = failed_rules.iter().find(|(failed, _)| *failed == rule) {
+                return Err(dbus::Error::new_failed(&error.to_string()).into());
+            }
+            messages.push(notify_message(
+                notification,
+                notification.id.unwrap_or(0),
+                notification.bus.clone(),
+                spec_version,
+            ));
+            Ok(Subscription::local(
+                connection,
+                responses_match(&notification.bus),
+            ))
+        })
+        .collect();
+
+    let mut replies = pipeline(connection, messages, timeout)
+        .into_iter()
+        .map(|reply| reply.map_err(|error| call_error(error, started, timeout)));
+    prepared
+        .into_iter()
+        .map(|prepared| {
+            let responses = prepared?;
+            let reply = replies
+                .next()
+                .unwrap_or_else(|| Err(dbus::Error::new_failed("missing reply").into()))?;
+            let (id, server) = notify_reply(&reply);
+            Ok((id, server, responses))
+        })
+        .collect()
+}
+
+/// Subscribes to the responses of the server, then calls `send`.
+///
+/// The bus is asked for the responses once per connection and notification bus.
+/// Signals the server sends right away, e.g. because of a tiny timeout, are queued by the subscription
+/// instead of getting lost before anyone listens.
+pub(crate) fn send_subscribed(
+    connection: &Arc<SharedConnection>,
+    bus: &NotificationBus,
+    send: impl FnOnce() -> Result<(u32, Option<String>)>,
+) -> Result<(u32, Option<String>, Subscription)> {
+    let responses = Subscription::responses(connection, bus)?;
+    let (id, server) = send()?;
+    Ok((id, server, responses))
+}
+
+/// Signals matching a rule, queued for whoever listens to them.
+///
+/// The filter is removed again once this is dropped, the match rule stays with the connection.
+pub(crate) struct Subscription {
+    connection: Arc<SharedConnection>,
+    token: Token,
+    signals: Arc<Mutex<VecDeque<Message>>>,
+}
+
+impl Subscription {
+    /// Subscribes to the signals from the object path of `bus`, see [`responses_rule()`].
+    fn responses(
+        connection: &Arc<SharedConnection>,
+        bus: &NotificationBus,
+    ) -> Result<Subscription> {
+        Subscription::add(connection, responses_match(bus), &responses_rule(bus))
+    }
+
+    /// Subscribes to owner changes of the well-known `name`.
+    fn owner_changes(connection: &Arc<SharedConnection>, name: &str) -> Result<Subscription> {
+        Subscription::add(connection, owner_changes_match(), &owner_changes_rule(name))
+    }
+
+    /// Queues the signals `local` matches, after asking the bus for the ones `rule` matches.
+    fn add(
+        connection: &Arc<SharedConnection>,
+        local: MatchRule<'static>,
+        rule: &str,
+    ) -> Result<Subscription> {
+        let subscription = Subscription::local(connection, local);
+        connection.add_match(rule)?;
+        Ok(subscription)
+    }
+
+    /// Queues the signals `local` matches, without asking the bus for any.
+    fn local(connection: &Arc<SharedConnection>, local: MatchRule<'static>) -> Subscription {
+        let signals = Arc::new(Mutex::new(VecDeque::new()));
+        let queue = Arc::downgrade(&signals);
+        let token = connection.connection.start_receive(
+            local,
+            Box::new(move |message, _| {
+                // a filter that is being called while its subscription is dropped comes back, this removes it
+                let Some(queue) = Weak::upgrade(&queue) else {
+                    return false;
+                };
+                queue
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push_back(message);
+                true
+            }),
+        );
+        Subscription {
+            connection: connection.clone(),
+            token,
+            signals,
+        }
+    }
+
+    /// The oldest signal nobody looked at yet.
+    fn pop(&self) -> Option<Message> {
+        self.signals
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
+    }
+}
+
+impl fmt::Debug for Subscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription").finish_non_exhaustive()
+    }
+}
+
+/// The match rule for the signals from the object path of `bus`.
+///
+/// The sender is the well-known name of `bus`, which the bus matches against whoever owns it at the time,
+/// so the rule outlives server restarts. Listeners check for the unique name of the server on top.
+pub(crate) fn responses_rule(bus: &NotificationBus) -> String {
+    format!(
+        "type='signal',sender='{}',path='{}',interface='{NOTIFICATION_INTERFACE}'",
+        bus.name(),
+        bus.path()
+    )
+}
+
+/// Matches the signals from the object path of `bus` locally, the sender is up to the listener.
+pub(crate) fn responses_match(bus: &NotificationBus) -> MatchRule<'static> {
+    MatchRule::new()
+        .with_type(MessageType::Signal)
+        .with_path(bus.path().to_owned())
+        .with_interface(NOTIFICATION_INTERFACE)
+}
+
+/// The match rule for owner changes of the well-known `name`.
+pub(crate) fn owner_changes_rule(name: &str) -> String {
+    format!(
+        "type='signal',sender='org.freedesktop.DBus',interface='org.freedesktop.DBus',member='NameOwnerChanged',arg0='{name}'"
+    )
+}
+
+/// Matches owner changes locally, the name is up to the listener.
+pub(crate) fn owner_changes_match() -> MatchRule<'static> {
+    MatchRule::new_signal("org.freedesktop.DBus", "NameOwnerChanged")
+        .with_sender("org.freedesktop.DBus")
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.connection.connection.stop_receive(self.token);
+    }
+}
+
+/// Returns the unique name of the current owner of `name`, if any.
+fn name_owner(connection: &impl Call, name: &str, timeout: Duration) -> Result<Option<String>> {
+    if !connection.is_bus() {
+        return Ok(None);
+    }
+    name_owner_reply(connection.call_raw(bus_message("GetNameOwner", name)?, timeout))
+}
+
+/// A method call to the bus itself with a single string argument.
+pub(crate) fn bus_message(method_name: &str, argument: &str) -> Result<Message> {
+    let mut message = Message::new_method_call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        method_name,
+    )
+    .map_err(ErrorKind::Conversion)?;
+    message.append_items(&[argument.into()]);
+    Ok(message)
+}
+
+/// The owner from the reply to `GetNameOwner`, `None` if the name has none.
+pub(crate) fn name_owner_reply(
+    reply: std::result::Result<Message, dbus::Error>,
+) -> Result<Option<String>> {
+    match reply {
+        Ok(reply) => match reply.get_items().first() {
+            Some(MessageItem::Str(owner)) => Ok(Some(owner.clone())),
+            _ => Ok(None),
+        },
+        Err(error) if error.name() == Some("org.freedesktop.DBus.Error.NameHasNoOwner") => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+pub(crate) fn connect(address: &BusAddress) -> Result<Connection> {
+    let connection = match address {
+        BusAddress::Session => Connection::get_private(BusType::Session)?,
+        BusAddress::System => Connection::get_private(BusType::System)?,
+        BusAddress::Address(address) => {
+            let connection = Connection::open_private(address)?;
+            connection.register()?;
+            connection
+        }
+        BusAddress::PeerToPeer(address) => Connection::open_private(address)?,
+    };
+    Ok(connection)
+}
+
 pub fn connect_and_send_notification(
     notification: &Notification,
 ) -> Result<DbusNotificationHandle> {
@@ -154,47 +1093,284 @@ pub fn connect_and_send_notification(
     connect_and_send_notification_at_bus(notification, bus)
 }
 
+/// Like [`connect()`], for a connection that can be shared between threads.
+fn connect_sync(address: &BusAddress) -> Result<SyncConnection> {
+    Ok(SyncConnection::from(open_channel(address)?))
+}
+
+/// Like [`connect()`], for a connection that handles and subscriptions can share between threads.
+pub(crate) fn connect_shared(address: &BusAddress) -> Result<Arc<SharedConnection>> {
+    Ok(Arc::new(SharedConnection::new(connect_sync(address)?)))
+}
+
+/// Opens a channel to `address` and says hello to the bus, if there is one.
+pub(crate) fn open_channel(address: &BusAddress) -> Result<Channel> {
+    let channel = match address {
+        BusAddress::Session => Channel::get_private(dbus::channel::BusType::Session)?,
+        BusAddress::System => Channel::get_private(dbus::channel::BusType::System)?,
+        BusAddress::Address(address) => {
+            let mut channel = Channel::open_private(address)?;
+            channel.register()?;
+            channel
+        }
+        BusAddress::PeerToPeer(address) => Channel::open_private(address)?,
+    };
+    Ok(channel)
+}
+
 pub fn connect_and_send_notification_at_bus(
     notification: &Notification,
     bus: NotificationBus,
 ) -> Result<DbusNotificationHandle> {
-    let connection = Connection::get_private(BusType::Session)?;
+    let connection = connect_shared(&BusAddress::Session)?;
     let inner_id = notification.id.unwrap_or(0);
-    let id = send_notification_via_connection_at_bus(notification, inner_id, &connection, bus)?;
+    let (id, server, responses, dispatcher) = if notification.action_callbacks.is_empty() {
+        let (id, server, responses) = send_subscribed(&connection, &bus, || {
+            send_notification_via_connection_at_bus(
+                notification,
+                inner_id,
+                &connection,
+                bus.clone(),
+                DEFAULT_CALL_TIMEOUT,
+            )
+        })?;
+        (id, server, responses, None)
+    } else {
+        let thread = session_dispatcher()?;
+        let mut notification = notification.clone();
+        notification.bus = bus.clone();
+        let (id, server, responses) = send_subscribed(&connection, &bus, || {
+            thread.send(&notification, inner_id, DEFAULT_CALL_TIMEOUT)
+        })?;
+        (id, server, responses, Some(thread.dispatcher))
+    };
 
     Ok(DbusNotificationHandle::new(
         id,
         connection,
+        BusAddress::Session,
         notification.clone(),
+        server,
+        DEFAULT_CALL_TIMEOUT,
+        Default::default(),
+        dispatcher,
+        Some(responses),
     ))
 }
 
-fn build_message(method_name: &str, bus: NotificationBus) -> Message {
-    Message::new_method_call(
-        bus.into_name(),
-        NOTIFICATION_OBJECTPATH,
-        NOTIFICATION_INTERFACE,
-        method_name,
-    )
-    .unwrap_or_else(|_| panic!("Error building message call {:?}.", method_name))
+/// The dispatcher thread shared by all notifications with callbacks that are not shown by a
+/// [`Notifier`](crate::Notifier).
+static SESSION_DISPATCHER: Mutex<Option<DispatcherThread>> = Mutex::new(None);
+
+fn session_dispatcher() -> Result<DispatcherThread> {
+    let mut shared = SESSION_DISPATCHER.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(ref thread) = *shared {
+        return Ok(thread.clone());
+    }
+    let thread = DispatcherThread::spawn(BusAddress::Session)?;
+    *shared = Some(thread.clone());
+    Ok(thread)
+}
+
+/// A notification to be sent by the [`DispatcherThread`].
+struct Show {
+    notification: Notification,
+    id: u32,
+    timeout: Duration,
+    reply: mpsc::Sender<Result<(u32, Option<String>)>>,
+}
+
+/// A thread that shows notifications with callbacks and routes the responses to its [`Dispatcher`].
+///
+/// `ffidisp` connections cannot be shared between threads, so the thread has a connection of its own
+/// and does the sending too, some servers only send the responses to the connection that showed the notification.
+/// The thread stops once every `DispatcherThread` is dropped.
+#[derive(Clone, Debug)]
+pub(crate) struct DispatcherThread {
+    commands: mpsc::Sender<Show>,
+    pub(crate) dispatcher: Arc<Dispatcher>,
+}
+
+impl DispatcherThread {
+    pub(crate) fn spawn(address: BusAddress) -> Result<DispatcherThread> {
+        let dispatcher = Arc::new(Dispatcher::default());
+        let (commands, received) = mpsc::channel::<Show>();
+        let (ready, connected) = mpsc::channel::<Result<()>>();
+
+        let shared = dispatcher.clone();
+        std::thread::Builder::new()
+            .name("notify-rust dispatcher".into())
+            .spawn(move || {
+                // the callbacks may use the blocking zbus API
+                #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+                super::blocking::mark_off_executor();
+                let connection = match connect(&address).and_then(|connection| {
+                    subscribe_responses(&connection)?;
+                    Ok(connection)
+                }) {
+                    Ok(connection) => connection,
+                    Err(error) => {
+                        let _ = ready.send(Err(error));
+                        return;
+                    }
+                };
+                let _ = ready.send(Ok(()));
+                loop {
+                    for item in connection.iter(50) {
+                        match item {
+                            ConnectionItem::Signal(message) => dispatch(&shared, &message),
+                            ConnectionItem::Nothing => break,
+                            _ => {}
+                        }
+                    }
+                    loop {
+                        match received.try_recv() {
+                            Ok(show) => {
+                                let result = send_notification_via_connection_at_bus(
+                                    &show.notification,
+                                    show.id,
+                                    &connection,
+                                    show.notification.bus.clone(),
+                                    show.timeout,
+                                );
+                                // registered before the next signal is looked at
+                                if let Ok((id, ref server)) = result {
+                                    shared.register(id, &show.notification, server.clone());
+                                }
+                                let _ = show.reply.send(result);
+                            }
+                            Err(mpsc::TryRecvError::Empty) => break,
+                            Err(mpsc::TryRecvError::Disconnected) => return,
+                        }
+                    }
+                }
+            })
+            .map_err(|e| ErrorKind::Msg(e.to_string()))?;
+
+        connected
+            .recv()
+            .map_err(|_| ErrorKind::Msg("dispatcher thread failed to start".into()))??;
+        Ok(DispatcherThread {
+            commands,
+            dispatcher,
+        })
+    }
+
+    /// Sends the notification from the dispatcher thread and registers its callbacks.
+    pub(crate) fn send(
+        &self,
+        notification: &Notification,
+        id: u32,
+        timeout: Duration,
+    ) -> Result<(u32, Option<String>)> {
+        let (reply, replied) = mpsc::channel();
+        self.commands
+            .send(Show {
+                notification: notification.clone(),
+                id,
+                timeout,
+                reply,
+            })
+            .map_err(|_| ErrorKind::Msg("dispatcher thread is gone".into()))?;
+        replied
+            .recv()
+            .map_err(|_| ErrorKind::Msg("dispatcher thread is gone".into()))?
+    }
 }
 
-pub fn pack_hints(notification: &Notification) -> Result<MessageItem> {
-    if !notification.hints.is_empty() || !notification.hints_unique.is_empty() {
-        let hints = notification
-            .get_hints()
-            .cloned()
-            .map(HintMessage::wrap_hint)
-            .collect::<Vec<(MessageItem, MessageItem)>>();
+fn subscribe_responses(connection: &Connection) -> Result<()> {
+    // peer-to-peer connections have no unique name and receive every signal anyway
+    if !connection.unique_name().is_empty() {
+        for member in ["ActionInvoked", "ActivationToken", "NotificationClosed"] {
+            connection.add_match(&format!(
+                "interface='{}',member='{}'",
+                NOTIFICATION_INTERFACE, member
+            ))?;
+        }
+    }
+    Ok(())
+}
 
-        if let Ok(array) = MessageItem::new_dict(hints) {
-            return Ok(array);
+pub(crate) fn dispatch(dispatcher: &Dispatcher, message: &Message) {
+    let (Some(path), Some(interface), Some(member)) =
+        (message.path(), message.interface(), message.member())
+    else {
+        return;
+    };
+    if &*interface != NOTIFICATION_INTERFACE {
+        return;
+    }
+    let sender = message.sender();
+    let sender = sender.as_deref();
+    match (&*member, message.get_items().as_slice()) {
+        ("ActionInvoked", [MessageItem::UInt32(id), MessageItem::Str(action), ..]) => {
+            dispatcher.action_invoked(&path, sender, *id, action);
+        }
+        ("ActivationToken", [MessageItem::UInt32(id), MessageItem::Str(token), ..]) => {
+            dispatcher.activation_token(&path, sender, *id, token);
         }
+        ("NotificationClosed", [MessageItem::UInt32(id), MessageItem::UInt32(reason), ..]) => {
+            dispatcher.closed(&path, sender, *id, (*reason).into());
+        }
+        _ => {}
     }
+}
 
-    Ok(MessageItem::Array(
-        MessageItemArray::new(vec![], "a{sv}".into()).unwrap(),
-    ))
+pub fn close_notification_via_connection_at_bus(
+    id: u32,
+    connection: &impl Call,
+    bus: NotificationBus,
+    timeout: Duration,
+) -> Result<()> {
+    call(connection, close_message(id, bus)?, timeout)?;
+    Ok(())
+}
+
+pub(crate) fn close_message(id: u32, bus: NotificationBus) -> Result<Message> {
+    let mut message = build_message("CloseNotification", bus)?;
+    message.append_items(&[id.into()]);
+    Ok(message)
+}
+
+fn duration_ms(duration: Duration) -> i32 {
+    i32::try_from(duration.as_millis()).unwrap_or(i32::MAX)
+}
+
+/// Sends a method call and blocks until the reply arrives or `timeout` has passed.
+fn call(connection: &impl Call, message: Message, timeout: Duration) -> Result<Message> {
+    let started = Instant::now();
+    connection
+        .call_raw(message, timeout)
+        .map_err(|error| call_error(error, started, timeout))
+}
+
+/// The error of a call that was started at `started`, telling an expired `timeout` apart.
+fn call_error(error: dbus::Error, started: Instant, timeout: Duration) -> Error {
+    // libdbus reports an expired timeout as `NoReply`, just like a peer that went away
+    if error.name() == Some("org.freedesktop.DBus.Error.NoReply") && started.elapsed() >= timeout {
+        ErrorKind::CallTimeout.into()
+    } else {
+        error.into()
+    }
+}
+
+pub(crate) fn build_message(method_name: &str, bus: NotificationBus) -> Result<Message> {
+    let (name, path) = bus.into_parts();
+    let name = dbus::strings::BusName::new(name).map_err(ErrorKind::Conversion)?;
+    let path = dbus::strings::Path::new(path).map_err(ErrorKind::Conversion)?;
+    Message::new_method_call(name, path, NOTIFICATION_INTERFACE, method_name)
+        .map_err(|error| ErrorKind::Conversion(error).into())
+}
+
+/// Appends the hints as `a{sv}`.
+fn append_hints(notification: &Notification, spec_version: Version, args: &mut IterAppend) {
+    args.append_dict(&"s".into(), &"v".into(), |dict| {
+        for hint in notification.get_hints() {
+            dict.append_dict_entry(|entry| {
+                HintMessage::from(hint.clone()).append_entry(spec_version, entry);
+            });
+        }
+    });
 }
 
 pub fn pack_actions(notification: &Notification) -> MessageItem {
@@ -212,11 +1388,27 @@ pub fn pack_actions(notification: &Notification) -> MessageItem {
 }
 
 pub fn get_capabilities() -> Result<Vec<String>> {
-    let mut capabilities = vec![];
+    get_capabilities_at_bus(Default::default())
+}
 
-    let message = build_message("GetCapabilities", Default::default());
+pub fn get_capabilities_at_bus(bus: NotificationBus) -> Result<Vec<String>> {
     let connection = Connection::get_private(BusType::Session)?;
-    let reply = connection.send_with_reply_and_block(message, 2000)?;
+    get_capabilities_via_connection_at_bus(&connection, bus, DEFAULT_CALL_TIMEOUT)
+}
+
+pub fn get_capabilities_via_connection_at_bus(
+    connection: &impl Call,
+    bus: NotificationBus,
+    timeout: Duration,
+) -> Result<Vec<String>> {
+    let message = build_message("GetCapabilities", bus)?;
+    let reply = call(connection, message, timeout)?;
+    Ok(capabilities_reply(&reply))
+}
+
+/// The capabilities from the reply to `GetCapabilities`.
+pub(crate) fn capabilities_reply(reply: &Message) -> Vec<String> {
+    let mut capabilities = vec![];
 
     if let Some(MessageItem::Array(items)) = reply.get_items().first() {
         for item in items.iter() {
@@ -226,7 +1418,7 @@ pub fn get_capabilities() -> Result<Vec<String>> {
         }
     }
 
-    Ok(capabilities)
+    capabilities
 }
 
 fn unwrap_message_string(item: Option<&MessageItem>) -> String {
@@ -236,97 +1428,475 @@ fn unwrap_message_string(item: Option<&MessageItem>) -> String {
     }
 }
 
-#[allow(clippy::get_first)]
 pub fn get_server_information() -> Result<ServerInformation> {
-    let message = build_message("GetServerInformation", Default::default());
+    get_server_information_at_bus(Default::default())
+}
+
+pub fn get_server_information_at_bus(bus: NotificationBus) -> Result<ServerInformation> {
     let connection = Connection::get_private(BusType::Session)?;
-    let reply = connection.send_with_reply_and_block(message, 2000)?;
+    get_server_information_via_connection_at_bus(&connection, bus, DEFAULT_CALL_TIMEOUT)
+}
 
+pub fn get_server_information_via_connection_at_bus(
+    connection: &impl Call,
+    bus: NotificationBus,
+    timeout: Duration,
+) -> Result<ServerInformation> {
+    let message = build_message("GetServerInformation", bus)?;
+    let reply = call(connection, message, timeout)?;
+    Ok(server_information_reply(&reply))
+}
+
+/// The [`ServerInformation`] from the reply to `GetServerInformation`.
+#[allow(clippy::get_first)]
+pub(crate) fn server_information_reply(reply: &Message) -> ServerInformation {
     let items = reply.get_items();
 
-    Ok(ServerInformation {
+    ServerInformation {
         name: unwrap_message_string(items.get(0)),
         vendor: unwrap_message_string(items.get(1)),
         version: unwrap_message_string(items.get(2)),
         spec_version: unwrap_message_string(items.get(3)),
-    })
+    }
+}
+
+/// An entry of logind's `ListSessions`: id, uid, user name, seat and object path.
+pub(crate) type SessionRow = (String, u32, String, String, dbus::Path<'static>);
+
+pub(crate) fn list_graphical_sessions() -> Result<Vec<LogindSession>> {
+    use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+
+    let connection = dbus::blocking::Connection::new_system()?;
+    let manager = connection.with_proxy(
+        session::LOGIND_BUS,
+        session::LOGIND_OBJECTPATH,
+        DEFAULT_CALL_TIMEOUT,
+    );
+    let (sessions,): (Vec<SessionRow>,) =
+        manager.method_call(session::LOGIND_MANAGER_INTERFACE, "ListSessions", ())?;
+
+    let mut graphical = Vec::new();
+    for (id, uid, user, _seat, path) in sessions {
+        let proxy = connection.with_proxy(session::LOGIND_BUS, path, DEFAULT_CALL_TIMEOUT);
+        let active: bool = proxy.get(session::LOGIND_SESSION_INTERFACE, "Active")?;
+        let session_type: String = proxy.get(session::LOGIND_SESSION_INTERFACE, "Type")?;
+        let class: String = proxy.get(session::LOGIND_SESSION_INTERFACE, "Class")?;
+        if session::is_graphical(active, &session_type, &class) {
+            graphical.push(LogindSession { id, uid, user });
+        }
+    }
+    Ok(graphical)
+}
+
+/// Waits until `name` has an owner on the bus, returns `false` if that did not happen within `timeout`.
+///
+/// Peer-to-peer connections have no names, the peer is taken to be there.
+pub(crate) fn wait_for_name_owner(
+    connection: &Arc<SharedConnection>,
+    name: &str,
+    timeout: Duration,
+) -> Result<bool> {
+    if !connection.is_bus() {
+        return Ok(true);
+    }
+    let deadline = Instant::now() + timeout;
+
+    // subscribe before asking, so an owner showing up in between is not missed
+    let owner_changes = Subscription::owner_changes(connection, name)?;
+    if name_owner(connection, name, timeout)?.is_some() {
+        return Ok(true);
+    }
+
+    loop {
+        while let Some(message) = owner_changes.pop() {
+            if let [MessageItem::Str(changed), _, MessageItem::Str(new_owner)] =
+                message.get_items().as_slice()
+            {
+                if changed == name && !new_owner.is_empty() {
+                    return Ok(true);
+                }
+            }
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(false);
+        }
+        connection.process(remaining.min(POLL_INTERVAL))?;
+    }
+}
+
+/// Whether somebody owns `name` right now, without waiting.
+pub(crate) fn has_name_owner(
+    connection: &Arc<SharedConnection>,
+    name: &str,
+    timeout: Duration,
+) -> Result<bool> {
+    // peer-to-peer connections talk to their server directly
+    Ok(!connection.is_bus() || name_owner(connection, name, timeout)?.is_some())
 }
 
 /// Listens for the `ActionInvoked(UInt32, String)` Signal.
 ///
 /// No need to use this, check out `Notification::show_and_wait_for_action(FnOnce(action:&str))`
 pub fn handle_action(id: u32, func: impl ResponseHandler) -> Result<()> {
-    let connection = Connection::get_private(BusType::Session)?;
-    wait_for_action_signal(&connection, id, func)
+    let connection = connect_shared(&BusAddress::Session)?;
+    wait_for_action_signal(&connection, &Default::default(), id, func)
 }
 
 // Listens for the `ActionInvoked(UInt32, String)` signal.
-fn wait_for_action_signal(
-    connection: &Connection,
+pub(crate) fn wait_for_action_signal(
+    connection: &Arc<SharedConnection>,
+    bus: &NotificationBus,
     id: u32,
     handler: impl ResponseHandler,
 ) -> Result<()> {
-    connection.add_match(&format!(
-        "interface='{}',member='ActionInvoked'",
-        NOTIFICATION_INTERFACE
-    ))?;
-    connection.add_match(&format!(
-        "interface='{}',member='NotificationClosed'",
-        NOTIFICATION_INTERFACE
-    ))?;
-
-    for item in connection.iter(1000) {
-        if let ConnectionItem::Signal(message) = item {
-            let items = message.get_items();
-
-            let (path, interface, member) = (
-                message.path().map_or_else(String::new, |p| {
-                    p.into_cstring().to_string_lossy().into_owned()
-                }),
-                message.interface().map_or_else(String::new, |p| {
-                    p.into_cstring().to_string_lossy().into_owned()
-                }),
-                message.member().map_or_else(String::new, |p| {
-                    p.into_cstring().to_string_lossy().into_owned()
-                }),
+    let id = Arc::new(AtomicU32::new(id));
+    wait_for_response(ResponseListener::new(connection, bus, id, None), handler)
+}
+
+/// The server that showed a notification, and what to do once it is gone.
+#[derive(Debug)]
+pub(crate) struct Restart {
+    pub server: String,
+    pub notification: Notification,
+    pub policy: ServerRestartPolicy,
+    pub call_timeout: Duration,
+}
+
+/// Calls `handler` with the first action or close of the notification `listener` follows.
+fn wait_for_response(mut listener: ResponseListener, handler: impl ResponseHandler) -> Result<()> {
+    listener.subscribe()?;
+    if let Some(response) = listener.next() {
+        handler.call(&response);
+        return Ok(());
+    }
+    if listener.vanished() {
+        return Err(ErrorKind::ServerVanished.into());
+    }
+    Ok(())
+}
+
+/// Follows the signals of one notification until it is closed.
+///
+/// Only signals from the server that showed the notification and from the object path of its bus count.
+/// The match rules are removed once the notification is closed or the listener dropped.
+///
+/// With a [`Restart`] it also watches the server that showed the notification and applies its policy
+/// when the server goes away.
+#[derive(Debug)]
+pub(crate) struct ResponseListener {
+    connection: Arc<SharedConnection>,
+    bus: NotificationBus,
+    /// Shared with the handle, so it learns about the id the notification got when shown again
+    id: Arc<AtomicU32>,
+    restart: Option<Restart>,
+    /// `None` while the name has no owner
+    server: Option<String>,
+    responses: Option<Subscription>,
+    owner_changes: Option<Subscription>,
+    /// Those of the connection the notification was shown over, see [`Expiries`].
+    expiries: Option<Arc<Expiries>>,
+    activation_token: ActivationTokenSlot,
+    closed: bool,
+    /// Set once the server went away and took the notification with it.
+    vanished: bool,
+}
+
+impl ResponseListener {
+    pub(crate) fn new(
+        connection: &Arc<SharedConnection>,
+        bus: &NotificationBus,
+        id: Arc<AtomicU32>,
+        restart: Option<Restart>,
+    ) -> ResponseListener {
+        ResponseListener {
+            connection: connection.clone(),
+            bus: bus.clone(),
+            id,
+            server: restart.as_ref().map(|restart| restart.server.clone()),
+            restart,
+            responses: None,
+            owner_changes: None,
+            expiries: None,
+            activation_token: Default::default(),
+            closed: false,
+            vanished: false,
+        }
+    }
+
+    /// Uses the responses subscribed to before the notification was sent.
+    pub(crate) fn subscribed_with(mut self, responses: Subscription) -> ResponseListener {
+        self.responses = Some(responses);
+        self
+    }
+
+    /// Reports the close by `expiries` as expired.
+    pub(crate) fn expiring(mut self, expiries: Arc<Expiries>) -> ResponseListener {
+        self.expiries = Some(expiries);
+        self
+    }
+
+    /// Keeps the activation tokens in `slot`, where the handle finds them.
+    pub(crate) fn recording_tokens(mut self, slot: ActivationTokenSlot) -> ResponseListener {
+        self.activation_token = slot;
+        self
+    }
+
+    fn subscribe(&mut self) -> Result<()> {
+        if self.restart.is_some() && self.owner_changes.is_none() {
+            self.owner_changes = Some(Subscription::owner_changes(
+                &self.connection,
+                self.bus.name(),
+            )?);
+        }
+        if self.responses.is_none() {
+            self.responses = Some(Subscription::responses(&self.connection, &self.bus)?);
+        }
+        Ok(())
+    }
+
+    /// The next response, blocks until there is one, `None` once the notification is closed.
+    pub(crate) fn next(&mut self) -> Option<NotificationResponse> {
+        self.next_before(None)
+    }
+
+    /// Like [`next()`](Self::next), but also `None` once `deadline` passed.
+    pub(crate) fn next_before(
+        &mut self,
+        deadline: Option<Instant>,
+    ) -> Option<NotificationResponse> {
+        if self.closed {
+            return None;
+        }
+        if let Err(error) = self.subscribe() {
+            log::warn!("failed to subscribe to notification signals: {error}");
+            self.close();
+            return None;
+        }
+        let mut response = self.receive(deadline);
+        if let (Some(NotificationResponse::Closed(ref mut reason)), Some(expiries)) =
+            (&mut response, &self.expiries)
+        {
+            *reason = expiries.closed(&self.id, *reason);
+        }
+        if matches!(response, Some(NotificationResponse::Closed(_))) {
+            self.close();
+        }
+        response
+    }
+
+    /// Whether the listener stopped because the server went away.
+    pub(crate) fn vanished(&self) -> bool {
+        self.vanished
+    }
+
+    /// Whether the listener stopped, no more responses are reported.
+    #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Gives up on the notification, the server that showed it is gone.
+    fn vanish(&mut self) -> Option<NotificationResponse> {
+        self.vanished = true;
+        self.close();
+        None
+    }
+
+    /// Removes the match rules, no more responses are reported.
+    pub(crate) fn close(&mut self) {
+        self.closed = true;
+        self.responses = None;
+        self.owner_changes = None;
+    }
+
+    fn receive(&mut self, deadline: Option<Instant>) -> Option<NotificationResponse> {
+        // read the socket at least once, so a deadline that passed still picks up what arrived
+        let mut read = false;
+        loop {
+            let queued = [&self.responses, &self.owner_changes]
+                .into_iter()
+                .flatten()
+                .find_map(Subscription::pop);
+            let Some(message) = queued else {
+                let wait = deadline.map_or(POLL_INTERVAL, |deadline| {
+                    deadline
+                        .saturating_duration_since(Instant::now())
+                        .min(POLL_INTERVAL)
+                });
+                if wait.is_zero() && read {
+                    return None;
+                }
+                read = true;
+                if let Err(error) = self.connection.process(wait) {
+                    log::warn!(
+                        "lost the connection while waiting for notification signals: {error}"
+                    );
+                    self.close();
+                    return None;
+                }
+                continue;
+            };
+            let signal = classify(
+                &message,
+                &self.bus,
+                self.id.load(Ordering::SeqCst),
+                self.server.as_deref(),
+                self.restart.is_some(),
             );
-            match (path.as_str(), interface.as_str(), member.as_str()) {
-                // match (protocol.unwrap(), iface.unwrap(), member.unwrap()) {
-                // Action Invoked
-                (path, interface, "ActionInvoked")
-                    if path == NOTIFICATION_OBJECTPATH && interface == NOTIFICATION_INTERFACE =>
-                {
-                    if let (&MessageItem::UInt32(nid), MessageItem::Str(ref action)) =
-                        (&items[0], &items[1])
-                    {
-                        if nid == id {
-                            let response = if action == "default" {
-                                NotificationResponse::Default
-                            } else {
-                                NotificationResponse::Action(action.to_string())
+            match signal {
+                Some(Signal::Response(response)) => return Some(response),
+                Some(Signal::ActivationToken(token)) => self.activation_token.set(token),
+                Some(Signal::OwnerChanged {
+                    old_owner,
+                    new_owner,
+                }) => {
+                    let Some(ref restart) = self.restart else {
+                        continue;
+                    };
+                    match restart.owner_changed(&mut self.server, &old_owner, &new_owner) {
+                        OwnerChange::Vanished => {
+                            return self.vanish();
+                        }
+                        OwnerChange::Reshow => {
+                            let sent = send_subscribed(&self.connection, &self.bus, || {
+                                send_notification_via_connection_at_bus(
+                                    &restart.notification,
+                                    0,
+                                    &self.connection,
+                                    self.bus.clone(),
+                                    restart.call_timeout,
+                                )
+                            });
+                            let Ok((new_id, new_server, responses)) = sent else {
+                                return self.vanish();
                             };
-                            handler.call(&response);
-                            break;
+                            self.id.store(new_id, Ordering::SeqCst);
+                            self.server = new_server.or(Some(new_owner));
+                            self.responses = Some(responses);
                         }
+                        OwnerChange::Unchanged => {}
                     }
                 }
+                None => {}
+            }
+        }
+    }
+}
 
-                // Notification Closed
-                (path, interface, "NotificationClosed")
-                    if path == NOTIFICATION_OBJECTPATH && interface == NOTIFICATION_INTERFACE =>
-                {
-                    if let (&MessageItem::UInt32(nid), &MessageItem::UInt32(reason)) =
-                        (&items[0], &items[1])
-                    {
-                        if nid == id {
-                            handler.call(&NotificationResponse::Closed(reason.into()));
-                            break;
-                        }
-                    }
-                }
-                (..) => (),
+/// What a signal means to the listener of one notification.
+pub(crate) enum Signal {
+    Response(NotificationResponse),
+    /// Sent right before the action it belongs to.
+    ActivationToken(String),
+    /// The well-known name of the bus changed its owner, an empty owner means none.
+    OwnerChanged {
+        old_owner: String,
+        new_owner: String,
+    },
+}
+
+/// Interprets `message` for the notification `id` that `server` showed at `bus`.
+///
+/// Without a `server` only listeners that do not follow restarts take any sender.
+pub(crate) fn classify(
+    message: &Message,
+    bus: &NotificationBus,
+    id: u32,
+    server: Option<&str>,
+    follows_restarts: bool,
+) -> Option<Signal> {
+    let items = message.get_items();
+
+    let (path, interface, member) = (
+        message.path().map_or_else(String::new, |p| {
+            p.into_cstring().to_string_lossy().into_owned()
+        }),
+        message.interface().map_or_else(String::new, |p| {
+            p.into_cstring().to_string_lossy().into_owned()
+        }),
+        message.member().map_or_else(String::new, |p| {
+            p.into_cstring().to_string_lossy().into_owned()
+        }),
+    );
+    let from_server = match (server, message.sender()) {
+        (Some(server), Some(sender)) => &*sender == server,
+        (None, _) => !follows_restarts,
+        (_, None) => true,
+    };
+    let ours = path == bus.path() && interface == NOTIFICATION_INTERFACE && from_server;
+    match member.as_str() {
+        // Action Invoked
+        "ActionInvoked" if ours => match items.as_slice() {
+            [MessageItem::UInt32(nid), MessageItem::Str(action), ..] if *nid == id => {
+                Some(Signal::Response(if action == "default" {
+                    NotificationResponse::Default
+                } else {
+                    NotificationResponse::Action(action.to_string())
+                }))
+            }
+            _ => None,
+        },
+
+        // Activation Token
+        "ActivationToken" if ours => match items.as_slice() {
+            [MessageItem::UInt32(nid), MessageItem::Str(token), ..] if *nid == id => {
+                Some(Signal::ActivationToken(token.clone()))
+            }
+            _ => None,
+        },
+
+        // Notification Closed
+        "NotificationClosed" if ours => match items.as_slice() {
+            [MessageItem::UInt32(nid), MessageItem::UInt32(reason), ..] if *nid == id => Some(
+                Signal::Response(NotificationResponse::Closed((*reason).into())),
+            ),
+            _ => None,
+        },
+
+        // Server Restarted
+        "NameOwnerChanged" if interface == "org.freedesktop.DBus" => match items.as_slice() {
+            [MessageItem::Str(name), MessageItem::Str(old_owner), MessageItem::Str(new_owner)]
+                if name == bus.name() =>
+            {
+                Some(Signal::OwnerChanged {
+                    old_owner: old_owner.clone(),
+                    new_owner: new_owner.clone(),
+                })
             }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// What to do about a notification once the owner of its bus name changed.
+pub(crate) enum OwnerChange {
+    /// The server that showed it is gone, and so is the notification.
+    Vanished,
+    /// Show it on the new server.
+    Reshow,
+    Unchanged,
+}
+
+impl Restart {
+    /// Applies the policy to an owner change, `server` is cleared once it is gone.
+    pub(crate) fn owner_changed(
+        &self,
+        server: &mut Option<String>,
+        old_owner: &str,
+        new_owner: &str,
+    ) -> OwnerChange {
+        if server.as_deref() == Some(old_owner) {
+            if self.policy == ServerRestartPolicy::Vanish {
+                return OwnerChange::Vanished;
+            }
+            *server = None;
+        }
+        if server.is_none() && !new_owner.is_empty() {
+            OwnerChange::Reshow
+        } else {
+            OwnerChange::Unchanged
         }
     }
-    Ok(())
 }
@@ -0,0 +1,311 @@
+//! Routes the signals of a connection to the callbacks of the notifications shown over it.
+//!
+//! Instead of every waiting handle subscribing to the signals and reading all messages on its own,
+//! one listener per connection feeds a [`Dispatcher`], which looks up the notification by id.
+//! The callbacks of a notification are dropped once it is closed, or once nothing follows it anymore:
+//! no handle, controller or listener, which all share its id.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{atomic::AtomicU32, Arc, Mutex, Weak},
+};
+
+use crate::{
+    notification::Notification,
+    response::{CloseReason, NotificationResponse},
+};
+
+/// Passed to the callbacks registered with [`Notification::action_with()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActionContext {
+    id: u32,
+    action: String,
+    activation_token: Option<String>,
+}
+
+impl ActionContext {
+    /// The id of the notification.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The identifier of the invoked action, `"default"` for the default action.
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+
+    /// The activation token the server sent along with the action, if any.
+    ///
+    /// Pass it on to the window you raise in response, e.g. via `XDG_ACTIVATION_TOKEN`.
+    pub fn activation_token(&self) -> Option<&str> {
+        self.activation_token.as_deref()
+    }
+}
+
+type ActionCallback = Arc<dyn Fn(&ActionContext) + Send + Sync>;
+type ResponseCallback = Arc<dyn Fn(NotificationResponse) + Send + Sync>;
+
+/// The callbacks attached to a notification, by action identifier.
+#[derive(Clone, Default)]
+pub(crate) struct ActionCallbacks {
+    actions: Vec<(String, ActionCallback)>,
+    /// Gets every action and the close, see [`observe()`](Self::observe).
+    responses: Option<ResponseCallback>,
+}
+
+impl ActionCallbacks {
+    pub(crate) fn push(&mut self, action: &str, callback: ActionCallback) {
+        self.actions.retain(|(existing, _)| existing != action);
+        self.actions.push((action.to_owned(), callback));
+    }
+
+    /// Calls `callback` with every response to the notification, the close included.
+    ///
+    /// Used by the [`NotificationService`](super::NotificationService), which has no thread to wait on.
+    pub(crate) fn observe(&mut self, callback: ResponseCallback) {
+        self.responses = Some(callback);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.actions.is_empty() && self.responses.is_none()
+    }
+
+    fn get(&self, action: &str) -> Option<ActionCallback> {
+        self.actions
+            .iter()
+            .find(|(existing, _)| existing == action)
+            .map(|(_, callback)| callback.clone())
+    }
+}
+
+impl fmt::Debug for ActionCallbacks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.actions.iter().map(|(action, _)| action))
+            .finish()
+    }
+}
+
+struct Registration {
+    callbacks: ActionCallbacks,
+    path: String,
+    /// Unique name of the server that showed the notification, `None` on peer-to-peer connections.
+    server: Option<String>,
+    activation_token: Option<String>,
+    /// The id shared by the handles of the notification, `None` until the first handle exists.
+    followed: Option<Weak<AtomicU32>>,
+}
+
+impl Registration {
+    fn is_followed(&self) -> bool {
+        self.followed
+            .as_ref()
+            .is_none_or(|followed| followed.strong_count() > 0)
+    }
+
+    fn matches(&self, path: &str, sender: Option<&str>) -> bool {
+        path == self.path
+            && match (&self.server, sender) {
+                (Some(server), Some(sender)) => server == sender,
+                _ => true,
+            }
+    }
+}
+
+/// The notifications with callbacks that were shown over one connection.
+#[derive(Default)]
+pub(crate) struct Dispatcher {
+    registrations: Mutex<HashMap<u32, Registration>>,
+}
+
+impl Dispatcher {
+    /// Routes the signals for `id` to the callbacks of `notification`, until it is closed or no longer followed.
+    ///
+    /// Registering an id again replaces the callbacks.
+    #[cfg(any(test, feature = "dbus"))]
+    pub(crate) fn register(&self, id: u32, notification: &Notification, server: Option<String>) {
+        self.reregister(id, id, notification, server);
+    }
+
+    /// Drops the callbacks registered for `id` once the last clone of `followed` is gone.
+    ///
+    /// Called by the first handle of a notification, `followed` is the id its handles share.
+    pub(crate) fn followed_by(&self, id: u32, followed: &Arc<AtomicU32>) {
+        if let Some(registration) = self.lock().get_mut(&id) {
+            registration.followed = Some(Arc::downgrade(followed));
+        }
+    }
+
+    /// Moves the registration of a notification that got a new id, e.g. after it was shown again.
+    pub(crate) fn reregister(
+        &self,
+        old_id: u32,
+        id: u32,
+        notification: &Notification,
+        server: Option<String>,
+    ) {
+        let mut registrations = self.lock();
+        let followed = registrations.remove(&old_id).and_then(|old| old.followed);
+        registrations.remove(&id);
+        // the callbacks of notifications nobody follows anymore go along
+        registrations.retain(|_, registration| registration.is_followed());
+        if notification.action_callbacks.is_empty() {
+            return;
+        }
+        registrations.insert(
+            id,
+            Registration {
+                callbacks: notification.action_callbacks.clone(),
+                path: notification.bus.path().to_owned(),
+                server,
+                activation_token: None,
+                followed,
+            },
+        );
+    }
+
+    pub(crate) fn action_invoked(&self, path: &str, sender: Option<&str>, id: u32, action: &str) {
+        let (callback, responses, activation_token) = {
+            let mut registrations = self.lock();
+            let Some(registration) = registrations.get_mut(&id) else {
+                return;
+            };
+            if !registration.matches(path, sender) || !registration.is_followed() {
+                return;
+            }
+            (
+                registration.callbacks.get(action),
+                registration.callbacks.responses.clone(),
+                registration.activation_token.take(),
+            )
+        };
+        // outside of the lock, the callback may well show another notification
+        if let Some(callback) = callback {
+            callback(&ActionContext {
+                id,
+                action: action.to_owned(),
+                activation_token,
+            });
+        }
+        if let Some(responses) = responses {
+            responses(NotificationResponse::from(action));
+        }
+    }
+
+    pub(crate) fn activation_token(&self, path: &str, sender: Option<&str>, id: u32, token: &str) {
+        if let Some(registration) = self.lock().get_mut(&id) {
+            if registration.matches(path, sender) {
+                registration.activation_token = Some(token.to_owned());
+            }
+        }
+    }
+
+    pub(crate) fn closed(&self, path: &str, sender: Option<&str>, id: u32, reason: CloseReason) {
+        let removed = {
+            let mut registrations = self.lock();
+            if registrations
+                .get(&id)
+                .is_some_and(|registration| registration.matches(path, sender))
+            {
+                registrations.remove(&id)
+            } else {
+                None
+            }
+        };
+        if let Some(responses) = removed
+            .filter(Registration::is_followed)
+            .and_then(|registration| registration.callbacks.responses)
+        {
+            responses(NotificationResponse::Closed(reason));
+        }
+    }
+
+    /// Whether callbacks are registered for `id`.
+    #[cfg(feature = "zbus")]
+    pub(crate) fn contains(&self, id: u32) -> bool {
+        self.lock().get(&id).is_some_and(Registration::is_followed)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u32, Registration>> {
+        // callbacks run outside of the lock, so a poisoned map is still consistent
+        self.registrations
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for Dispatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dispatcher")
+            .field("registered", &self.lock().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn routes_by_id_until_closed() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let notification = Notification::new()
+            .action_with("reply", "Reply", move |ctx| {
+                assert_eq!(ctx.action(), "reply");
+                assert_eq!(ctx.activation_token(), Some("token"));
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .finalize();
+        let path = notification.bus.path().to_owned();
+
+        let dispatcher = Dispatcher::default();
+        dispatcher.register(7, &notification, Some(":1.1".into()));
+
+        // other notifications, other servers
+        dispatcher.action_invoked(&path, Some(":1.1"), 8, "reply");
+        dispatcher.action_invoked(&path, Some(":1.2"), 7, "reply");
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        dispatcher.activation_token(&path, Some(":1.1"), 7, "token");
+        dispatcher.action_invoked(&path, Some(":1.1"), 7, "reply");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        dispatcher.closed(&path, Some(":1.1"), 7, CloseReason::Dismissed);
+        dispatcher.action_invoked(&path, Some(":1.1"), 7, "reply");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn dropped_with_the_last_handle() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let notification = Notification::new()
+            .action_with("reply", "Reply", move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .finalize();
+        let path = notification.bus.path().to_owned();
+
+        let dispatcher = Dispatcher::default();
+        dispatcher.register(7, &notification, None);
+        let followed = Arc::new(AtomicU32::new(7));
+        dispatcher.followed_by(7, &followed);
+        dispatcher.action_invoked(&path, None, 7, "reply");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // shown again, the handles follow along
+        dispatcher.reregister(7, 8, &notification, None);
+        dispatcher.action_invoked(&path, None, 8, "reply");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        drop(followed);
+        dispatcher.action_invoked(&path, None, 8, "reply");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        // forgotten with the next registration
+        dispatcher.register(9, &Notification::new(), None);
+        assert!(dispatcher.lock().is_empty());
+    }
+}
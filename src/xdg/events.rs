@@ -0,0 +1,209 @@
+//! Following a notification through all of its responses.
+
+use std::sync::{Arc, Mutex};
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+#[cfg(feature = "dbus")]
+use super::dbus_rs;
+#[cfg(feature = "zbus")]
+use super::zbus_rs;
+#[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+use super::{dbus_nonblock, queue::Queue};
+use crate::response::NotificationResponse;
+
+/// The responses to a shown notification, in the order they arrive.
+///
+/// Returned by [`NotificationHandle::events()`](crate::NotificationHandle::events).
+/// Unlike [`wait_for_response()`](crate::NotificationHandle::wait_for_response) this does not stop at the first action,
+/// so the buttons of a resident notification (see [`Hint::Resident`](crate::Hint::Resident)) can be used more than once.
+/// It ends after [`NotificationResponse::Closed`].
+///
+/// Iterating blocks until the next response; with the async API this is also a [`Stream`](futures_lite::Stream).
+///
+/// ```no_run
+/// # use notify_rust::*;
+/// let handle = Notification::new()
+///     .summary("Now playing")
+///     .action("play-pause", "Play/Pause")
+///     .action("next", "Next")
+///     .hint(Hint::Resident(true))
+///     .show()
+///     .unwrap();
+///
+/// for response in handle.events() {
+///     match response {
+///         NotificationResponse::Action(action) => {
+///             println!("{action} pressed, activation token {:?}", handle.activation_token())
+///         }
+///         NotificationResponse::Closed(reason) => println!("closed: {reason:?}"),
+///         _ => {}
+///     }
+/// }
+/// ```
+pub struct NotificationEvents {
+    inner: NotificationEventsInner,
+}
+
+enum NotificationEventsInner {
+    #[cfg(feature = "dbus")]
+    Dbus(Box<dbus_rs::ResponseListener>),
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    Stream(Pin<Box<dyn futures_lite::Stream<Item = NotificationResponse> + Send>>),
+}
+
+impl NotificationEvents {
+    #[cfg(feature = "dbus")]
+    pub(crate) fn for_dbus(listener: dbus_rs::ResponseListener) -> NotificationEvents {
+        NotificationEvents {
+            inner: NotificationEventsInner::Dbus(Box::new(listener)),
+        }
+    }
+
+    #[cfg(feature = "zbus")]
+    pub(crate) fn for_zbus(listener: zbus_rs::ResponseListener) -> NotificationEvents {
+        let stream = futures_lite::stream::unfold(listener, |mut listener| async move {
+            let response = listener.next().await?;
+            Some((response, listener))
+        });
+        NotificationEvents {
+            inner: NotificationEventsInner::Stream(Box::pin(stream)),
+        }
+    }
+
+    #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+    pub(crate) fn for_dbus_async(listener: dbus_nonblock::ResponseListener) -> NotificationEvents {
+        let stream = futures_lite::stream::unfold(listener, |mut listener| async move {
+            let response = listener.next().await?;
+            Some((response, listener))
+        });
+        NotificationEvents {
+            inner: NotificationEventsInner::Stream(Box::pin(stream)),
+        }
+    }
+}
+
+impl std::fmt::Debug for NotificationEvents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NotificationEvents").finish_non_exhaustive()
+    }
+}
+
+impl IntoIterator for NotificationEvents {
+    type Item = NotificationResponse;
+    type IntoIter = NotificationEventsIter;
+
+    fn into_iter(self) -> NotificationEventsIter {
+        NotificationEventsIter(self)
+    }
+}
+
+/// Blocking iterator over [`NotificationEvents`].
+#[derive(Debug)]
+pub struct NotificationEventsIter(NotificationEvents);
+
+impl Iterator for NotificationEventsIter {
+    type Item = NotificationResponse;
+
+    fn next(&mut self) -> Option<NotificationResponse> {
+        match self.0.inner {
+            #[cfg(feature = "dbus")]
+            NotificationEventsInner::Dbus(ref mut listener) => listener.next(),
+            #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+            NotificationEventsInner::Stream(ref mut stream) => {
+                super::blocking::block_on(futures_lite::StreamExt::next(stream))
+            }
+        }
+    }
+}
+
+/// If the notification was shown by the blocking API with a [`Dbus`](crate::DbusStack::Dbus) backend,
+/// a thread of its own waits for the responses once the stream is first polled.
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+impl futures_lite::Stream for NotificationEvents {
+    type Item = NotificationResponse;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<NotificationResponse>> {
+        #[cfg(feature = "dbus")]
+        if let NotificationEventsInner::Dbus(_) = self.inner {
+            let empty = NotificationEventsInner::Stream(Box::pin(futures_lite::stream::empty()));
+            if let NotificationEventsInner::Dbus(listener) =
+                std::mem::replace(&mut self.inner, empty)
+            {
+                self.inner = NotificationEventsInner::Stream(Box::pin(Received::follow(listener)));
+            }
+        }
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationEventsInner::Dbus(_) => Poll::Ready(None),
+            NotificationEventsInner::Stream(ref mut stream) => stream.as_mut().poll_next(cx),
+        }
+    }
+}
+
+/// The responses a blocking dbus-rs listener receives on a thread of its own.
+///
+/// Dropping this stops the thread at the next response, or once the notification is closed.
+#[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+struct Received(Arc<Queue<NotificationResponse>>);
+
+#[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+impl Received {
+    fn follow(mut listener: Box<dbus_rs::ResponseListener>) -> Received {
+        let queue = Arc::new(Queue::default());
+        let sink = queue.clone();
+        let spawned = std::thread::Builder::new()
+            .name("notify-rust events".into())
+            .spawn(move || {
+                while let Some(response) = listener.next() {
+                    if !sink.push(response) {
+                        return;
+                    }
+                }
+                sink.close();
+            });
+        if let Err(error) = spawned {
+            log::warn!("cannot follow the notification asynchronously: {error}");
+            queue.close();
+        }
+        Received(queue)
+    }
+}
+
+#[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+impl futures_lite::Stream for Received {
+    type Item = NotificationResponse;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<NotificationResponse>> {
+        self.0.poll_pop(cx)
+    }
+}
+
+#[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+impl Drop for Received {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+/// The last activation token of a notification, shared by its handle and listeners.
+///
+/// The server sends the token right before the action it belongs to.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ActivationTokenSlot(Arc<Mutex<Option<String>>>);
+
+impl ActivationTokenSlot {
+    pub(crate) fn set(&self, token: String) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(token);
+    }
+
+    pub(crate) fn get(&self) -> Option<String> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
@@ -0,0 +1,239 @@
+//! Closing notifications the server leaves on screen past their timeout, see [`Notification::enforce_timeout()`].
+//!
+//! Every connection has one [`Expiries`], shared by the handles, listeners and controllers of what was shown over it.
+//! A helper thread waits for the earliest deadline and closes the notification,
+//! unless the notification was closed before or its server is known to expire notifications itself.
+//! The thread is started by the first notification that enforces its timeout and ends once nothing is left to wait for.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    fmt,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::ServerInformation;
+use crate::{error::Result, notification::Notification, response::CloseReason, timeout::Timeout};
+
+/// Names in `GetServerInformation` of servers known to close notifications once their timeout passed.
+const HONORS_TIMEOUTS: &[&str] = &[
+    "dunst",
+    "mako",
+    "Notification Daemon",
+    "Plasma",
+    "Xfce Notify Daemon",
+];
+
+/// How many ids closed here are remembered until their close is reported.
+const EXPIRED_KEPT: usize = 32;
+
+/// Whether the server that sent `information` is known to close notifications once their timeout passed.
+pub(crate) fn honors_timeouts(information: &ServerInformation) -> bool {
+    HONORS_TIMEOUTS.contains(&information.name.as_str())
+}
+
+/// The server a notification was shown by, as far as [`Expiries`] needs it.
+///
+/// Called from the helper thread, after the handle may long be gone.
+pub(crate) trait ExpiringServer: Send + Sync {
+    fn server_information(&self) -> Result<ServerInformation>;
+
+    fn close(&self, id: u32) -> Result<()>;
+}
+
+/// Closes the notifications shown over one connection once their timeout passed.
+#[derive(Default)]
+pub(crate) struct Expiries {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    /// The earliest first, an entry is stale once its notification was shown again or closed.
+    deadlines: BinaryHeap<Reverse<(Instant, usize)>>,
+    /// What is waited for, by the address of the id its handles share.
+    pending: HashMap<usize, Pending>,
+    /// Ids of the notifications closed here, their close is reported as expired.
+    expired: VecDeque<u32>,
+    /// Whether a server, by unique name, is known to close notifications once their timeout passed.
+    honors: Vec<(String, bool)>,
+    /// Whether a helper thread waits for the deadlines.
+    waiting: bool,
+}
+
+struct Pending {
+    id: Arc<AtomicU32>,
+    deadline: Instant,
+    /// Unique name of the server that showed it, empty on peer-to-peer connections.
+    server: String,
+    at: Arc<dyn ExpiringServer>,
+}
+
+/// The key of a notification in [`State::pending`], its id moves along when it is shown again.
+fn key(id: &Arc<AtomicU32>) -> usize {
+    Arc::as_ptr(id) as usize
+}
+
+impl Expiries {
+    /// Waits for the timeout of the notification `id`, which `server` just showed or showed again.
+    ///
+    /// Nothing happens unless it enforces a timeout in milliseconds, only then `at` is called.
+    pub(crate) fn shown<S: ExpiringServer + 'static>(
+        self: &Arc<Self>,
+        id: &Arc<AtomicU32>,
+        server: Option<&str>,
+        notification: &Notification,
+        at: impl FnOnce() -> S,
+    ) {
+        let deadline = match notification.timeout {
+            Timeout::Milliseconds(ms) if notification.enforce_timeout => {
+                Instant::now() + Duration::from_millis(ms.into())
+            }
+            _ => return self.cancel(id),
+        };
+        let mut state = self.lock();
+        let shown = id.load(Ordering::SeqCst);
+        state.expired.retain(|&expired| expired != shown);
+        state.pending.insert(
+            key(id),
+            Pending {
+                id: id.clone(),
+                deadline,
+                server: server.unwrap_or_default().to_owned(),
+                at: Arc::new(at()),
+            },
+        );
+        state.deadlines.push(Reverse((deadline, key(id))));
+        if !state.waiting {
+            let expiries = self.clone();
+            let spawned = thread::Builder::new()
+                .name("notify-rust expiry".into())
+                .spawn(move || {
+                    // closing uses the blocking API
+                    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+                    super::blocking::mark_off_executor();
+                    expiries.run();
+                });
+            match spawned {
+                Ok(_) => state.waiting = true,
+                Err(error) => log::warn!("cannot enforce the timeout of a notification: {error}"),
+            }
+        }
+        self.changed.notify_all();
+    }
+
+    /// Stops waiting for the notification `id`, it is about to be closed anyway.
+    pub(crate) fn cancel(&self, id: &Arc<AtomicU32>) {
+        let mut state = self.lock();
+        if state.pending.remove(&key(id)).is_some() && state.pending.is_empty() {
+            // only stale entries are left, the helper thread can go
+            state.deadlines.clear();
+            self.changed.notify_all();
+        }
+    }
+
+    /// The notification `id` was closed for `reason`, there is nothing left to do.
+    ///
+    /// Returns the reason to report: if the close was ours, it expired.
+    pub(crate) fn closed(&self, id: &Arc<AtomicU32>, reason: CloseReason) -> CloseReason {
+        self.cancel(id);
+        let closed = id.load(Ordering::SeqCst);
+        let mut state = self.lock();
+        match state.expired.iter().position(|&expired| expired == closed) {
+            Some(index) if reason == CloseReason::CloseAction => {
+                state.expired.remove(index);
+                CloseReason::Expired
+            }
+            _ => reason,
+        }
+    }
+
+    /// Runs on the helper thread until no deadline is left.
+    fn run(&self) {
+        let mut state = self.lock();
+        loop {
+            let Some(&Reverse((deadline, key))) = state.deadlines.peek() else {
+                state.waiting = false;
+                return;
+            };
+            let Some(pending) = state
+                .pending
+                .get(&key)
+                .filter(|pending| pending.deadline == deadline)
+            else {
+                state.deadlines.pop();
+                continue;
+            };
+            let now = Instant::now();
+            if now < deadline {
+                state = self
+                    .changed
+                    .wait_timeout(state, deadline - now)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+                continue;
+            }
+            let known = state
+                .honors
+                .iter()
+                .find(|(server, _)| *server == pending.server)
+                .map(|&(_, honored)| honored);
+            let honored = if let Some(honored) = known {
+                honored
+            } else {
+                let (server, at) = (pending.server.clone(), pending.at.clone());
+                drop(state);
+                // asked once per server, a restarted one has another unique name
+                let answer = at
+                    .server_information()
+                    .map(|information| honors_timeouts(&information));
+                state = self.lock();
+                if let Ok(&honored) = answer.as_ref() {
+                    state.honors.push((server, honored));
+                }
+                // it may have been closed or shown again in the meantime
+                let current = state.pending.get(&key);
+                if current.is_none_or(|pending| pending.deadline != deadline) {
+                    continue;
+                }
+                answer.unwrap_or(false)
+            };
+            state.deadlines.pop();
+            let Some(pending) = state.pending.remove(&key) else {
+                continue;
+            };
+            if honored {
+                continue;
+            }
+            let id = pending.id.load(Ordering::SeqCst);
+            if state.expired.len() == EXPIRED_KEPT {
+                state.expired.pop_front();
+            }
+            state.expired.push_back(id);
+            drop(state);
+            // fails if the user closed it already and nobody listened for that
+            if let Err(error) = pending.at.close(id) {
+                log::debug!("failed to close expired notification {id}: {error}");
+            }
+            state = self.lock();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for Expiries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Expiries")
+            .field("pending", &self.lock().pending.len())
+            .finish_non_exhaustive()
+    }
+}
@@ -2,30 +2,63 @@
 //!
 //! it should not be available under any platform other than `(unix, not(target_os = "macos"))`
 
-#[cfg(feature = "dbus")]
-use dbus::ffidisp::Connection as DbusConnection;
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+use blocking::{block_on, block_on_responding};
 #[cfg(feature = "zbus")]
-use zbus::{block_on, zvariant};
+use zbus::zvariant;
 
-use crate::{error::*, notification::Notification};
+use crate::{error::*, miniver::Version, notification::Notification};
 
 pub use crate::response::ActionResponse;
 pub use crate::response::{CloseHandler, NotificationResponse, ResponseHandler};
 
-use std::ops::{Deref, DerefMut};
+#[cfg(all(feature = "dbus", feature = "zbus"))]
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+    time::Duration,
+};
 
+#[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+mod dbus_nonblock;
 #[cfg(feature = "dbus")]
 mod dbus_rs;
-#[cfg(all(feature = "dbus", not(feature = "zbus")))]
-use dbus_rs::bus;
 
 #[cfg(feature = "zbus")]
 mod zbus_rs;
-#[cfg(all(feature = "zbus", not(feature = "dbus")))]
-use zbus_rs::bus;
 
-#[cfg(all(feature = "dbus", feature = "zbus"))]
+mod address;
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+mod blocking;
 mod bus;
+mod controller;
+mod dispatcher;
+mod events;
+mod expiry;
+mod notifier;
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+mod queue;
+#[cfg(feature = "zbus")]
+mod router;
+mod scoped;
+mod service;
+mod session;
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+mod timer;
+pub use address::BusAddress;
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+pub use blocking::{blocking_mode, set_blocking_mode, BlockingMode};
+pub use controller::{NotificationController, ResponseWaiter};
+pub(crate) use dispatcher::ActionCallbacks;
+pub use dispatcher::ActionContext;
+pub use events::{NotificationEvents, NotificationEventsIter};
+pub use notifier::{Notifier, NotifierBuilder};
+pub use scoped::ScopedNotification;
+pub use service::{NotificationService, ServiceEvent, Ticket};
+pub use session::{show_to_all_sessions, show_to_user, user_sessions, UserSession};
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+pub use session::{show_to_all_sessions_async, show_to_user_async, user_sessions_async};
 
 // #[cfg(all(feature = "server", feature = "dbus", unix, not(target_os = "macos")))]
 // pub mod server_dbus;
@@ -51,15 +84,20 @@ pub static NOTIFICATION_INTERFACE: &str = "org.freedesktop.Notifications";
 #[doc(hidden)]
 pub static NOTIFICATION_OBJECTPATH: &str = "/org/freedesktop/Notifications";
 
-pub(crate) use bus::NotificationBus;
+pub use bus::NotificationBus;
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)] // there is one per shown notification
 enum NotificationHandleInner {
     #[cfg(feature = "dbus")]
     Dbus(dbus_rs::DbusNotificationHandle),
 
     #[cfg(feature = "zbus")]
     Zbus(zbus_rs::ZbusNotificationHandle),
+
+    /// Shown by the async API with the dbus-rs backend.
+    #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+    DbusAsync(dbus_nonblock::NonblockNotificationHandle),
 }
 
 /// A handle to a shown notification.
@@ -68,30 +106,84 @@ enum NotificationHandleInner {
 #[derive(Debug)]
 pub struct NotificationHandle {
     inner: NotificationHandleInner,
+    close_on_timeout: bool,
+    /// Serves the async API if the notification was shown through the blocking dbus-rs API.
+    #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+    following: std::sync::OnceLock<dbus_nonblock::Following>,
 }
 
 #[allow(dead_code)]
 impl NotificationHandle {
     #[cfg(feature = "dbus")]
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn for_dbus(
         id: u32,
-        connection: DbusConnection,
+        connection: std::sync::Arc<dbus_rs::SharedConnection>,
+        address: BusAddress,
         notification: Notification,
+        server: Option<String>,
+        call_timeout: Duration,
+        restart_policy: ServerRestartPolicy,
+        dispatcher: Option<std::sync::Arc<dispatcher::Dispatcher>>,
+        responses: Option<dbus_rs::Subscription>,
     ) -> NotificationHandle {
         NotificationHandle {
-            inner: dbus_rs::DbusNotificationHandle::new(id, connection, notification).into(),
+            inner: dbus_rs::DbusNotificationHandle::new(
+                id,
+                connection,
+                address,
+                notification,
+                server,
+                call_timeout,
+                restart_policy,
+                dispatcher,
+                responses,
+            )
+            .into(),
+            close_on_timeout: false,
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            following: Default::default(),
         }
     }
 
     #[cfg(feature = "zbus")]
     pub(crate) fn for_zbus(
         id: u32,
-        connection: zbus::Connection,
+        connection: router::RoutedConnection,
         notification: Notification,
-    ) -> NotificationHandle {
-        NotificationHandle {
-            inner: zbus_rs::ZbusNotificationHandle::new(id, connection, notification).into(),
+        server: Option<String>,
+        restart_policy: ServerRestartPolicy,
+    ) -> Result<NotificationHandle> {
+        Ok(NotificationHandle {
+            inner: zbus_rs::ZbusNotificationHandle::new(
+                id,
+                connection,
+                notification,
+                server,
+                restart_policy,
+            )?
+            .into(),
+            close_on_timeout: false,
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            following: Default::default(),
+        })
+    }
+
+    /// Follows a notification shown through the blocking dbus-rs API asynchronously,
+    /// from a thread that takes over its listener on first use.
+    ///
+    /// All waits go through it from then on, so every response is reported once.
+    #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+    fn following(
+        &self,
+        handle: &dbus_rs::DbusNotificationHandle,
+    ) -> Result<&dbus_nonblock::Following> {
+        if let Some(following) = self.following.get() {
+            return Ok(following);
         }
+        let following = dbus_nonblock::Following::new(handle)?;
+        // somebody else may have been quicker, theirs is used then
+        Ok(self.following.get_or_init(|| following))
     }
 
     /// Waits for the user to act on a notification and then calls
@@ -100,29 +192,12 @@ impl NotificationHandle {
     where
         F: FnOnce(&str),
     {
-        match self.inner {
-            #[cfg(feature = "dbus")]
-            NotificationHandleInner::Dbus(inner) => {
-                let _ = inner.wait_for_action(|response: &NotificationResponse| match response {
-                    NotificationResponse::Default => invocation_closure("default"),
-                    NotificationResponse::Action(ref action) => invocation_closure(action),
-                    NotificationResponse::Reply(_) => { /* XDG does not support inline replies */ }
-                    NotificationResponse::Closed(_) => invocation_closure("__closed"),
-                });
-            }
-
-            #[cfg(feature = "zbus")]
-            NotificationHandleInner::Zbus(inner) => {
-                block_on(
-                    inner.wait_for_action(|response: &NotificationResponse| match response {
-                        NotificationResponse::Default => invocation_closure("default"),
-                        NotificationResponse::Action(ref action) => invocation_closure(action),
-                        NotificationResponse::Reply(_) => { /* XDG does not support inline replies */ }
-                        NotificationResponse::Closed(_) => invocation_closure("__closed"), // FIXME: remove backward compatibility with 5.0
-                    }),
-                );
-            }
-        };
+        let _ = self.wait_for_response(|response: &NotificationResponse| match response {
+            NotificationResponse::Default => invocation_closure("default"),
+            NotificationResponse::Action(ref action) => invocation_closure(action),
+            NotificationResponse::Reply(_) => { /* XDG does not support inline replies */ }
+            NotificationResponse::Closed(_) => invocation_closure("__closed"), // FIXME: remove backward compatibility with 5.0
+        });
     }
 
     /// Waits for the user to act on a notification and then calls `handler`
@@ -130,23 +205,131 @@ impl NotificationHandle {
     ///
     /// This is the typed, forward-compatible replacement for [`wait_for_action`](Self::wait_for_action).
     pub fn wait_for_response(self, handler: impl ResponseHandler) -> Result<()> {
+        #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+        if let Some(following) = self.following.get() {
+            return block_on_responding(|recorder| following.wait_for_action(recorder), handler);
+        }
         match self.inner {
             #[cfg(feature = "dbus")]
             NotificationHandleInner::Dbus(inner) => inner.wait_for_action(handler),
             #[cfg(feature = "zbus")]
             NotificationHandleInner::Zbus(inner) => {
-                block_on(inner.wait_for_action(handler));
-                Ok(())
+                block_on_responding(|recorder| inner.wait_for_action(recorder), handler)
+            }
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(inner) => {
+                block_on_responding(|recorder| inner.wait_for_action(recorder), handler)
             }
         }
     }
 
-    /// Returns a future that waits for the user to act on a notification and then calls
-    /// `invocation_closure` with the name of the corresponding action.
+    /// Waits up to `timeout` for the user to act on the notification.
     ///
-    /// # Panics
+    /// Returns `None` if nothing happened in time, or the notification was closed already.
+    /// Waiting again picks up where the last call stopped, responses that arrive in between are kept.
+    /// Several servers never close a notification the user ignores, this keeps you from waiting forever;
+    /// see also [`close_on_timeout()`](Self::close_on_timeout).
     ///
-    /// Panics if called with a [`Dbus`](DbusStack::Dbus) backend.
+    /// ```no_run
+    /// # use notify_rust::*;
+    /// # use std::time::Duration;
+    /// let handle = Notification::new()
+    ///     .summary("Deploy to production?")
+    ///     .action("deploy", "Deploy")
+    ///     .show()
+    ///     .unwrap();
+    ///
+    /// match handle.wait_for_response_timeout(Duration::from_secs(30)) {
+    ///     Some(NotificationResponse::Action(action)) => println!("{action}"),
+    ///     Some(_) => println!("never mind"),
+    ///     None => println!("nobody answered"),
+    /// }
+    /// ```
+    pub fn wait_for_response_timeout(&self, timeout: Duration) -> Option<NotificationResponse> {
+        #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+        if let Some(following) = self.following.get() {
+            return logged(block_on(
+                following.next_response(Some(timeout), self.close_on_timeout),
+            ));
+        }
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationHandleInner::Dbus(ref inner) => {
+                inner.next_response(timeout, self.close_on_timeout)
+            }
+            #[cfg(feature = "zbus")]
+            NotificationHandleInner::Zbus(ref inner) => logged(block_on(
+                inner.next_response(Some(timeout), self.close_on_timeout),
+            )),
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(ref inner) => logged(block_on(
+                inner.next_response(Some(timeout), self.close_on_timeout),
+            )),
+        }
+    }
+
+    /// Async version of [`wait_for_response()`](Self::wait_for_response).
+    ///
+    /// Returns `None` if the notification was closed already,
+    /// and an error if the signals of the notification could not be subscribed to.
+    /// The future can be dropped at any point without losing a response, waiting again picks it up.
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn wait_for_response_async(&self) -> Result<Option<NotificationResponse>> {
+        match &self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationHandleInner::Dbus(inner) => {
+                self.following(inner)?.next_response(None, false).await
+            }
+            #[cfg(feature = "zbus")]
+            NotificationHandleInner::Zbus(inner) => inner.next_response(None, false).await,
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(inner) => inner.next_response(None, false).await,
+        }
+    }
+
+    /// Async version of [`wait_for_response_timeout()`](Self::wait_for_response_timeout).
+    ///
+    /// The future can be dropped at any point without losing a response, waiting again picks it up.
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn wait_for_response_timeout_async(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<NotificationResponse>> {
+        match &self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationHandleInner::Dbus(inner) => {
+                self.following(inner)?
+                    .next_response(Some(timeout), self.close_on_timeout)
+                    .await
+            }
+            #[cfg(feature = "zbus")]
+            NotificationHandleInner::Zbus(inner) => {
+                inner
+                    .next_response(Some(timeout), self.close_on_timeout)
+                    .await
+            }
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(inner) => {
+                inner
+                    .next_response(Some(timeout), self.close_on_timeout)
+                    .await
+            }
+        }
+    }
+
+    /// Closes the notification when a timed wait runs out, instead of leaving it on screen.
+    ///
+    /// The wait then reports [`CloseReason::Expired`](crate::CloseReason::Expired) instead of `None`,
+    /// as if the server had expired the notification itself.
+    /// Applies to [`wait_for_response_timeout()`](Self::wait_for_response_timeout)
+    /// and its async version.
+    pub fn close_on_timeout(&mut self, close: bool) -> &mut NotificationHandle {
+        self.close_on_timeout = close;
+        self
+    }
+
+    /// Returns a future that waits for the user to act on a notification and then calls
+    /// `invocation_closure` with the name of the corresponding action.
     ///
     /// # Example
     ///
@@ -171,24 +354,49 @@ impl NotificationHandle {
     /// };
     ///
     /// // run both futures concurrently
-    /// # let _ =
-    /// zip(wait_future, close_future).await;
+    /// zip(wait_future, close_future).await.0?;
     /// # Ok(())
     /// # }
     /// ```
     // TODO: make this consume `self` in 5.0
-    #[cfg(feature = "zbus")]
-    pub async fn wait_for_action_async<F>(&self, invocation_closure: F)
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn wait_for_action_async<F>(&self, invocation_closure: F) -> Result<()>
     where
         F: FnOnce(&NotificationResponse),
     {
         match &self.inner {
             #[cfg(feature = "dbus")]
-            NotificationHandleInner::Dbus(_) => {
-                unimplemented!("async methods are not supported with the `dbus` backend");
+            NotificationHandleInner::Dbus(inner) => {
+                self.following(inner)?
+                    .wait_for_action(invocation_closure)
+                    .await
             }
             #[cfg(feature = "zbus")]
             NotificationHandleInner::Zbus(inner) => inner.wait_for_action(invocation_closure).await,
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(inner) => {
+                inner.wait_for_action(invocation_closure).await
+            }
+        }
+    }
+
+    /// Follows the notification through all of its responses until it is closed.
+    ///
+    /// See [`NotificationEvents`] for an example.
+    pub fn events(&self) -> NotificationEvents {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationHandleInner::Dbus(ref inner) => {
+                NotificationEvents::for_dbus(inner.listen())
+            }
+            #[cfg(feature = "zbus")]
+            NotificationHandleInner::Zbus(ref inner) => {
+                NotificationEvents::for_zbus(inner.listen())
+            }
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(ref inner) => {
+                NotificationEvents::for_dbus_async(inner.listen())
+            }
         }
     }
 
@@ -215,23 +423,36 @@ impl NotificationHandle {
             NotificationHandleInner::Dbus(inner) => inner.close(),
             #[cfg(feature = "zbus")]
             NotificationHandleInner::Zbus(inner) => block_on(inner.close()),
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(inner) => block_on(inner.close()),
+        }
+    }
+
+    /// Like [`close()`](Self::close), reporting whether closing failed.
+    pub(crate) fn close_fallible(&self) -> Result<()> {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationHandleInner::Dbus(ref inner) => inner.close_fallible(),
+            #[cfg(feature = "zbus")]
+            NotificationHandleInner::Zbus(ref inner) => block_on(inner.close_fallible()),
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(ref inner) => block_on(inner.close_fallible()),
         }
     }
 
     /// Async version of [`close`](Self::close).
-    ///
-    /// # Panics
-    ///
-    /// Panics if called with a [`Dbus`](DbusStack::Dbus) backend.
-    #[cfg(feature = "zbus")]
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
     pub async fn close_async(&self) {
         match &self.inner {
             #[cfg(feature = "dbus")]
-            NotificationHandleInner::Dbus(_) => {
-                unimplemented!("async methods are not supported with the `dbus` backend");
-            }
+            NotificationHandleInner::Dbus(inner) => match self.following(inner) {
+                Ok(following) => following.close().await,
+                Err(error) => log::warn!("failed to close notification {}: {error}", inner.id()),
+            },
             #[cfg(feature = "zbus")]
             NotificationHandleInner::Zbus(inner) => inner.close().await,
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(inner) => inner.close().await,
         }
     }
 
@@ -265,24 +486,51 @@ impl NotificationHandle {
     //     note = "Use `wait_for_response()` and match on `ActionResponse::Closed` instead"
     // )]
     pub fn on_close<A>(self, handler: impl CloseHandler<A>) {
-        match self.inner {
-            #[cfg(feature = "dbus")]
-            NotificationHandleInner::Dbus(inner) => {
-                let _ = inner.wait_for_action(|action: &NotificationResponse| {
-                    if let NotificationResponse::Closed(reason) = action {
-                        handler.call(*reason);
-                    }
-                });
+        let _ = self.wait_for_response(|action: &NotificationResponse| {
+            if let NotificationResponse::Closed(reason) = action {
+                handler.call(*reason);
             }
+        });
+    }
+
+    /// Async version of [`on_close()`](Self::on_close).
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn on_close_async<A>(&self, handler: impl CloseHandler<A>) {
+        match &self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationHandleInner::Dbus(inner) => match self.following(inner) {
+                Ok(following) => {
+                    let _ = following
+                        .wait_for_action(|action: &NotificationResponse| {
+                            if let NotificationResponse::Closed(reason) = action {
+                                handler.call(*reason);
+                            }
+                        })
+                        .await;
+                }
+                Err(error) => log::warn!("failed to wait for the notification to close: {error}"),
+            },
             #[cfg(feature = "zbus")]
             NotificationHandleInner::Zbus(inner) => {
-                block_on(inner.wait_for_action(|action: &NotificationResponse| {
-                    if let NotificationResponse::Closed(reason) = action {
-                        handler.call(*reason);
-                    }
-                }));
+                let _ = inner
+                    .wait_for_action(|action: &NotificationResponse| {
+                        if let NotificationResponse::Closed(reason) = action {
+                            handler.call(*reason);
+                        }
+                    })
+                    .await;
             }
-        };
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(inner) => {
+                let _ = inner
+                    .wait_for_action(|action: &NotificationResponse| {
+                        if let NotificationResponse::Closed(reason) = action {
+                            handler.call(*reason);
+                        }
+                    })
+                    .await;
+            }
+        }
     }
 
     /// Replace the original notification with an updated version
@@ -311,6 +559,29 @@ impl NotificationHandle {
             NotificationHandleInner::Dbus(ref mut inner) => inner.update(),
             #[cfg(feature = "zbus")]
             NotificationHandleInner::Zbus(ref mut inner) => inner.update(),
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(ref mut inner) => block_on(inner.update()),
+        }
+    }
+
+    /// Async version of [`update()`](Self::update).
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn update_async(&mut self) -> Result<()> {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationHandleInner::Dbus(ref mut inner) => {
+                let following = if let Some(following) = self.following.get() {
+                    following
+                } else {
+                    let following = dbus_nonblock::Following::new(inner)?;
+                    self.following.get_or_init(|| following)
+                };
+                following.update(inner).await
+            }
+            #[cfg(feature = "zbus")]
+            NotificationHandleInner::Zbus(ref mut inner) => inner.update_async().await,
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(ref mut inner) => inner.update().await,
         }
     }
 
@@ -318,9 +589,39 @@ impl NotificationHandle {
     pub fn id(&self) -> u32 {
         match self.inner {
             #[cfg(feature = "dbus")]
-            NotificationHandleInner::Dbus(ref inner) => inner.id,
+            NotificationHandleInner::Dbus(ref inner) => inner.id(),
             #[cfg(feature = "zbus")]
-            NotificationHandleInner::Zbus(ref inner) => inner.id,
+            NotificationHandleInner::Zbus(ref inner) => inner.id(),
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(ref inner) => inner.id(),
+        }
+    }
+
+    /// The activation token the server sent along with the last action on this notification, if any.
+    ///
+    /// Pass it on to the window you raise in response, e.g. via `XDG_ACTIVATION_TOKEN`.
+    /// Tokens are picked up while waiting for a response or following [`events()`](Self::events),
+    /// the server sends them right before the action.
+    pub fn activation_token(&self) -> Option<String> {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationHandleInner::Dbus(ref inner) => inner.activation_token.get(),
+            #[cfg(feature = "zbus")]
+            NotificationHandleInner::Zbus(ref inner) => inner.activation_token.get(),
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(ref inner) => inner.activation_token.get(),
+        }
+    }
+
+    /// The D-Bus implementation this notification was shown with.
+    pub fn dbus_stack(&self) -> DbusStack {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotificationHandleInner::Dbus(_) => DbusStack::Dbus,
+            #[cfg(feature = "zbus")]
+            NotificationHandleInner::Zbus(_) => DbusStack::Zbus,
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(_) => DbusStack::Dbus,
         }
     }
 }
@@ -335,6 +636,8 @@ impl Deref for NotificationHandle {
             NotificationHandleInner::Dbus(ref inner) => &inner.notification,
             #[cfg(feature = "zbus")]
             NotificationHandleInner::Zbus(ref inner) => &inner.notification,
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(ref inner) => &inner.notification,
         }
     }
 }
@@ -347,6 +650,8 @@ impl DerefMut for NotificationHandle {
             NotificationHandleInner::Dbus(ref mut inner) => &mut inner.notification,
             #[cfg(feature = "zbus")]
             NotificationHandleInner::Zbus(ref mut inner) => &mut inner.notification,
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotificationHandleInner::DbusAsync(ref mut inner) => &mut inner.notification,
         }
     }
 }
@@ -361,15 +666,31 @@ impl From<dbus_rs::DbusNotificationHandle> for NotificationHandleInner {
 #[cfg(feature = "zbus")]
 impl From<zbus_rs::ZbusNotificationHandle> for NotificationHandleInner {
     fn from(handle: zbus_rs::ZbusNotificationHandle) -> NotificationHandleInner {
+        #[cfg(feature = "zbus")]
         NotificationHandleInner::Zbus(handle)
     }
 }
 
+#[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+impl From<dbus_nonblock::NonblockNotificationHandle> for NotificationHandle {
+    fn from(handle: dbus_nonblock::NonblockNotificationHandle) -> NotificationHandle {
+        NotificationHandle {
+            inner: NotificationHandleInner::DbusAsync(handle),
+            close_on_timeout: false,
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            following: Default::default(),
+        }
+    }
+}
+
 #[cfg(feature = "dbus")]
 impl From<dbus_rs::DbusNotificationHandle> for NotificationHandle {
     fn from(handle: dbus_rs::DbusNotificationHandle) -> NotificationHandle {
         NotificationHandle {
             inner: handle.into(),
+            close_on_timeout: false,
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            following: Default::default(),
         }
     }
 }
@@ -379,6 +700,9 @@ impl From<zbus_rs::ZbusNotificationHandle> for NotificationHandle {
     fn from(handle: zbus_rs::ZbusNotificationHandle) -> NotificationHandle {
         NotificationHandle {
             inner: handle.into(),
+            close_on_timeout: false,
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            following: Default::default(),
         }
     }
 }
@@ -396,8 +720,27 @@ impl From<zbus_rs::ZbusNotificationHandle> for NotificationHandle {
 ))]
 compile_error!("you have to build with either zbus or dbus turned on");
 
+/// What happens to shown notifications when the notification server restarts.
+///
+/// A restarted server knows nothing of the notifications shown by its predecessor,
+/// their ids are meaningless to it.
+/// Restarts are noticed while waiting for a response and on [`NotificationHandle::update()`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ServerRestartPolicy {
+    /// Give up on the notification.
+    ///
+    /// [`NotificationHandle::wait_for_response()`] and updating fail with an error of category
+    /// [`ServerVanished`](crate::error::ErrorCategory::ServerVanished),
+    /// other waits end without a response.
+    #[default]
+    Vanish,
+
+    /// Show the notification again on the new server and continue with the id it hands out.
+    Reshow,
+}
+
 /// Which D-Bus implementation is in use.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum DbusStack {
     /// Using [dbus-rs](https://docs.rs/dbus-rs).
     Dbus,
@@ -405,15 +748,75 @@ pub enum DbusStack {
     Zbus,
 }
 
+impl DbusStack {
+    /// Whether this implementation is compiled in, with feature `d` or `z` respectively.
+    pub fn is_available(self) -> bool {
+        match self {
+            DbusStack::Dbus => cfg!(feature = "dbus"),
+            DbusStack::Zbus => cfg!(feature = "zbus"),
+        }
+    }
+}
+
 #[cfg(all(feature = "dbus", feature = "zbus"))]
 const DBUS_SWITCH_VAR: &str = "DBUSRS";
 
+/// The stack chosen by [`set_dbus_stack()`], `0` while `$DBUSRS` decides.
+#[cfg(all(feature = "dbus", feature = "zbus"))]
+static SELECTED_STACK: AtomicU8 = AtomicU8::new(0);
+
+/// Selects the [`DbusStack`] for the whole process, taking precedence over the `$DBUSRS` environment variable.
+///
+/// Applies to everything that does not bring a stack of its own, like [`Notification::show()`]
+/// or a [`Notifier`] connected without [`NotifierBuilder::dbus_stack()`].
+/// `None` leaves the choice to the environment variable again.
+///
+/// Fails if `stack` is not compiled in.
+///
+/// ```no_run
+/// # use notify_rust::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// set_dbus_stack(Some(DbusStack::Zbus))?;
+/// let handle = Notification::new().summary("via zbus").show()?;
+/// assert_eq!(handle.dbus_stack(), DbusStack::Zbus);
+/// # Ok(())
+/// # }
+/// ```
+pub fn set_dbus_stack(stack: Option<DbusStack>) -> Result<()> {
+    if let Some(stack) = stack {
+        if !stack.is_available() {
+            return Err(ErrorKind::ImplementationMissing.into());
+        }
+    }
+    #[cfg(all(feature = "dbus", feature = "zbus"))]
+    SELECTED_STACK.store(
+        match stack {
+            None => 0,
+            Some(DbusStack::Dbus) => 1,
+            Some(DbusStack::Zbus) => 2,
+        },
+        Ordering::SeqCst,
+    );
+    Ok(())
+}
+
+/// The stack set by [`set_dbus_stack()`], otherwise dbus-rs if `$DBUSRS` is set and zbus if not.
+#[cfg(all(feature = "dbus", feature = "zbus"))]
+pub(crate) fn selected_stack() -> DbusStack {
+    match SELECTED_STACK.load(Ordering::SeqCst) {
+        1 => DbusStack::Dbus,
+        2 => DbusStack::Zbus,
+        _ if std::env::var(DBUS_SWITCH_VAR).is_ok() => DbusStack::Dbus,
+        _ => DbusStack::Zbus,
+    }
+}
+
 #[cfg(all(feature = "zbus", not(feature = "dbus")))]
 pub(crate) fn show_notification(notification: &Notification) -> Result<NotificationHandle> {
     block_on(zbus_rs::connect_and_send_notification(notification)).map(Into::into)
 }
 
-#[cfg(feature = "zbus")]
+#[cfg(all(feature = "zbus", not(feature = "dbus")))]
 pub(crate) async fn show_notification_async(
     notification: &Notification,
 ) -> Result<NotificationHandle> {
@@ -422,7 +825,31 @@ pub(crate) async fn show_notification_async(
         .map(Into::into)
 }
 
-#[cfg(feature = "zbus")]
+#[cfg(all(feature = "dbus", not(feature = "zbus"), feature = "async"))]
+pub(crate) async fn show_notification_async(
+    notification: &Notification,
+) -> Result<NotificationHandle> {
+    dbus_nonblock::connect_and_send_notification(notification)
+        .await
+        .map(Into::into)
+}
+
+#[cfg(all(feature = "dbus", feature = "zbus"))]
+pub(crate) async fn show_notification_async(
+    notification: &Notification,
+) -> Result<NotificationHandle> {
+    if selected_stack() == DbusStack::Dbus {
+        dbus_nonblock::connect_and_send_notification(notification)
+            .await
+            .map(Into::into)
+    } else {
+        zbus_rs::connect_and_send_notification(notification)
+            .await
+            .map(Into::into)
+    }
+}
+
+#[cfg(all(feature = "zbus", not(feature = "dbus")))]
 pub(crate) async fn show_notification_async_at_bus(
     notification: &Notification,
     bus: NotificationBus,
@@ -432,6 +859,32 @@ pub(crate) async fn show_notification_async_at_bus(
         .map(Into::into)
 }
 
+#[cfg(all(feature = "dbus", not(feature = "zbus"), feature = "async"))]
+pub(crate) async fn show_notification_async_at_bus(
+    notification: &Notification,
+    bus: NotificationBus,
+) -> Result<NotificationHandle> {
+    dbus_nonblock::connect_and_send_notification_at_bus(notification, bus)
+        .await
+        .map(Into::into)
+}
+
+#[cfg(all(feature = "dbus", feature = "zbus"))]
+pub(crate) async fn show_notification_async_at_bus(
+    notification: &Notification,
+    bus: NotificationBus,
+) -> Result<NotificationHandle> {
+    if selected_stack() == DbusStack::Dbus {
+        dbus_nonblock::connect_and_send_notification_at_bus(notification, bus)
+            .await
+            .map(Into::into)
+    } else {
+        zbus_rs::connect_and_send_notification_at_bus(notification, bus)
+            .await
+            .map(Into::into)
+    }
+}
+
 #[cfg(all(feature = "dbus", not(feature = "zbus")))]
 pub(crate) fn show_notification(notification: &Notification) -> Result<NotificationHandle> {
     dbus_rs::connect_and_send_notification(notification).map(Into::into)
@@ -439,7 +892,7 @@ pub(crate) fn show_notification(notification: &Notification) -> Result<Notificat
 
 #[cfg(all(feature = "dbus", feature = "zbus"))]
 pub(crate) fn show_notification(notification: &Notification) -> Result<NotificationHandle> {
-    if std::env::var(DBUS_SWITCH_VAR).is_ok() {
+    if selected_stack() == DbusStack::Dbus {
         dbus_rs::connect_and_send_notification(notification).map(Into::into)
     } else {
         block_on(zbus_rs::connect_and_send_notification(notification)).map(Into::into)
@@ -464,14 +917,10 @@ pub fn dbus_stack() -> Option<DbusStack> {
 
 /// Get the currently active [`DbusStack`].
 ///
-/// Both dbus-rs and zbus are compiled in; switch via the `$DBUSRS` environment variable.
+/// Both dbus-rs and zbus are compiled in; switch via [`set_dbus_stack()`] or the `$DBUSRS` environment variable.
 #[cfg(all(feature = "dbus", feature = "zbus"))]
 pub fn dbus_stack() -> Option<DbusStack> {
-    Some(if std::env::var(DBUS_SWITCH_VAR).is_ok() {
-        DbusStack::Dbus
-    } else {
-        DbusStack::Zbus
-    })
+    Some(selected_stack())
 }
 
 /// Get the currently active [`DbusStack`].
@@ -500,16 +949,78 @@ pub fn get_capabilities() -> Result<Vec<String>> {
 
 /// Returns a list of all capabilities of the running notification server.
 ///
-/// Both dbus-rs and zbus are compiled in; switch via the `$DBUSRS` environment variable.
+/// Both dbus-rs and zbus are compiled in; switch via [`set_dbus_stack()`] or the `$DBUSRS` environment variable.
 #[cfg(all(feature = "dbus", feature = "zbus"))]
 pub fn get_capabilities() -> Result<Vec<String>> {
-    if std::env::var(DBUS_SWITCH_VAR).is_ok() {
+    if selected_stack() == DbusStack::Dbus {
         dbus_rs::get_capabilities()
     } else {
         block_on(zbus_rs::get_capabilities())
     }
 }
 
+/// Returns a list of all capabilities of the notification server at the given bus.
+///
+/// (zbus only)
+#[cfg(all(feature = "zbus", not(feature = "dbus")))]
+pub fn get_capabilities_at_bus(bus: NotificationBus) -> Result<Vec<String>> {
+    block_on(zbus_rs::get_capabilities_at_bus(bus))
+}
+
+/// Returns a list of all capabilities of the notification server at the given bus.
+///
+/// (dbus-rs only)
+#[cfg(all(feature = "dbus", not(feature = "zbus")))]
+pub fn get_capabilities_at_bus(bus: NotificationBus) -> Result<Vec<String>> {
+    dbus_rs::get_capabilities_at_bus(bus)
+}
+
+/// Returns a list of all capabilities of the notification server at the given bus.
+///
+/// Both dbus-rs and zbus are compiled in; switch via [`set_dbus_stack()`] or the `$DBUSRS` environment variable.
+#[cfg(all(feature = "dbus", feature = "zbus"))]
+pub fn get_capabilities_at_bus(bus: NotificationBus) -> Result<Vec<String>> {
+    if selected_stack() == DbusStack::Dbus {
+        dbus_rs::get_capabilities_at_bus(bus)
+    } else {
+        block_on(zbus_rs::get_capabilities_at_bus(bus))
+    }
+}
+
+/// Async version of [`get_capabilities()`].
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+pub async fn get_capabilities_async() -> Result<Vec<String>> {
+    get_capabilities_at_bus_async(Default::default()).await
+}
+
+/// Async version of [`get_capabilities_at_bus()`].
+///
+/// (zbus only)
+#[cfg(all(feature = "zbus", not(feature = "dbus")))]
+pub async fn get_capabilities_at_bus_async(bus: NotificationBus) -> Result<Vec<String>> {
+    zbus_rs::get_capabilities_at_bus(bus).await
+}
+
+/// Async version of [`get_capabilities_at_bus()`].
+///
+/// (dbus-rs only)
+#[cfg(all(feature = "dbus", not(feature = "zbus"), feature = "async"))]
+pub async fn get_capabilities_at_bus_async(bus: NotificationBus) -> Result<Vec<String>> {
+    dbus_nonblock::get_capabilities_at_bus(bus).await
+}
+
+/// Async version of [`get_capabilities_at_bus()`].
+///
+/// Both dbus-rs and zbus are compiled in; switch via [`set_dbus_stack()`] or the `$DBUSRS` environment variable.
+#[cfg(all(feature = "dbus", feature = "zbus"))]
+pub async fn get_capabilities_at_bus_async(bus: NotificationBus) -> Result<Vec<String>> {
+    if selected_stack() == DbusStack::Dbus {
+        dbus_nonblock::get_capabilities_at_bus(bus).await
+    } else {
+        zbus_rs::get_capabilities_at_bus(bus).await
+    }
+}
+
 /// Returns a [`ServerInformation`] struct describing the running notification server.
 ///
 /// The struct contains `name`, `vendor`, `version`, and `spec_version`.
@@ -534,16 +1045,84 @@ pub fn get_server_information() -> Result<ServerInformation> {
 ///
 /// The struct contains `name`, `vendor`, `version`, and `spec_version`.
 ///
-/// Both dbus-rs and zbus are compiled in; switch via the `$DBUSRS` environment variable.
+/// Both dbus-rs and zbus are compiled in; switch via [`set_dbus_stack()`] or the `$DBUSRS` environment variable.
 #[cfg(all(feature = "dbus", feature = "zbus"))]
 pub fn get_server_information() -> Result<ServerInformation> {
-    if std::env::var(DBUS_SWITCH_VAR).is_ok() {
+    if selected_stack() == DbusStack::Dbus {
         dbus_rs::get_server_information()
     } else {
         block_on(zbus_rs::get_server_information())
     }
 }
 
+/// Returns a [`ServerInformation`] struct describing the notification server at the given bus.
+///
+/// (zbus only)
+#[cfg(all(feature = "zbus", not(feature = "dbus")))]
+pub fn get_server_information_at_bus(bus: NotificationBus) -> Result<ServerInformation> {
+    block_on(zbus_rs::get_server_information_at_bus(bus))
+}
+
+/// Returns a [`ServerInformation`] struct describing the notification server at the given bus.
+///
+/// (dbus-rs only)
+#[cfg(all(feature = "dbus", not(feature = "zbus")))]
+pub fn get_server_information_at_bus(bus: NotificationBus) -> Result<ServerInformation> {
+    dbus_rs::get_server_information_at_bus(bus)
+}
+
+/// Returns a [`ServerInformation`] struct describing the notification server at the given bus.
+///
+/// Both dbus-rs and zbus are compiled in; switch via [`set_dbus_stack()`] or the `$DBUSRS` environment variable.
+#[cfg(all(feature = "dbus", feature = "zbus"))]
+pub fn get_server_information_at_bus(bus: NotificationBus) -> Result<ServerInformation> {
+    if selected_stack() == DbusStack::Dbus {
+        dbus_rs::get_server_information_at_bus(bus)
+    } else {
+        block_on(zbus_rs::get_server_information_at_bus(bus))
+    }
+}
+
+/// Async version of [`get_server_information()`].
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+pub async fn get_server_information_async() -> Result<ServerInformation> {
+    get_server_information_at_bus_async(Default::default()).await
+}
+
+/// Async version of [`get_server_information_at_bus()`].
+///
+/// (zbus only)
+#[cfg(all(feature = "zbus", not(feature = "dbus")))]
+pub async fn get_server_information_at_bus_async(
+    bus: NotificationBus,
+) -> Result<ServerInformation> {
+    zbus_rs::get_server_information_at_bus(bus).await
+}
+
+/// Async version of [`get_server_information_at_bus()`].
+///
+/// (dbus-rs only)
+#[cfg(all(feature = "dbus", not(feature = "zbus"), feature = "async"))]
+pub async fn get_server_information_at_bus_async(
+    bus: NotificationBus,
+) -> Result<ServerInformation> {
+    dbus_nonblock::get_server_information_at_bus(bus).await
+}
+
+/// Async version of [`get_server_information_at_bus()`].
+///
+/// Both dbus-rs and zbus are compiled in; switch via [`set_dbus_stack()`] or the `$DBUSRS` environment variable.
+#[cfg(all(feature = "dbus", feature = "zbus"))]
+pub async fn get_server_information_at_bus_async(
+    bus: NotificationBus,
+) -> Result<ServerInformation> {
+    if selected_stack() == DbusStack::Dbus {
+        dbus_nonblock::get_server_information_at_bus(bus).await
+    } else {
+        zbus_rs::get_server_information_at_bus(bus).await
+    }
+}
+
 /// Return value of [`get_server_information()`].
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize))]
@@ -559,6 +1138,56 @@ pub struct ServerInformation {
     pub spec_version: String,
 }
 
+/// The spec version hints are encoded for when the server does not tell its own.
+pub(crate) const FALLBACK_SPEC_VERSION: Version = Version::new(1, 1);
+
+/// The spec version from the server's answer to `GetServerInformation`.
+///
+/// Falls back to [`FALLBACK_SPEC_VERSION`] if the call failed or the answer is not a version.
+pub(crate) fn negotiated_spec_version(information: Result<ServerInformation>) -> Version {
+    information
+        .and_then(|information| information.spec_version.parse())
+        .unwrap_or_else(|error| {
+            log::warn!(
+                "assuming spec version {FALLBACK_SPEC_VERSION:?}, the server did not tell: {error}"
+            );
+            FALLBACK_SPEC_VERSION
+        })
+}
+
+/// The spec versions negotiated with the servers on one connection, by their unique name.
+///
+/// A server that restarts gets a new unique name, so it is asked again.
+#[derive(Debug, Default)]
+pub(crate) struct SpecVersions(Mutex<Vec<(String, Version)>>);
+
+impl SpecVersions {
+    pub(crate) fn get(&self, server: &str) -> Option<Version> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|(negotiated, _)| negotiated == server)
+            .map(|&(_, version)| version)
+    }
+
+    /// Negotiates with the answer of `server` to `GetServerInformation`, which is remembered unless there was none.
+    pub(crate) fn negotiate(
+        &self,
+        server: String,
+        information: Result<ServerInformation>,
+    ) -> Version {
+        let answered = information.is_ok();
+        let version = negotiated_spec_version(information);
+        if answered {
+            let mut versions = self.0.lock().unwrap_or_else(|e| e.into_inner());
+            versions.retain(|(negotiated, _)| *negotiated != server);
+            versions.push((server, version));
+        }
+        version
+    }
+}
+
 // /// Strictly internal.
 // /// The NotificationServer implemented here exposes a "Stop" function.
 // /// stops the notification server
@@ -579,8 +1208,10 @@ pub fn handle_action<F>(id: u32, func: F) -> Result<()>
 where
     F: FnOnce(&ActionResponse<'_>),
 {
-    block_on(zbus_rs::handle_action(id, action_response_adapter(func)));
-    Ok(())
+    block_on_responding(
+        |recorder| zbus_rs::handle_action(id, recorder),
+        action_response_adapter(func),
+    )
 }
 
 /// Listens for the `ActionInvoked(UInt32, String)` signal.
@@ -599,21 +1230,72 @@ where
 /// Listens for the `ActionInvoked(UInt32, String)` signal.
 ///
 /// Prefer [`NotificationHandle::wait_for_action`] instead.
-/// Both dbus-rs and zbus are compiled in; switch via the `$DBUSRS` environment variable.
+/// Both dbus-rs and zbus are compiled in; switch via [`set_dbus_stack()`] or the `$DBUSRS` environment variable.
 #[cfg(all(feature = "dbus", feature = "zbus"))]
 // #[deprecated(note="please use `NotificationHandle::wait_for_action`")]
 pub fn handle_action<F>(id: u32, func: F) -> Result<()>
 where
     F: FnOnce(&ActionResponse<'_>),
 {
-    if std::env::var(DBUS_SWITCH_VAR).is_ok() {
+    if selected_stack() == DbusStack::Dbus {
         dbus_rs::handle_action(id, action_response_adapter(func))
     } else {
-        block_on(zbus_rs::handle_action(id, action_response_adapter(func)));
-        Ok(())
+        block_on_responding(
+            |recorder| zbus_rs::handle_action(id, recorder),
+            action_response_adapter(func),
+        )
+    }
+}
+
+/// Async version of [`handle_action()`].
+///
+/// Prefer [`NotificationHandle::wait_for_action_async`] instead.
+/// (zbus only)
+#[cfg(all(feature = "zbus", not(feature = "dbus")))]
+pub async fn handle_action_async<F>(id: u32, func: F) -> Result<()>
+where
+    F: FnOnce(&ActionResponse<'_>),
+{
+    zbus_rs::handle_action(id, action_response_adapter(func)).await
+}
+
+/// Async version of [`handle_action()`].
+///
+/// Prefer [`NotificationHandle::wait_for_action_async`] instead.
+/// (dbus-rs only)
+#[cfg(all(feature = "dbus", not(feature = "zbus"), feature = "async"))]
+pub async fn handle_action_async<F>(id: u32, func: F) -> Result<()>
+where
+    F: FnOnce(&ActionResponse<'_>),
+{
+    dbus_nonblock::handle_action(id, action_response_adapter(func)).await
+}
+
+/// Async version of [`handle_action()`].
+///
+/// Prefer [`NotificationHandle::wait_for_action_async`] instead.
+/// Both dbus-rs and zbus are compiled in; switch via [`set_dbus_stack()`] or the `$DBUSRS` environment variable.
+#[cfg(all(feature = "dbus", feature = "zbus"))]
+pub async fn handle_action_async<F>(id: u32, func: F) -> Result<()>
+where
+    F: FnOnce(&ActionResponse<'_>),
+{
+    if selected_stack() == DbusStack::Dbus {
+        dbus_nonblock::handle_action(id, action_response_adapter(func)).await
+    } else {
+        zbus_rs::handle_action(id, action_response_adapter(func)).await
     }
 }
 
+/// Turns a failed wait into no response for the blocking API, which has no way to report it.
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+fn logged(next: Result<Option<NotificationResponse>>) -> Option<NotificationResponse> {
+    next.unwrap_or_else(|error| {
+        log::warn!("failed to wait for notification responses: {error}");
+        None
+    })
+}
+
 /// Wraps an old-style `FnOnce(&ActionResponse)` into a new-style `FnOnce(&NotificationResponse)`
 /// so legacy callers of [`handle_action`] keep working.
 fn action_response_adapter<F>(func: F) -> impl FnOnce(&NotificationResponse)
@@ -0,0 +1,993 @@
+//! A reusable client that keeps one D-Bus connection open.
+
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+use super::blocking::{block_on, block_on_responding};
+
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+use std::{future::Future, task::Poll};
+use std::{
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+use super::dbus_nonblock::{self, AsyncConnection};
+#[cfg(feature = "dbus")]
+use super::dbus_rs;
+use super::{
+    bus::NotificationBus, BusAddress, DbusStack, NotificationHandle, ServerInformation,
+    ServerRestartPolicy,
+};
+#[cfg(feature = "zbus")]
+use super::{router::RoutedConnection, zbus_rs};
+use crate::{error::*, notification::Notification, response::ResponseHandler};
+
+#[derive(Clone, Debug)]
+enum NotifierInner {
+    #[cfg(feature = "dbus")]
+    Dbus(Arc<dbus_rs::SharedConnection>, Duration),
+
+    /// Connected by [`NotifierBuilder::connect_async()`], the blocking methods wait for the async ones.
+    #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+    DbusAsync(AsyncConnection, Duration),
+
+    /// Routes the responses to notifications with callbacks itself, see [`Notification::action_with()`].
+    #[cfg(feature = "zbus")]
+    Zbus(RoutedConnection),
+}
+
+/// A client that owns a single D-Bus connection and reuses it for every call.
+///
+/// [`Notification::show()`] and friends open a new connection every time they are called.
+/// If you send many notifications, create one `Notifier` and send them all through it.
+/// Every [`NotificationHandle`] returned by a `Notifier` shares its connection.
+///
+/// # Example
+///
+/// ```no_run
+/// # use notify_rust::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let notifier = Notifier::new()?;
+/// println!("talking to {}", notifier.server_information()?.name);
+///
+/// for i in 0..3 {
+///     notifier.show(Notification::new().summary(&format!("message #{i}")))?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Notifier {
+    inner: NotifierInner,
+    /// For the dispatcher's own connection.
+    #[cfg(feature = "dbus")]
+    address: BusAddress,
+    bus: NotificationBus,
+    /// Routes the responses to notifications with callbacks, see [`Notification::action_with()`].
+    ///
+    /// With a connection of its own, `ffidisp` connections cannot be shared with the listening thread.
+    #[cfg(feature = "dbus")]
+    dispatcher: Arc<OnceLock<dbus_rs::DispatcherThread>>,
+    /// The connection of the async API of a [`Dbus`](DbusStack::Dbus) notifier, opened once it is first used.
+    #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+    nonblock: OnceLock<AsyncConnection>,
+    server_information: OnceLock<ServerInformation>,
+    server_deadline: Option<Instant>,
+    /// Shared with the helper thread of [`send()`](Self::send).
+    waiting: Arc<Mutex<Waiting>>,
+    restart_policy: ServerRestartPolicy,
+}
+
+/// What [`Notifier::send()`] holds back until a server shows up, see [`NotifierBuilder::wait_for_server()`].
+#[derive(Debug, Default)]
+struct Waiting {
+    /// The names a server was seen to own, they are not asked for again.
+    seen: Vec<String>,
+    queued: Vec<Notification>,
+    /// Whether a helper thread waits for the servers of the queued notifications.
+    flushing: bool,
+}
+
+impl Notifier {
+    /// Connects to the session bus.
+    pub fn new() -> Result<Notifier> {
+        Self::builder().connect()
+    }
+
+    /// Connects to the session bus asynchronously.
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn new_async() -> Result<Notifier> {
+        Self::builder().connect_async().await
+    }
+
+    /// Connects to the given [`BusAddress`].
+    pub fn connect(address: BusAddress) -> Result<Notifier> {
+        Self::builder().address(address).connect()
+    }
+
+    /// Connects to the given [`BusAddress`] asynchronously.
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn connect_async(address: BusAddress) -> Result<Notifier> {
+        Self::builder().address(address).connect_async().await
+    }
+
+    /// Configures a `Notifier` before connecting.
+    ///
+    /// ```no_run
+    /// # use notify_rust::*;
+    /// # use std::time::Duration;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let notifier = Notifier::builder()
+    ///     .address(BusAddress::System)
+    ///     .call_timeout(Duration::from_millis(500))
+    ///     .connect()?;
+    ///
+    /// match notifier.show(Notification::new().summary("are you there?")) {
+    ///     Err(error) if error.is_call_timeout() => eprintln!("the notification daemon hangs"),
+    ///     result => drop(result?),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder() -> NotifierBuilder {
+        NotifierBuilder::default()
+    }
+
+    fn from_inner(inner: NotifierInner, builder: &NotifierBuilder) -> Notifier {
+        Notifier {
+            inner,
+            #[cfg(feature = "dbus")]
+            address: builder.address.clone(),
+            bus: Default::default(),
+            #[cfg(feature = "dbus")]
+            dispatcher: Default::default(),
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            nonblock: OnceLock::new(),
+            server_information: OnceLock::new(),
+            server_deadline: builder
+                .server_timeout
+                .map(|timeout| Instant::now() + timeout),
+            waiting: Default::default(),
+            restart_policy: builder.restart_policy,
+        }
+    }
+
+    /// A notifier on the same connection for the helper thread of [`send()`](Self::send), it does not wait for servers.
+    fn helper(&self) -> Notifier {
+        Notifier {
+            inner: self.inner.clone(),
+            #[cfg(feature = "dbus")]
+            address: self.address.clone(),
+            bus: self.bus.clone(),
+            #[cfg(feature = "dbus")]
+            dispatcher: self.dispatcher.clone(),
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            nonblock: OnceLock::new(),
+            server_information: OnceLock::new(),
+            server_deadline: None,
+            waiting: self.waiting.clone(),
+            restart_policy: self.restart_policy,
+        }
+    }
+
+    /// Targets a notification server at another well-known name or object path.
+    ///
+    /// This applies to [`close()`](Self::close), [`capabilities()`](Self::capabilities),
+    /// [`server_information()`](Self::server_information) and [`wait_for_response()`](Self::wait_for_response).
+    /// Notifications are sent to their own [`Notification::bus()`].
+    pub fn with_bus(mut self, bus: NotificationBus) -> Notifier {
+        self.bus = bus;
+        self.server_information = OnceLock::new();
+        self
+    }
+
+    /// The D-Bus implementation this notifier is connected with.
+    pub fn dbus_stack(&self) -> DbusStack {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(..) => DbusStack::Dbus,
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotifierInner::DbusAsync(..) => DbusStack::Dbus,
+            #[cfg(feature = "zbus")]
+            NotifierInner::Zbus(_) => DbusStack::Zbus,
+        }
+    }
+
+    /// Waits until a notification server owns the name of this notifier's [`NotificationBus`].
+    ///
+    /// Returns `false` if no server showed up within `timeout`.
+    ///
+    /// ```no_run
+    /// # use notify_rust::*;
+    /// # use std::time::Duration;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let notifier = Notifier::new()?;
+    /// if notifier.wait_for_server(Duration::from_secs(10))? {
+    ///     notifier.show(Notification::new().summary("autostarted"))?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn wait_for_server(&self, timeout: Duration) -> Result<bool> {
+        self.wait_for_name_owner(self.bus.name(), timeout)
+    }
+
+    /// Waits until a notification server owns the name of this notifier's [`NotificationBus`] asynchronously.
+    ///
+    /// Returns `false` if no server showed up within `timeout`.
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn wait_for_server_async(&self, timeout: Duration) -> Result<bool> {
+        self.wait_for_name_owner_async(self.bus.name(), timeout)
+            .await
+    }
+
+    fn wait_for_name_owner(&self, name: &str, timeout: Duration) -> Result<bool> {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(ref connection, _) => {
+                dbus_rs::wait_for_name_owner(connection, name, timeout)
+            }
+            #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+            _ => block_on(self.wait_for_name_owner_async(name, timeout)),
+        }
+    }
+
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    async fn wait_for_name_owner_async(&self, name: &str, timeout: Duration) -> Result<bool> {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(..) | NotifierInner::DbusAsync(..) => {
+                let (connection, _) = self.nonblock().await?;
+                dbus_nonblock::wait_for_name_owner(connection, name, timeout).await
+            }
+            #[cfg(feature = "zbus")]
+            NotifierInner::Zbus(ref connection) => {
+                zbus_rs::wait_for_name_owner(connection, name, timeout).await
+            }
+        }
+    }
+
+    /// Whether a server owns `name`, see [`NotifierBuilder::wait_for_server()`].
+    ///
+    /// Only asked for notifiers that wait for the server, and only until one was seen.
+    fn server_available(&self, name: &str) -> Result<bool> {
+        if !self.checks_server(name) {
+            return Ok(true);
+        }
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(ref connection, call_timeout) => {
+                dbus_rs::has_name_owner(connection, name, call_timeout)
+                    .map(|owned| self.saw_server(name, owned))
+            }
+            #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+            _ => block_on(self.server_available_async(name)),
+        }
+    }
+
+    /// Like [`server_available()`](Self::server_available), without blocking.
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    async fn server_available_async(&self, name: &str) -> Result<bool> {
+        if !self.checks_server(name) {
+            return Ok(true);
+        }
+        let owned = match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(..) | NotifierInner::DbusAsync(..) => {
+                let (connection, call_timeout) = self.nonblock().await?;
+                dbus_nonblock::has_name_owner(connection, name, call_timeout).await?
+            }
+            #[cfg(feature = "zbus")]
+            NotifierInner::Zbus(ref connection) => {
+                zbus_rs::has_name_owner(connection, name).await?
+            }
+        };
+        Ok(self.saw_server(name, owned))
+    }
+
+    fn checks_server(&self, name: &str) -> bool {
+        self.server_deadline.is_some() && !self.lock_waiting().seen.iter().any(|seen| seen == name)
+    }
+
+    /// Remembers that a server owns `name` if it does, returns whether it does.
+    fn saw_server(&self, name: &str, owned: bool) -> bool {
+        let mut waiting = self.lock_waiting();
+        if owned && !waiting.seen.iter().any(|seen| seen == name) {
+            waiting.seen.push(name.to_owned());
+        }
+        owned
+    }
+
+    fn lock_waiting(&self) -> MutexGuard<'_, Waiting> {
+        self.waiting.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sends the notification without a handle, or queues it until a server shows up.
+    ///
+    /// With [`NotifierBuilder::wait_for_server()`], a notification for a bus name nobody owns yet is queued
+    /// instead of failing, a helper thread sends it as soon as a server claims the name.
+    /// Notifications still queued once the timeout passed are dropped with a warning.
+    /// Otherwise this is [`show()`](Self::show) without the handle.
+    ///
+    /// ```no_run
+    /// # use notify_rust::*;
+    /// # use std::time::Duration;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let notifier = Notifier::builder()
+    ///     .wait_for_server(Duration::from_secs(30))
+    ///     .connect()?;
+    /// // started with the session, the notification server may not be there yet
+    /// notifier.send(Notification::new().summary("Sync finished"))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send(&self, notification: &Notification) -> Result<()> {
+        let name = notification.bus.name();
+        {
+            let mut waiting = self.lock_waiting();
+            // behind the ones queued before
+            if waiting
+                .queued
+                .iter()
+                .any(|queued| queued.bus.name() == name)
+            {
+                waiting.queued.push(notification.clone());
+                return Ok(());
+            }
+        }
+        if self.server_available(name)? {
+            return self.show(notification).map(drop);
+        }
+        let Some(deadline) = self
+            .server_deadline
+            .filter(|&deadline| deadline > Instant::now())
+        else {
+            return Err(ErrorKind::ServerUnavailable.into());
+        };
+        let mut waiting = self.lock_waiting();
+        waiting.queued.push(notification.clone());
+        if !waiting.flushing {
+            let helper = self.helper();
+            thread::Builder::new()
+                .name("notify-rust queue".into())
+                .spawn(move || {
+                    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+                    super::blocking::mark_off_executor();
+                    helper.flush(deadline);
+                })
+                .map_err(|error| Error::from(error.to_string().as_str()))?;
+            waiting.flushing = true;
+        }
+        Ok(())
+    }
+
+    /// Runs on the helper thread of [`send()`](Self::send) until nothing is queued.
+    fn flush(&self, deadline: Instant) {
+        loop {
+            let name = {
+                let mut waiting = self.lock_waiting();
+                let Some(queued) = waiting.queued.first() else {
+                    waiting.flushing = false;
+                    return;
+                };
+                queued.bus.name().to_owned()
+            };
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let owned = self
+                .wait_for_name_owner(&name, timeout)
+                .unwrap_or_else(|error| {
+                    log::warn!("failed to wait for a notification server: {error}");
+                    false
+                });
+            // until the queue is empty, the notifications sent in the meantime were queued behind
+            loop {
+                let queued: Vec<Notification> = {
+                    let mut waiting = self.lock_waiting();
+                    let (queued, rest) = std::mem::take(&mut waiting.queued)
+                        .into_iter()
+                        .partition(|queued| queued.bus.name() == name);
+                    waiting.queued = rest;
+                    if queued.is_empty() {
+                        if owned {
+                            waiting.seen.push(name);
+                        }
+                        break;
+                    }
+                    queued
+                };
+                for notification in &queued {
+                    if !owned {
+                        log::warn!(
+                            "no notification server showed up at {name}, dropped a notification"
+                        );
+                    } else if let Err(error) = self.show(notification) {
+                        log::warn!("failed to send a queued notification: {error}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends the notification over this notifier's connection.
+    ///
+    /// The returned handle shares the connection.
+    pub fn show(&self, notification: &Notification) -> Result<NotificationHandle> {
+        if !self.server_available(notification.bus.name())? {
+            return Err(ErrorKind::ServerUnavailable.into());
+        }
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(ref connection, call_timeout) => {
+                let id = notification.id.unwrap_or(0);
+                let bus = &notification.bus;
+                let (id, server, responses, dispatcher) =
+                    if notification.action_callbacks.is_empty() {
+                        let (id, server, responses) =
+                            dbus_rs::send_subscribed(connection, bus, || {
+                                dbus_rs::send_notification_via_connection_at_bus(
+                                    notification,
+                                    id,
+                                    connection,
+                                    bus.clone(),
+                                    call_timeout,
+                                )
+                            })?;
+                        (id, server, responses, None)
+                    } else {
+                        let thread = self.dbus_dispatcher()?;
+                        let (id, server, responses) =
+                            dbus_rs::send_subscribed(connection, bus, || {
+                                thread.send(notification, id, call_timeout)
+                            })?;
+                        (id, server, responses, Some(thread.dispatcher.clone()))
+                    };
+                Ok(NotificationHandle::for_dbus(
+                    id,
+                    connection.clone(),
+                    self.address.clone(),
+                    notification.clone(),
+                    server,
+                    call_timeout,
+                    self.restart_policy,
+                    dispatcher,
+                    Some(responses),
+                ))
+            }
+            #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+            _ => block_on(self.show_async(notification)),
+        }
+    }
+
+    /// Sends all notifications over this notifier's connection, without waiting for a reply before sending the next.
+    ///
+    /// Returns a result for each notification, in the same order.
+    /// Saves a round trip per notification over calling [`show()`](Self::show) for each of them;
+    /// with the [`Dbus`](super::DbusStack::Dbus) backend, notifications with callbacks are still sent one by one.
+    ///
+    /// ```no_run
+    /// # use notify_rust::*;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let failed_jobs = ["build", "test", "lint"].map(|job| {
+    ///     Notification::new()
+    ///         .summary("CI")
+    ///         .body(&format!("{job} failed"))
+    ///         .finalize()
+    /// });
+    ///
+    /// for result in Notifier::new()?.show_all(&failed_jobs) {
+    ///     result?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn show_all(&self, notifications: &[Notification]) -> Vec<Result<NotificationHandle>> {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(ref connection, call_timeout) => {
+                self.show_all_dbus(connection, call_timeout, notifications)
+            }
+            #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+            _ => block_on(self.show_all_async(notifications)),
+        }
+    }
+
+    /// Sends all notifications over this notifier's connection asynchronously,
+    /// without waiting for a reply before sending the next.
+    ///
+    /// Returns a result for each notification, in the same order.
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn show_all_async(
+        &self,
+        notifications: &[Notification],
+    ) -> Vec<Result<NotificationHandle>> {
+        join_all(
+            notifications
+                .iter()
+                .map(|notification| self.show_async(notification)),
+        )
+        .await
+    }
+
+    /// [`show_all()`](Self::show_all) with the `dbus` backend.
+    #[cfg(feature = "dbus")]
+    fn show_all_dbus(
+        &self,
+        connection: &Arc<dbus_rs::SharedConnection>,
+        call_timeout: Duration,
+        notifications: &[Notification],
+    ) -> Vec<Result<NotificationHandle>> {
+        // `None` until the batch a notification is part of was sent
+        let mut results: Vec<Option<Result<NotificationHandle>>> = Vec::new();
+        let mut batch: Vec<(usize, &Notification)> = Vec::new();
+        let mut available: Vec<&str> = Vec::new();
+        let send_batch =
+            |batch: &mut Vec<(usize, &Notification)>,
+             results: &mut Vec<Option<Result<NotificationHandle>>>| {
+                let (indices, sent): (Vec<usize>, Vec<&Notification>) = batch.drain(..).unzip();
+                let sent_all = dbus_rs::send_all_subscribed(connection, &sent, call_timeout);
+                for ((index, notification), sent) in indices.into_iter().zip(&sent).zip(sent_all) {
+                    results[index] = Some(sent.map(|(id, server, responses)| {
+                        NotificationHandle::for_dbus(
+                            id,
+                            connection.clone(),
+                            self.address.clone(),
+                            (*notification).clone(),
+                            server,
+                            call_timeout,
+                            self.restart_policy,
+                            None,
+                            Some(responses),
+                        )
+                    }));
+                }
+            };
+        for notification in notifications {
+            let name = notification.bus.name();
+            if !available.contains(&name) {
+                match self.server_available(name) {
+                    Ok(true) => available.push(name),
+                    Ok(false) => {
+                        results.push(Some(Err(ErrorKind::ServerUnavailable.into())));
+                        continue;
+                    }
+                    Err(error) => {
+                        results.push(Some(Err(error)));
+                        continue;
+                    }
+                }
+            }
+            if notification.action_callbacks.is_empty() {
+                batch.push((results.len(), notification));
+                results.push(None);
+            } else {
+                // sent by the dispatcher thread, after the ones before it to keep the order
+                send_batch(&mut batch, &mut results);
+                results.push(Some(self.show(notification)));
+            }
+        }
+        send_batch(&mut batch, &mut results);
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err("not sent".into())))
+            .collect()
+    }
+
+    /// Sends the notification over this notifier's connection asynchronously.
+    ///
+    /// The returned handle shares the connection.
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn show_async(&self, notification: &Notification) -> Result<NotificationHandle> {
+        if !self.server_available_async(notification.bus.name()).await? {
+            return Err(ErrorKind::ServerUnavailable.into());
+        }
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(..) | NotifierInner::DbusAsync(..) => {
+                let (connection, call_timeout) = self.nonblock().await?;
+                let handle = dbus_nonblock::show(
+                    connection,
+                    notification,
+                    call_timeout,
+                    self.restart_policy,
+                )
+                .await?;
+                Ok(handle.into())
+            }
+            #[cfg(feature = "zbus")]
+            NotifierInner::Zbus(ref connection) => {
+                let (id, server) = zbus_rs::send_subscribed(
+                    notification,
+                    notification.id.unwrap_or(0),
+                    connection,
+                    &notification.bus,
+                )
+                .await?;
+                NotificationHandle::for_zbus(
+                    id,
+                    connection.clone(),
+                    notification.clone(),
+                    server,
+                    self.restart_policy,
+                )
+            }
+        }
+    }
+
+    /// The connection for the async API with the `dbus` backend, with its call timeout.
+    ///
+    /// A notifier connected by the blocking API opens it on first use.
+    #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+    async fn nonblock(&self) -> Result<(&AsyncConnection, Duration)> {
+        let call_timeout = match self.inner {
+            NotifierInner::DbusAsync(ref connection, call_timeout) => {
+                return Ok((connection, call_timeout))
+            }
+            NotifierInner::Dbus(_, call_timeout) => call_timeout,
+            #[cfg(feature = "zbus")]
+            NotifierInner::Zbus(_) => unreachable!("only called for the dbus backend"),
+        };
+        if let Some(connection) = self.nonblock.get() {
+            return Ok((connection, call_timeout));
+        }
+        let connection = AsyncConnection::connect(&self.address).await?;
+        // somebody else may have been quicker, theirs is used then
+        Ok((self.nonblock.get_or_init(|| connection), call_timeout))
+    }
+
+    /// Starts listening for the responses to notifications with callbacks on first use.
+    #[cfg(feature = "dbus")]
+    fn dbus_dispatcher(&self) -> Result<&dbus_rs::DispatcherThread> {
+        if let Some(thread) = self.dispatcher.get() {
+            return Ok(thread);
+        }
+        let thread = dbus_rs::DispatcherThread::spawn(self.address.clone())?;
+        // somebody else may have been quicker, theirs is used then
+        Ok(self.dispatcher.get_or_init(|| thread))
+    }
+
+    /// Closes the notification with the given id.
+    pub fn close(&self, id: u32) -> Result<()> {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(ref connection, call_timeout) => {
+                dbus_rs::close_notification_via_connection_at_bus(
+                    id,
+                    connection,
+                    self.bus.clone(),
+                    call_timeout,
+                )
+            }
+            #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+            _ => block_on(self.close_async(id)),
+        }
+    }
+
+    /// Closes the notification with the given id asynchronously.
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn close_async(&self, id: u32) -> Result<()> {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(..) | NotifierInner::DbusAsync(..) => {
+                let (connection, call_timeout) = self.nonblock().await?;
+                dbus_nonblock::close(connection, id, self.bus.clone(), call_timeout).await
+            }
+            #[cfg(feature = "zbus")]
+            NotifierInner::Zbus(ref connection) => {
+                zbus_rs::close_notification_via_connection_at_bus(id, connection, self.bus.clone())
+                    .await
+            }
+        }
+    }
+
+    /// Returns a list of all capabilities of the notification server.
+    pub fn capabilities(&self) -> Result<Vec<String>> {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(ref connection, call_timeout) => {
+                dbus_rs::get_capabilities_via_connection_at_bus(
+                    connection,
+                    self.bus.clone(),
+                    call_timeout,
+                )
+            }
+            #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+            _ => block_on(self.capabilities_async()),
+        }
+    }
+
+    /// Returns a list of all capabilities of the notification server asynchronously.
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn capabilities_async(&self) -> Result<Vec<String>> {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(..) | NotifierInner::DbusAsync(..) => {
+                let (connection, call_timeout) = self.nonblock().await?;
+                dbus_nonblock::capabilities(connection, self.bus.clone(), call_timeout).await
+            }
+            #[cfg(feature = "zbus")]
+            NotifierInner::Zbus(ref connection) => {
+                zbus_rs::get_capabilities_via_connection_at_bus(connection, self.bus.clone()).await
+            }
+        }
+    }
+
+    /// Returns the [`ServerInformation`] of the notification server.
+    ///
+    /// The server is only asked once, later calls return the cached value.
+    pub fn server_information(&self) -> Result<&ServerInformation> {
+        if let Some(info) = self.server_information.get() {
+            return Ok(info);
+        }
+        let info = match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(ref connection, call_timeout) => {
+                dbus_rs::get_server_information_via_connection_at_bus(
+                    connection,
+                    self.bus.clone(),
+                    call_timeout,
+                )?
+            }
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            NotifierInner::DbusAsync(..) => return block_on(self.server_information_async()),
+            #[cfg(feature = "zbus")]
+            NotifierInner::Zbus(ref connection) => block_on(
+                zbus_rs::get_server_information_via_connection_at_bus(connection, self.bus.clone()),
+            )?,
+        };
+        Ok(self.server_information.get_or_init(|| info))
+    }
+
+    /// Returns the [`ServerInformation`] of the notification server asynchronously.
+    ///
+    /// Shares the cache with [`server_information()`](Self::server_information).
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn server_information_async(&self) -> Result<&ServerInformation> {
+        if let Some(info) = self.server_information.get() {
+            return Ok(info);
+        }
+        let info = match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(..) | NotifierInner::DbusAsync(..) => {
+                let (connection, call_timeout) = self.nonblock().await?;
+                dbus_nonblock::server_information(connection, self.bus.clone(), call_timeout)
+                    .await?
+            }
+            #[cfg(feature = "zbus")]
+            NotifierInner::Zbus(ref connection) => {
+                zbus_rs::get_server_information_via_connection_at_bus(connection, self.bus.clone())
+                    .await?
+            }
+        };
+        Ok(self.server_information.get_or_init(|| info))
+    }
+
+    /// Waits for the `ActionInvoked` or `NotificationClosed` signal of the notification with the given id
+    /// and then calls `handler` with the [`NotificationResponse`](crate::NotificationResponse).
+    ///
+    /// This listens on the notifier's connection, no new connection is opened.
+    pub fn wait_for_response(&self, id: u32, handler: impl ResponseHandler) -> Result<()> {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(ref connection, _) => {
+                dbus_rs::wait_for_action_signal(connection, &self.bus, id, handler)
+            }
+            #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+            _ => block_on_responding(
+                |recorder| self.wait_for_response_async(id, recorder),
+                handler,
+            ),
+        }
+    }
+
+    /// Async version of [`wait_for_response()`](Self::wait_for_response).
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn wait_for_response_async(
+        &self,
+        id: u32,
+        handler: impl ResponseHandler,
+    ) -> Result<()> {
+        match self.inner {
+            #[cfg(feature = "dbus")]
+            NotifierInner::Dbus(..) | NotifierInner::DbusAsync(..) => {
+                let (connection, _) = self.nonblock().await?;
+                dbus_nonblock::wait_for_action_signal(connection, &self.bus, id, handler).await
+            }
+            #[cfg(feature = "zbus")]
+            NotifierInner::Zbus(ref connection) => {
+                zbus_rs::wait_for_action_signal(connection, &self.bus, id, handler).await
+            }
+        }
+    }
+}
+
+/// Configures and connects a [`Notifier`].
+///
+/// Created by [`Notifier::builder()`].
+#[derive(Clone, Debug, Default)]
+pub struct NotifierBuilder {
+    address: BusAddress,
+    call_timeout: Option<Duration>,
+    server_timeout: Option<Duration>,
+    restart_policy: ServerRestartPolicy,
+    stack: Option<DbusStack>,
+}
+
+impl NotifierBuilder {
+    /// Where to connect to, the session bus by default.
+    pub fn address(&mut self, address: BusAddress) -> &mut NotifierBuilder {
+        self.address = address;
+        self
+    }
+
+    /// How long to wait for the notification server to reply to a method call.
+    ///
+    /// Applies to sending, closing and querying the server.
+    /// A call that exceeds it fails with an error for which [`Error::is_call_timeout()`] is `true`.
+    ///
+    /// Defaults to 2 seconds with dbus-rs and to no timeout at all with zbus.
+    pub fn call_timeout(&mut self, timeout: Duration) -> &mut NotifierBuilder {
+        self.call_timeout = Some(timeout);
+        self
+    }
+
+    /// Holds back notifications until a notification server has shown up on the bus.
+    ///
+    /// Useful for applications that are started with the session, possibly before the notification server.
+    /// Until `timeout` has passed since connecting, [`Notifier::send()`] queues the notifications
+    /// for a bus name nobody owns yet, they are sent once a server claims the name.
+    /// [`Notifier::show()`] has no handle to return for a queued notification,
+    /// it fails with [`ErrorKind::ServerUnavailable`] right away, [`Notifier::wait_for_server()`] blocks instead.
+    ///
+    /// The bus is asked for the owner of a name until a server was seen, afterwards nothing is checked.
+    pub fn wait_for_server(&mut self, timeout: Duration) -> &mut NotifierBuilder {
+        self.server_timeout = Some(timeout);
+        self
+    }
+
+    /// What happens to notifications shown by this notifier when the notification server restarts.
+    ///
+    /// Defaults to [`ServerRestartPolicy::Vanish`].
+    pub fn on_server_restart(&mut self, policy: ServerRestartPolicy) -> &mut NotifierBuilder {
+        self.restart_policy = policy;
+        self
+    }
+
+    /// Which D-Bus implementation to connect with, regardless of [`set_dbus_stack()`](super::set_dbus_stack)
+    /// and the `$DBUSRS` environment variable.
+    ///
+    /// Connecting fails if `stack` is not compiled in.
+    pub fn dbus_stack(&mut self, stack: DbusStack) -> &mut NotifierBuilder {
+        self.stack = Some(stack);
+        self
+    }
+
+    /// The stack asked for with [`dbus_stack()`](Self::dbus_stack), if it is compiled in.
+    fn requested_stack(&self) -> Result<Option<DbusStack>> {
+        match self.stack {
+            Some(stack) if !stack.is_available() => Err(ErrorKind::ImplementationMissing.into()),
+            stack => Ok(stack),
+        }
+    }
+
+    /// Connects the [`Notifier`].
+    ///
+    /// (zbus only)
+    #[cfg(all(feature = "zbus", not(feature = "dbus")))]
+    pub fn connect(&self) -> Result<Notifier> {
+        block_on(self.connect_async())
+    }
+
+    /// Connects the [`Notifier`].
+    ///
+    /// (dbus-rs only)
+    #[cfg(all(feature = "dbus", not(feature = "zbus")))]
+    pub fn connect(&self) -> Result<Notifier> {
+        self.requested_stack()?;
+        self.connect_dbus()
+    }
+
+    /// Connects the [`Notifier`].
+    ///
+    /// Both dbus-rs and zbus are compiled in; switch via [`dbus_stack()`](Self::dbus_stack),
+    /// [`set_dbus_stack()`](super::set_dbus_stack) or the `$DBUSRS` environment variable.
+    #[cfg(all(feature = "dbus", feature = "zbus"))]
+    pub fn connect(&self) -> Result<Notifier> {
+        match self
+            .requested_stack()?
+            .unwrap_or_else(super::selected_stack)
+        {
+            DbusStack::Dbus => self.connect_dbus(),
+            DbusStack::Zbus => block_on(self.connect_async()),
+        }
+    }
+
+    /// Connects the [`Notifier`] asynchronously.
+    ///
+    /// (zbus only)
+    #[cfg(all(feature = "zbus", not(feature = "dbus")))]
+    pub async fn connect_async(&self) -> Result<Notifier> {
+        self.requested_stack()?;
+        self.connect_zbus().await
+    }
+
+    /// Connects the [`Notifier`] asynchronously.
+    ///
+    /// (dbus-rs only)
+    #[cfg(all(feature = "dbus", not(feature = "zbus"), feature = "async"))]
+    pub async fn connect_async(&self) -> Result<Notifier> {
+        self.requested_stack()?;
+        self.connect_dbus_async().await
+    }
+
+    /// Connects the [`Notifier`] asynchronously.
+    ///
+    /// The stack is picked like for [`connect()`](Self::connect).
+    #[cfg(all(feature = "dbus", feature = "zbus"))]
+    pub async fn connect_async(&self) -> Result<Notifier> {
+        match self
+            .requested_stack()?
+            .unwrap_or_else(super::selected_stack)
+        {
+            DbusStack::Dbus => self.connect_dbus_async().await,
+            DbusStack::Zbus => self.connect_zbus().await,
+        }
+    }
+
+    #[cfg(feature = "zbus")]
+    async fn connect_zbus(&self) -> Result<Notifier> {
+        let connection = zbus_rs::connect(&self.address, self.call_timeout).await?;
+        Ok(Notifier::from_inner(
+            NotifierInner::Zbus(RoutedConnection::new(connection)),
+            self,
+        ))
+    }
+
+    #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+    async fn connect_dbus_async(&self) -> Result<Notifier> {
+        let connection = AsyncConnection::connect(&self.address).await?;
+        let call_timeout = self.call_timeout.unwrap_or(dbus_rs::DEFAULT_CALL_TIMEOUT);
+        Ok(Notifier::from_inner(
+            NotifierInner::DbusAsync(connection, call_timeout),
+            self,
+        ))
+    }
+
+    #[cfg(feature = "dbus")]
+    fn connect_dbus(&self) -> Result<Notifier> {
+        let connection = dbus_rs::connect_shared(&self.address)?;
+        let call_timeout = self.call_timeout.unwrap_or(dbus_rs::DEFAULT_CALL_TIMEOUT);
+        Ok(Notifier::from_inner(
+            NotifierInner::Dbus(connection, call_timeout),
+            self,
+        ))
+    }
+}
+
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+/// Runs all `futures` at once and returns their outputs in order.
+///
+/// Method calls of the futures on the same connection are pipelined, since every future sends
+/// its next call as soon as it is polled, instead of waiting for the replies to the ones before.
+async fn join_all<F: Future>(futures: impl IntoIterator<Item = F>) -> Vec<F::Output> {
+    let mut futures: Vec<_> = futures.into_iter().map(Box::pin).collect();
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    futures_lite::future::poll_fn(|cx| {
+        let mut done = true;
+        for (future, output) in futures.iter_mut().zip(&mut outputs) {
+            if output.is_none() {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(ready) => *output = Some(ready),
+                    Poll::Pending => done = false,
+                }
+            }
+        }
+        if done {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+    outputs.into_iter().flatten().collect()
+}
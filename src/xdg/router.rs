@@ -0,0 +1,622 @@
+//! Routes the signals of a zbus connection to whoever follows the notifications shown over it.
+//!
+//! A connection subscribes once per [`NotificationBus`] it shows notifications on, instead of once per notification.
+//! One reader task drains the subscriptions and hands every signal to the listeners of the notification it is about,
+//! or to the callbacks registered with the connection's [`Dispatcher`].
+//! Signals nobody claimed are kept for a while, for listeners that only start after the server answered.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::poll_fn,
+    ops::Deref,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc, Arc, Mutex, MutexGuard, Weak,
+    },
+    task::Poll,
+    thread,
+};
+
+use futures_lite::Stream;
+use zbus::MatchRule;
+
+use super::{
+    bus::NotificationBus, dispatcher::Dispatcher, expiry::Expiries, queue::Queue, SpecVersions,
+    NOTIFICATION_INTERFACE,
+};
+use crate::{error::*, notification::Notification};
+
+/// How many signals nobody claimed are kept around.
+const BACKLOG: usize = 64;
+
+/// A zbus connection together with the router for its signals.
+///
+/// Clones share both. The subscriptions are removed once the last clone is dropped,
+/// along with the callbacks of the notifications shown over it.
+#[derive(Clone, Debug)]
+pub(crate) struct RoutedConnection {
+    connection: zbus::Connection,
+    router: Arc<Router>,
+}
+
+impl RoutedConnection {
+    pub(crate) fn new(connection: zbus::Connection) -> RoutedConnection {
+        RoutedConnection {
+            router: Arc::new(Router::new(connection.clone())),
+            connection,
+        }
+    }
+
+    /// Subscribes to the signals of the notifications at `bus`, unless that was done already.
+    ///
+    /// The rule names the well-known name of `bus` as the sender,
+    /// which the bus matches against whoever owns the name at the time, so it survives server restarts.
+    pub(crate) async fn subscribe(&self, bus: &NotificationBus) -> Result<()> {
+        let rule = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface(NOTIFICATION_INTERFACE)?
+            .path(bus.path())?;
+        // peer-to-peer connections have no sender to match
+        let rule = if self.connection.is_bus() {
+            rule.sender(bus.name())?
+        } else {
+            rule
+        };
+        let rule = rule.build().into_owned();
+        let subscription = Subscription::Responses {
+            name: bus.name().to_owned(),
+            path: bus.path().to_owned(),
+        };
+        self.router.add(subscription, rule).await
+    }
+
+    /// Subscribes to the owner changes of the bus `name`, unless that was done already.
+    pub(crate) async fn watch_owner(&self, name: &str) -> Result<()> {
+        if !self.connection.is_bus() {
+            return Ok(());
+        }
+        let rule = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .sender("org.freedesktop.DBus")?
+            .interface("org.freedesktop.DBus")?
+            .member("NameOwnerChanged")?
+            .arg(0, name)?
+            .build()
+            .into_owned();
+        self.router
+            .add(Subscription::Owner(name.to_owned()), rule)
+            .await
+    }
+
+    /// Routes the signals about the notification at `bus` to the returned [`Route`].
+    ///
+    /// Only signals sent by `server`, the unique name of the server that showed it, are routed,
+    /// unless it is `None` on a peer-to-peer connection.
+    /// Follows `id` when the notification is shown again under another one.
+    /// Signals that arrived before and nobody claimed are routed right away.
+    pub(crate) fn route(
+        &self,
+        bus: &NotificationBus,
+        id: Arc<AtomicU32>,
+        server: Option<String>,
+    ) -> Route {
+        self.router.add_route(Target {
+            about: Follows::Notification {
+                path: bus.path().to_owned(),
+                id,
+                server: Mutex::new(server),
+            },
+            queue: Queue::default(),
+        })
+    }
+
+    /// Routes the owner changes of the bus `name` to the returned [`Route`], see [`watch_owner()`](Self::watch_owner).
+    pub(crate) fn route_owner_changes(&self, name: &str) -> Route {
+        self.router.add_route(Target {
+            about: Follows::Owner(name.to_owned()),
+            queue: Queue::default(),
+        })
+    }
+
+    /// Follows a notification that `server` just showed as `id`, its callbacks included.
+    ///
+    /// Like [`route()`](Self::route), and the callbacks of `notification` are called until it is closed.
+    pub(crate) fn follow(
+        &self,
+        id: &Arc<AtomicU32>,
+        notification: &Notification,
+        server: Option<String>,
+    ) -> Result<Route> {
+        let target = Arc::new(Target {
+            about: Follows::Notification {
+                path: notification.bus.path().to_owned(),
+                id: id.clone(),
+                server: Mutex::new(server.clone()),
+            },
+            queue: Queue::default(),
+        });
+        let shown = id.load(Ordering::SeqCst);
+        self.router
+            .shown(shown, shown, id, notification, server, Some(&target))?;
+        Ok(Route(target))
+    }
+
+    /// Moves everything that follows `id` over to `new_id`, after `server` showed the notification again under it.
+    ///
+    /// Stores `new_id` in `id`.
+    pub(crate) fn renumber(
+        &self,
+        id: &Arc<AtomicU32>,
+        new_id: u32,
+        notification: &Notification,
+        server: Option<String>,
+    ) -> Result<()> {
+        let old_id = id.load(Ordering::SeqCst);
+        self.router
+            .shown(old_id, new_id, id, notification, server, None)
+    }
+}
+
+impl RoutedConnection {
+    /// The spec versions negotiated with the servers on this connection.
+    pub(crate) fn spec_versions(&self) -> &SpecVersions {
+        &self.router.spec_versions
+    }
+
+    /// Closes the notifications shown over this connection that enforce their timeout.
+    pub(crate) fn expiries(&self) -> &Arc<Expiries> {
+        &self.router.expiries
+    }
+}
+
+impl Deref for RoutedConnection {
+    type Target = zbus::Connection;
+
+    fn deref(&self) -> &zbus::Connection {
+        &self.connection
+    }
+}
+
+/// The signals about one notification, or the owner changes of one bus name.
+///
+/// Nothing is routed here anymore once this is dropped.
+pub(crate) struct Route(Arc<Target>);
+
+impl Route {
+    /// The next signal, `None` once the connection is gone.
+    pub(crate) async fn next(&self) -> Option<zbus::Message> {
+        self.0.queue.pop().await
+    }
+}
+
+impl fmt::Debug for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Route").field(&self.0.about).finish()
+    }
+}
+
+struct Target {
+    about: Follows,
+    queue: Queue<zbus::Message>,
+}
+
+#[derive(Debug)]
+enum Follows {
+    Notification {
+        path: String,
+        id: Arc<AtomicU32>,
+        /// Unique name of the server that showed the notification, `None` on peer-to-peer connections.
+        server: Mutex<Option<String>>,
+    },
+    Owner(String),
+}
+
+impl Target {
+    fn follows(&self, followed: &Arc<AtomicU32>) -> bool {
+        matches!(self.about, Follows::Notification { ref id, .. } if Arc::ptr_eq(id, followed))
+    }
+
+    /// The notification was shown again, possibly by a restarted server.
+    fn shown_by(&self, new_server: Option<String>) {
+        if let Follows::Notification { ref server, .. } = self.about {
+            *server.lock().unwrap_or_else(|e| e.into_inner()) = new_server;
+        }
+    }
+
+    fn wants(&self, signal: &Signal) -> bool {
+        match (&self.about, signal) {
+            (Follows::Notification { path, id, server }, signal) => {
+                signal.is_about(path, id.load(Ordering::SeqCst))
+                    && signal.is_from(server.lock().unwrap_or_else(|e| e.into_inner()).as_deref())
+            }
+            (Follows::Owner(name), Signal::OwnerChanged(changed)) => name == changed,
+            (Follows::Owner(_), _) => false,
+        }
+    }
+}
+
+/// What the router needs to know about a signal.
+enum Signal {
+    Notification {
+        path: String,
+        id: u32,
+        sender: Option<String>,
+    },
+    OwnerChanged(String),
+}
+
+impl Signal {
+    /// `None` for signals the router does not care about.
+    fn of(msg: &zbus::Message) -> Option<Signal> {
+        let header = msg.header();
+        if header.message_type() != zbus::message::Type::Signal {
+            return None;
+        }
+        let member = header.member()?.as_str();
+        match header.interface()?.as_str() {
+            interface if interface == NOTIFICATION_INTERFACE => {
+                let id = match member {
+                    "ActionInvoked" | "ActivationToken" => {
+                        msg.body().deserialize::<(u32, String)>().ok()?.0
+                    }
+                    "NotificationClosed" => msg.body().deserialize::<(u32, u32)>().ok()?.0,
+                    _ => return None,
+                };
+                Some(Signal::Notification {
+                    path: header.path()?.to_string(),
+                    id,
+                    sender: header.sender().map(ToString::to_string),
+                })
+            }
+            "org.freedesktop.DBus" if member == "NameOwnerChanged" => {
+                let (name, _, _) = msg.body().deserialize::<(String, String, String)>().ok()?;
+                Some(Signal::OwnerChanged(name))
+            }
+            _ => None,
+        }
+    }
+
+    fn is_about(&self, notification_path: &str, notification_id: u32) -> bool {
+        matches!(self, Signal::Notification { path, id, .. } if path == notification_path && *id == notification_id)
+    }
+
+    /// Whether the signal was sent by `server`, anybody will do if it is not known.
+    fn is_from(&self, server: Option<&str>) -> bool {
+        match (self, server) {
+            (Signal::Notification { sender, .. }, Some(server)) => {
+                sender.as_deref().is_none_or(|sender| sender == server)
+            }
+            _ => true,
+        }
+    }
+}
+
+/// A match rule added by the router, each is added once per connection.
+#[derive(PartialEq, Eq)]
+enum Subscription {
+    Responses { name: String, path: String },
+    Owner(String),
+}
+
+struct Router {
+    connection: zbus::Connection,
+    dispatcher: Arc<Dispatcher>,
+    state: Mutex<State>,
+    /// Hands new subscriptions to the reader, closed once the router is dropped.
+    subscriptions: Arc<Queue<zbus::MessageStream>>,
+    /// Feeds the thread that runs the callbacks, started with the first notification that has any.
+    ///
+    /// Callbacks may block on the connection, so they cannot run on its executor like the reader does.
+    /// The thread ends along with the router, once the last handle that could have callbacks is gone.
+    callbacks: Mutex<Option<mpsc::Sender<zbus::Message>>>,
+    spec_versions: SpecVersions,
+    expiries: Arc<Expiries>,
+}
+
+#[derive(Default)]
+struct State {
+    subscribed: Vec<Subscription>,
+    reading: bool,
+    disconnected: bool,
+    routes: Vec<Weak<Target>>,
+    backlog: VecDeque<(Signal, zbus::Message)>,
+}
+
+impl Router {
+    fn new(connection: zbus::Connection) -> Router {
+        Router {
+            connection,
+            dispatcher: Default::default(),
+            state: Default::default(),
+            subscriptions: Default::default(),
+            callbacks: Mutex::new(None),
+            spec_versions: SpecVersions::default(),
+            expiries: Default::default(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn add(
+        self: &Arc<Self>,
+        subscription: Subscription,
+        rule: MatchRule<'static>,
+    ) -> Result<()> {
+        if self.lock().subscribed.contains(&subscription) {
+            return Ok(());
+        }
+        let stream = zbus::MessageStream::for_match_rule(rule, &self.connection, None).await?;
+        let mut state = self.lock();
+        if state.subscribed.contains(&subscription) {
+            // somebody else was quicker, dropping the stream leaves their rule in place
+            return Ok(());
+        }
+        state.subscribed.push(subscription);
+        if !state.reading {
+            state.reading = true;
+            self.connection
+                .executor()
+                .spawn(
+                    read(Arc::downgrade(self), self.subscriptions.clone()),
+                    "notify-rust router",
+                )
+                .detach();
+        }
+        self.subscriptions.push(stream);
+        Ok(())
+    }
+
+    fn add_route(&self, target: Target) -> Route {
+        let target = Arc::new(target);
+        let mut state = self.lock();
+        state
+            .backlog
+            .retain(|(signal, msg)| !(target.wants(signal) && target.queue.push(msg.clone())));
+        if state.disconnected {
+            target.queue.close();
+        }
+        state.routes.retain(|route| route.strong_count() > 0);
+        state.routes.push(Arc::downgrade(&target));
+        Route(target)
+    }
+
+    /// Registers the callbacks of a notification that was shown as `new_id`, and `target` if there is one.
+    ///
+    /// Everything happens under the lock [`deliver()`](Self::deliver) takes,
+    /// so no signal slips through between the routes and the callbacks.
+    fn shown(
+        self: &Arc<Self>,
+        old_id: u32,
+        new_id: u32,
+        id: &Arc<AtomicU32>,
+        notification: &Notification,
+        server: Option<String>,
+        target: Option<&Arc<Target>>,
+    ) -> Result<()> {
+        let callbacks = if notification.action_callbacks.is_empty() {
+            None
+        } else {
+            Some(self.start_callbacks()?)
+        };
+        let mut state = self.lock();
+        id.store(new_id, Ordering::SeqCst);
+        self.dispatcher
+            .reregister(old_id, new_id, notification, server.clone());
+        self.dispatcher.followed_by(new_id, id);
+        if let Some(target) = target {
+            if state.disconnected {
+                target.queue.close();
+            }
+            state.routes.push(Arc::downgrade(target));
+        }
+        state.routes.retain(|route| route.strong_count() > 0);
+        let followers: Vec<_> = state
+            .routes
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|target| target.follows(id))
+            .collect();
+        for target in &followers {
+            target.shown_by(server.clone());
+        }
+        let path = notification.bus.path();
+        state.backlog.retain(|(signal, msg)| {
+            if !signal.is_about(path, new_id)
+                || !signal.is_from(server.as_deref())
+                || (followers.is_empty() && callbacks.is_none())
+            {
+                return true;
+            }
+            for target in &followers {
+                target.queue.push(msg.clone());
+            }
+            if let Some(ref callbacks) = callbacks {
+                let _ = callbacks.send(msg.clone());
+            }
+            false
+        });
+        Ok(())
+    }
+
+    /// Hands `msg` to every route that wants it and to the callbacks, keeps it if nobody does.
+    fn deliver(&self, msg: zbus::Message) {
+        let Some(signal) = Signal::of(&msg) else {
+            return;
+        };
+        let mut state = self.lock();
+        state.routes.retain(|route| route.strong_count() > 0);
+        let mut claimed = false;
+        for target in state.routes.iter().filter_map(Weak::upgrade) {
+            if target.wants(&signal) {
+                claimed |= target.queue.push(msg.clone());
+            }
+        }
+        let Signal::Notification { id, .. } = signal else {
+            return;
+        };
+        if self.dispatcher.contains(id) {
+            let callbacks = self.callbacks.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(ref callbacks) = *callbacks {
+                claimed |= callbacks.send(msg.clone()).is_ok();
+            }
+        }
+        if !claimed {
+            if state.backlog.len() == BACKLOG {
+                state.backlog.pop_front();
+            }
+            state.backlog.push_back((signal, msg));
+        }
+    }
+
+    /// The connection is gone, no route will ever see another signal.
+    fn disconnected(&self) {
+        let mut state = self.lock();
+        state.disconnected = true;
+        for target in state.routes.iter().filter_map(Weak::upgrade) {
+            target.queue.close();
+        }
+    }
+
+    fn start_callbacks(self: &Arc<Self>) -> Result<mpsc::Sender<zbus::Message>> {
+        let mut callbacks = self.callbacks.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(ref sender) = *callbacks {
+            return Ok(sender.clone());
+        }
+        let (sender, receiver) = mpsc::channel::<zbus::Message>();
+        let dispatcher = self.dispatcher.clone();
+        thread::Builder::new()
+            .name("notify-rust callbacks".into())
+            .spawn(move || {
+                // the callbacks may use the blocking API
+                super::blocking::mark_off_executor();
+                // ends once the router is dropped, with it the sender
+                for msg in receiver {
+                    dispatch(&dispatcher, &msg);
+                }
+            })
+            .map_err(|e| ErrorKind::Msg(e.to_string()))?;
+        *callbacks = Some(sender.clone());
+        Ok(sender)
+    }
+}
+
+impl Drop for Router {
+    fn drop(&mut self) {
+        // the reader drops the streams, which removes their match rules
+        self.subscriptions.close();
+    }
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("Router")
+            .field("subscriptions", &state.subscribed.len())
+            .field("routes", &state.routes.len())
+            .field("dispatcher", &self.dispatcher)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Drains the subscriptions of `router` until it is dropped or the connection is gone.
+///
+/// Runs on the executor of the connection, which stalls once a subscription nobody reads is full.
+async fn read(router: Weak<Router>, subscriptions: Arc<Queue<zbus::MessageStream>>) {
+    let mut streams: Vec<zbus::MessageStream> = Vec::new();
+    loop {
+        let next = poll_fn(|cx| {
+            loop {
+                match subscriptions.poll_pop(cx) {
+                    Poll::Ready(Some(stream)) => streams.push(stream),
+                    Poll::Ready(None) => return Poll::Ready(None),
+                    Poll::Pending => break,
+                }
+            }
+            for stream in &mut streams {
+                if let Poll::Ready(item) = Pin::new(stream).poll_next(cx) {
+                    return Poll::Ready(Some(item));
+                }
+            }
+            Poll::Pending
+        })
+        .await;
+        let Some(item) = next else {
+            return;
+        };
+        let Some(router) = router.upgrade() else {
+            return;
+        };
+        match item {
+            Some(Ok(msg)) => router.deliver(msg),
+            Some(Err(error)) => log::debug!("failed to receive a signal: {error}"),
+            None => {
+                router.disconnected();
+                return;
+            }
+        }
+    }
+}
+
+fn dispatch(dispatcher: &Dispatcher, msg: &zbus::Message) {
+    let header = msg.header();
+    let path = header.path().map_or("", |p| p.as_str());
+    let sender = header.sender().map(|s| s.as_str());
+    match header.member().map(|m| m.as_str()) {
+        Some("ActionInvoked") => {
+            if let Ok((id, action)) = msg.body().deserialize::<(u32, String)>() {
+                dispatcher.action_invoked(path, sender, id, &action);
+            }
+        }
+        Some("ActivationToken") => {
+            if let Ok((id, token)) = msg.body().deserialize::<(u32, String)>() {
+                dispatcher.activation_token(path, sender, id, &token);
+            }
+        }
+        Some("NotificationClosed") => {
+            if let Ok((id, reason)) = msg.body().deserialize::<(u32, u32)>() {
+                dispatcher.closed(path, sender, id, reason.into());
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn closed(id: u32, sender: Option<&str>) -> Signal {
+        Signal::Notification {
+            path: "/org/freedesktop/Notifications".into(),
+            id,
+            sender: sender.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn wants_signals_of_the_server_that_showed_it() {
+        let target = Target {
+            about: Follows::Notification {
+                path: "/org/freedesktop/Notifications".into(),
+                id: Arc::new(AtomicU32::new(7)),
+                server: Mutex::new(Some(":1.1".into())),
+            },
+            queue: Queue::default(),
+        };
+        assert!(target.wants(&closed(7, Some(":1.1"))));
+        assert!(target.wants(&closed(7, None)));
+        assert!(!target.wants(&closed(7, Some(":1.2"))));
+        assert!(!target.wants(&closed(8, Some(":1.1"))));
+
+        target.shown_by(Some(":1.2".into()));
+        assert!(target.wants(&closed(7, Some(":1.2"))));
+        assert!(!target.wants(&closed(7, Some(":1.1"))));
+
+        target.shown_by(None);
+        assert!(target.wants(&closed(7, Some(":1.3"))));
+    }
+}
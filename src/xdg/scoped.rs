@@ -0,0 +1,100 @@
+//! Notifications that only stay on screen as long as they are needed.
+
+use std::ops::{Deref, DerefMut};
+
+use super::NotificationHandle;
+
+/// A shown notification that is closed when this guard is dropped.
+///
+/// Returned by [`Notification::show_scoped()`](crate::Notification::show_scoped), [`Notification::show_scoped_async()`](crate::Notification::show_scoped_async)
+/// and [`NotificationHandle::close_on_drop()`].
+/// The guard is dropped when it goes out of scope, on early returns with `?` and while unwinding from a panic,
+/// so progress notifications do not outlive the work they report on.
+/// It dereferences to the [`NotificationHandle`], which can still be updated and waited on.
+///
+/// ```no_run
+/// # use notify_rust::*;
+/// # fn sync_files() -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let _syncing = Notification::new().summary("Syncing...").show_scoped()?;
+/// sync_files()?;
+/// // closed here, or as soon as `sync_files()` fails
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ScopedNotification {
+    handle: Option<NotificationHandle>,
+    /// Shown from async code, where closing must not block the executor.
+    #[cfg_attr(
+        not(any(feature = "zbus", all(feature = "dbus", feature = "async"))),
+        allow(dead_code)
+    )]
+    spawn_close: bool,
+}
+
+impl ScopedNotification {
+    fn new(handle: NotificationHandle, spawn_close: bool) -> ScopedNotification {
+        ScopedNotification {
+            handle: Some(handle),
+            spawn_close,
+        }
+    }
+
+    /// Leaves the notification on screen and returns its handle.
+    pub fn keep(mut self) -> NotificationHandle {
+        self.handle.take().expect("only taken once")
+    }
+}
+
+impl Deref for ScopedNotification {
+    type Target = NotificationHandle;
+
+    fn deref(&self) -> &NotificationHandle {
+        self.handle.as_ref().expect("only taken on drop")
+    }
+}
+
+impl DerefMut for ScopedNotification {
+    fn deref_mut(&mut self) -> &mut NotificationHandle {
+        self.handle.as_mut().expect("only taken on drop")
+    }
+}
+
+/// Closes the notification.
+///
+/// Guards from [`Notification::show_scoped_async()`](crate::Notification::show_scoped_async) hand the close to the executor of their connection,
+/// all others block until the server answered.
+impl Drop for ScopedNotification {
+    fn drop(&mut self) {
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        match handle.inner {
+            #[cfg(feature = "zbus")]
+            super::NotificationHandleInner::Zbus(ref inner) if self.spawn_close => {
+                inner.close_in_background();
+            }
+            #[cfg(all(feature = "dbus", any(feature = "zbus", feature = "async")))]
+            super::NotificationHandleInner::DbusAsync(ref inner) if self.spawn_close => {
+                inner.close_in_background();
+            }
+            _ => handle.close(),
+        }
+    }
+}
+
+impl NotificationHandle {
+    /// Closes the notification once the returned guard is dropped, see [`ScopedNotification`].
+    ///
+    /// Closing blocks the dropping thread until the server answered.
+    pub fn close_on_drop(self) -> ScopedNotification {
+        ScopedNotification::new(self, false)
+    }
+
+    /// Like [`close_on_drop()`](Self::close_on_drop), but closes on the executor of the connection without blocking.
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub(crate) fn close_on_drop_in_background(self) -> ScopedNotification {
+        ScopedNotification::new(self, true)
+    }
+}
@@ -0,0 +1,466 @@
+//! Sends notifications from a background thread, so the caller never waits on D-Bus.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{NotificationHandle, Notifier, NotifierBuilder};
+use crate::{
+    error::*,
+    notification::Notification,
+    response::{CloseReason, NotificationResponse},
+    timeout::Timeout,
+};
+
+/// How long past its timeout a notification is kept for the server to report it closed.
+///
+/// Some servers never do, so it is forgotten after that.
+const EXPIRY_GRACE: Duration = Duration::from_secs(1);
+
+/// Refers to a notification sent through a [`NotificationService`].
+///
+/// Valid right away, before the server assigned an id to the notification.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Ticket(u64);
+
+/// What a [`NotificationService`] reports back, see [`NotificationService::spawn()`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ServiceEvent {
+    /// The notification was shown.
+    Shown {
+        /// Returned by [`NotificationService::send()`].
+        ticket: Ticket,
+        /// The id the server assigned.
+        id: u32,
+    },
+
+    /// The notification was replaced with a new version.
+    Updated {
+        /// Returned by [`NotificationService::send()`].
+        ticket: Ticket,
+        /// The id the server assigned, it may have changed.
+        id: u32,
+    },
+
+    /// The user acted on a notification, or it was closed.
+    ///
+    /// Only reported for notifications passed to [`NotificationService::subscribe()`].
+    Response {
+        /// Returned by [`NotificationService::send()`].
+        ticket: Ticket,
+        /// What happened.
+        response: NotificationResponse,
+    },
+
+    /// Showing, updating or closing the notification failed.
+    Failed {
+        /// Returned by [`NotificationService::send()`].
+        ticket: Ticket,
+        /// Why.
+        error: Error,
+    },
+
+    /// The notification was updated, closed or subscribed to after the service forgot it.
+    ///
+    /// That is once it was closed, if showing it failed, or a little after its [`Timeout::Milliseconds`] passed.
+    Forgotten {
+        /// Returned by [`NotificationService::send()`].
+        ticket: Ticket,
+    },
+}
+
+enum Command {
+    Show(Ticket, Notification),
+    Update(Ticket, Notification),
+    Close(Ticket),
+    Subscribe(Ticket),
+    /// Sent by the dispatcher of the connection.
+    Responded(Ticket, NotificationResponse),
+    /// Sent once the last [`NotificationService`] is gone, the dispatcher may still hold senders.
+    Stop,
+}
+
+/// Shows, updates and closes notifications on a background thread.
+///
+/// The thread owns the connection and gets its commands over a channel,
+/// none of the methods wait for D-Bus or even for the connection to be set up.
+/// Results are reported to the callback passed to [`spawn()`](Self::spawn), on the service thread.
+///
+/// The handle is cheap to clone, the thread stops once the last clone is dropped.
+/// Commands that pile up while the thread is busy are handled as one batch:
+/// the notifications it shows are sent together, see [`Notifier::show_all()`],
+/// and an update that is superseded by a later update or close in the same batch is skipped.
+///
+/// Responses are routed to the service by the connection, it does not poll for them.
+/// A notification is forgotten once it is closed, or a little after its [`Timeout::Milliseconds`] passed,
+/// commands for it are then reported as [`ServiceEvent::Forgotten`].
+///
+/// ```no_run
+/// # use notify_rust::*;
+/// # use std::sync::mpsc;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let (events, received) = mpsc::channel();
+/// let service = NotificationService::spawn(move |event| {
+///     let _ = events.send(event);
+/// })?;
+///
+/// let ticket = service.send(Notification::new().summary("Download started"));
+/// service.subscribe(ticket);
+/// service.update(ticket, Notification::new().summary("Download finished"));
+///
+/// // later, e.g. in the event loop of the GUI
+/// for event in received.try_iter() {
+///     match event {
+///         ServiceEvent::Failed { error, .. } => eprintln!("{error}"),
+///         event => println!("{event:?}"),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct NotificationService {
+    running: Arc<Running>,
+    tickets: Arc<AtomicU64>,
+}
+
+/// Stops the service thread once the last [`NotificationService`] drops it.
+struct Running(mpsc::Sender<Command>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let _ = self.0.send(Command::Stop);
+    }
+}
+
+impl NotificationService {
+    /// Starts the service for the session bus.
+    ///
+    /// `on_event` is called on the service thread, keep it short or hand the event on to a channel.
+    pub fn spawn(
+        on_event: impl FnMut(ServiceEvent) + Send + 'static,
+    ) -> Result<NotificationService> {
+        Self::spawn_with(&Notifier::builder(), on_event)
+    }
+
+    /// Starts the service, connecting like `notifier` does.
+    ///
+    /// The connection is set up on the service thread once the first command arrives,
+    /// if that fails the commands are reported as [`ServiceEvent::Failed`] and the next one tries again.
+    pub fn spawn_with(
+        notifier: &NotifierBuilder,
+        on_event: impl FnMut(ServiceEvent) + Send + 'static,
+    ) -> Result<NotificationService> {
+        let (commands, received) = mpsc::channel();
+        let worker = Worker {
+            builder: notifier.clone(),
+            notifier: None,
+            shown: HashMap::new(),
+            responses: commands.clone(),
+            on_event: Box::new(on_event),
+        };
+        thread::Builder::new()
+            .name("notify-rust service".into())
+            .spawn(move || {
+                // the worker uses the blocking API
+                #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+                super::blocking::mark_off_executor();
+                worker.run(&received);
+            })
+            .map_err(|error| Error::from(error.to_string().as_str()))?;
+        Ok(NotificationService {
+            running: Arc::new(Running(commands)),
+            tickets: Default::default(),
+        })
+    }
+
+    /// Shows the notification, the result is reported as [`ServiceEvent::Shown`] or [`ServiceEvent::Failed`].
+    pub fn send(&self, notification: &Notification) -> Ticket {
+        let ticket = Ticket(self.tickets.fetch_add(1, Ordering::Relaxed));
+        self.command(Command::Show(ticket, notification.clone()));
+        ticket
+    }
+
+    /// Replaces a notification sent earlier with `notification`.
+    pub fn update(&self, ticket: Ticket, notification: &Notification) {
+        self.command(Command::Update(ticket, notification.clone()));
+    }
+
+    /// Closes a notification sent earlier.
+    pub fn close(&self, ticket: Ticket) {
+        self.command(Command::Close(ticket));
+    }
+
+    /// Reports what happens to a notification as [`ServiceEvent::Response`], until it is closed.
+    ///
+    /// Responses that arrived before are reported too, unless the notification was closed in the meantime.
+    pub fn subscribe(&self, ticket: Ticket) {
+        self.command(Command::Subscribe(ticket));
+    }
+
+    fn command(&self, command: Command) {
+        if self.running.0.send(command).is_err() {
+            log::warn!("the notification service thread is gone");
+        }
+    }
+}
+
+impl fmt::Debug for NotificationService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NotificationService")
+            .finish_non_exhaustive()
+    }
+}
+
+/// The state of the service thread.
+struct Worker {
+    builder: NotifierBuilder,
+    notifier: Option<Notifier>,
+    shown: HashMap<Ticket, Shown>,
+    /// Handed to the dispatcher along with every notification, see [`observed()`](Self::observed).
+    responses: mpsc::Sender<Command>,
+    on_event: Box<dyn FnMut(ServiceEvent) + Send>,
+}
+
+impl Worker {
+    fn run(mut self, commands: &mpsc::Receiver<Command>) {
+        loop {
+            let received = match self.next_expiry() {
+                Some(expiry) => {
+                    commands.recv_timeout(expiry.saturating_duration_since(Instant::now()))
+                }
+                None => commands.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(Command::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+                Ok(first) => {
+                    let batch =
+                        coalesce(std::iter::once(first).chain(commands.try_iter()).collect());
+                    if self.handle_batch(batch) {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            self.forget_expired();
+        }
+    }
+
+    /// Handles the commands in order, the shows in a row are sent together.
+    ///
+    /// Returns whether the service was stopped.
+    fn handle_batch(&mut self, batch: Vec<Command>) -> bool {
+        let mut shows = Vec::new();
+        for command in batch {
+            match command {
+                Command::Show(ticket, notification) => shows.push((ticket, notification)),
+                Command::Stop => return true,
+                command => {
+                    self.show_all(std::mem::take(&mut shows));
+                    self.handle(command);
+                }
+            }
+        }
+        self.show_all(shows);
+        false
+    }
+
+    fn show_all(&mut self, shows: Vec<(Ticket, Notification)>) {
+        if shows.is_empty() {
+            return;
+        }
+        let (tickets, notifications): (Vec<Ticket>, Vec<Notification>) = shows
+            .into_iter()
+            .map(|(ticket, notification)| (ticket, self.observed(ticket, notification)))
+            .unzip();
+        let results = match self.notifier() {
+            Ok(notifier) => notifier.show_all(&notifications),
+            Err(error) => {
+                // the others try to connect again, one by one
+                let mut results = vec![Err(error)];
+                for notification in &notifications[1..] {
+                    results.push(
+                        self.notifier()
+                            .and_then(|notifier| notifier.show(notification)),
+                    );
+                }
+                results
+            }
+        };
+        for ((ticket, notification), result) in tickets.into_iter().zip(&notifications).zip(results)
+        {
+            match result {
+                Ok(handle) => {
+                    let id = handle.id();
+                    self.shown.insert(
+                        ticket,
+                        Shown {
+                            handle,
+                            expiry: expiry(notification),
+                            unreported: Some(Vec::new()),
+                        },
+                    );
+                    (self.on_event)(ServiceEvent::Shown { ticket, id });
+                }
+                Err(error) => (self.on_event)(ServiceEvent::Failed { ticket, error }),
+            }
+        }
+    }
+
+    fn handle(&mut self, command: Command) {
+        let ticket = match command {
+            Command::Show(ticket, _)
+            | Command::Update(ticket, _)
+            | Command::Close(ticket)
+            | Command::Subscribe(ticket)
+            | Command::Responded(ticket, _) => ticket,
+            Command::Stop => return,
+        };
+        if let Err(error) = self.try_handle(command) {
+            (self.on_event)(ServiceEvent::Failed { ticket, error });
+        }
+    }
+
+    fn try_handle(&mut self, command: Command) -> Result<()> {
+        match command {
+            Command::Show(ticket, notification) => self.show_all(vec![(ticket, notification)]),
+            Command::Update(ticket, notification) => {
+                let notification = self.observed(ticket, notification);
+                let Some(shown) = self.shown.get_mut(&ticket) else {
+                    (self.on_event)(ServiceEvent::Forgotten { ticket });
+                    return Ok(());
+                };
+                shown.expiry = expiry(&notification);
+                *shown.handle = notification;
+                shown.handle.update()?;
+                let id = shown.handle.id();
+                (self.on_event)(ServiceEvent::Updated { ticket, id });
+            }
+            Command::Close(ticket) => {
+                let Some(shown) = self.shown.remove(&ticket) else {
+                    (self.on_event)(ServiceEvent::Forgotten { ticket });
+                    return Ok(());
+                };
+                // on the bus it was shown on
+                shown.handle.close_fallible()?;
+                if shown.unreported.is_none() {
+                    (self.on_event)(ServiceEvent::Response {
+                        ticket,
+                        response: NotificationResponse::Closed(CloseReason::CloseAction),
+                    });
+                }
+            }
+            Command::Subscribe(ticket) => {
+                let Some(shown) = self.shown.get_mut(&ticket) else {
+                    (self.on_event)(ServiceEvent::Forgotten { ticket });
+                    return Ok(());
+                };
+                for response in shown.unreported.take().unwrap_or_default() {
+                    (self.on_event)(ServiceEvent::Response { ticket, response });
+                }
+            }
+            Command::Responded(ticket, response) => self.responded(ticket, response),
+            Command::Stop => {}
+        }
+        Ok(())
+    }
+
+    fn notifier(&mut self) -> Result<&Notifier> {
+        Ok(match self.notifier {
+            Some(ref notifier) => notifier,
+            None => self.notifier.insert(self.builder.connect()?),
+        })
+    }
+
+    /// Has the dispatcher of the connection send the responses to `notification` back to the service thread.
+    fn observed(&self, ticket: Ticket, mut notification: Notification) -> Notification {
+        let responses = self.responses.clone();
+        notification
+            .action_callbacks
+            .observe(Arc::new(move |response| {
+                // the service may be gone already
+                let _ = responses.send(Command::Responded(ticket, response));
+            }));
+        notification
+    }
+
+    /// Reports a response to a subscribed notification, or keeps it until it is subscribed to.
+    ///
+    /// Forgets the notification once it was closed, whether subscribed or not.
+    fn responded(&mut self, ticket: Ticket, response: NotificationResponse) {
+        let Some(shown) = self.shown.get_mut(&ticket) else {
+            // closed by the service, which reported it already
+            return;
+        };
+        let done = matches!(response, NotificationResponse::Closed(_));
+        match shown.unreported {
+            Some(ref mut unreported) => unreported.push(response),
+            None => (self.on_event)(ServiceEvent::Response { ticket, response }),
+        }
+        if done {
+            self.shown.remove(&ticket);
+        }
+    }
+
+    /// When the next notification is forgotten that the server did not report closed.
+    fn next_expiry(&self) -> Option<Instant> {
+        self.shown.values().filter_map(|shown| shown.expiry).min()
+    }
+
+    fn forget_expired(&mut self) {
+        let now = Instant::now();
+        self.shown
+            .retain(|_, shown| shown.expiry.is_none_or(|expiry| expiry > now));
+    }
+}
+
+/// When a notification shown now is forgotten, `None` unless its timeout is known.
+fn expiry(notification: &Notification) -> Option<Instant> {
+    match notification.timeout {
+        Timeout::Milliseconds(ms) => {
+            Some(Instant::now() + Duration::from_millis(ms.into()) + EXPIRY_GRACE)
+        }
+        Timeout::Default | Timeout::Never => None,
+    }
+}
+
+/// A notification the service showed and that was not closed yet.
+struct Shown {
+    handle: NotificationHandle,
+    /// Forgotten by then, even if the server never reports it closed.
+    expiry: Option<Instant>,
+    /// The responses that arrived before [`NotificationService::subscribe()`], `None` once subscribed.
+    unreported: Option<Vec<NotificationResponse>>,
+}
+
+/// Drops the updates of a batch that a later update or close of the same notification makes pointless.
+fn coalesce(batch: Vec<Command>) -> Vec<Command> {
+    let mut superseded = HashSet::new();
+    let mut kept: Vec<Command> = batch
+        .into_iter()
+        .rev()
+        .filter(|command| match *command {
+            Command::Update(ticket, _) => superseded.insert(ticket),
+            Command::Close(ticket) => {
+                superseded.insert(ticket);
+                true
+            }
+            Command::Show(ticket, _) => {
+                superseded.remove(&ticket);
+                true
+            }
+            Command::Subscribe(_) | Command::Responded(..) | Command::Stop => true,
+        })
+        .collect();
+    kept.reverse();
+    kept
+}
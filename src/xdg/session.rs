@@ -0,0 +1,360 @@
+//! Reaching the graphical sessions of logged in users from a system service.
+
+#[cfg(feature = "zbus")]
+use super::blocking::block_on;
+
+#[cfg(feature = "dbus")]
+use super::dbus_rs;
+#[cfg(feature = "zbus")]
+use super::zbus_rs;
+use super::{BusAddress, NotificationHandle, Notifier};
+use crate::{error::*, notification::Notification};
+
+use std::path::Path;
+
+pub(crate) static LOGIND_BUS: &str = "org.freedesktop.login1";
+pub(crate) static LOGIND_OBJECTPATH: &str = "/org/freedesktop/login1";
+pub(crate) static LOGIND_MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+pub(crate) static LOGIND_SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+
+static RUNTIME_DIR: &str = "/run/user";
+
+/// A session as listed by logind's `ListSessions`, reduced to what we need.
+#[derive(Debug)]
+pub(crate) struct LogindSession {
+    pub id: String,
+    pub uid: u32,
+    pub user: String,
+}
+
+/// Whether a logind session with these properties has a desktop that can show notifications.
+pub(crate) fn is_graphical(active: bool, session_type: &str, class: &str) -> bool {
+    active && class == "user" && matches!(session_type, "x11" | "wayland" | "mir")
+}
+
+/// An active graphical session of a logged in user.
+///
+/// Returned by [`user_sessions()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserSession {
+    /// The logind session id, `None` if the session was found by looking at `/run/user`.
+    pub id: Option<String>,
+    /// The uid of the user owning the session.
+    pub uid: u32,
+    /// The user name, `None` if the session was found by looking at `/run/user`.
+    pub user: Option<String>,
+    /// The session bus of the user.
+    pub address: BusAddress,
+}
+
+impl UserSession {
+    fn from_runtime_dir(
+        runtime_dir: &Path,
+        uid: u32,
+        id: Option<String>,
+        user: Option<String>,
+    ) -> UserSession {
+        let bus = runtime_dir.join(uid.to_string()).join("bus");
+        UserSession {
+            id,
+            uid,
+            user,
+            address: BusAddress::Address(format!("unix:path={}", bus.display())),
+        }
+    }
+
+    /// Connects to the session bus of this session.
+    pub fn notifier(&self) -> Result<Notifier> {
+        Notifier::connect(self.address.clone())
+    }
+
+    /// Sends the notification to this session.
+    pub fn show(&self, notification: &Notification) -> Result<NotificationHandle> {
+        self.notifier()?.show(notification)
+    }
+
+    /// Connects to the session bus of this session asynchronously.
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn notifier_async(&self) -> Result<Notifier> {
+        Notifier::connect_async(self.address.clone()).await
+    }
+
+    /// Sends the notification to this session asynchronously.
+    #[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+    pub async fn show_async(&self, notification: &Notification) -> Result<NotificationHandle> {
+        self.notifier_async().await?.show_async(notification).await
+    }
+}
+
+/// Lists the active graphical sessions of all logged in users.
+///
+/// Sessions are asked from logind on the system bus.
+/// If logind is not available every `/run/user/<uid>/bus` socket is taken as one session,
+/// whether or not there is a desktop behind it.
+///
+/// A session bus only lets in the user it belongs to, not even root.
+/// Sending to a session therefore only works from a process running with the uid of its user,
+/// e.g. a helper the service starts as that user.
+pub fn user_sessions() -> Result<Vec<UserSession>> {
+    sessions_or_runtime_dir(logind_sessions())
+}
+
+/// Async version of [`user_sessions()`].
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+pub async fn user_sessions_async() -> Result<Vec<UserSession>> {
+    sessions_or_runtime_dir(logind_sessions_async().await)
+}
+
+fn sessions_or_runtime_dir(logind: Result<Vec<LogindSession>>) -> Result<Vec<UserSession>> {
+    match logind {
+        Ok(sessions) => Ok(sessions
+            .into_iter()
+            .map(|s| {
+                UserSession::from_runtime_dir(
+                    Path::new(RUNTIME_DIR),
+                    s.uid,
+                    Some(s.id),
+                    Some(s.user),
+                )
+            })
+            .collect()),
+        Err(error) => {
+            log::debug!("logind unavailable ({error}), looking at {RUNTIME_DIR}");
+            runtime_dir_sessions(Path::new(RUNTIME_DIR))
+        }
+    }
+}
+
+/// Sends the notification to every graphical session of the user with the given uid.
+///
+/// Sessions that share a session bus are only notified once.
+/// See [`user_sessions()`] for who may send to a session.
+/// Returns the outcome for each session bus, an empty list if the user has no graphical session.
+///
+/// ```no_run
+/// # use notify_rust::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let warning = Notification::new().summary("Backup failed").finalize();
+/// for (session, result) in show_to_user(&warning, 1000)? {
+///     if let Err(error) = result {
+///         eprintln!("could not warn {:?}: {error}", session.user);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn show_to_user(
+    notification: &Notification,
+    uid: u32,
+) -> Result<Vec<(UserSession, Result<NotificationHandle>)>> {
+    let sessions = user_sessions()?
+        .into_iter()
+        .filter(|session| session.uid == uid);
+    Ok(show_to_sessions(notification, sessions))
+}
+
+/// Async version of [`show_to_user()`].
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+pub async fn show_to_user_async(
+    notification: &Notification,
+    uid: u32,
+) -> Result<Vec<(UserSession, Result<NotificationHandle>)>> {
+    let sessions = user_sessions_async()
+        .await?
+        .into_iter()
+        .filter(|session| session.uid == uid);
+    Ok(show_to_sessions_async(notification, sessions).await)
+}
+
+/// Sends the notification to every active graphical session.
+///
+/// Sessions that share a session bus are only notified once.
+/// Returns the outcome for each session bus.
+pub fn show_to_all_sessions(
+    notification: &Notification,
+) -> Result<Vec<(UserSession, Result<NotificationHandle>)>> {
+    Ok(show_to_sessions(notification, user_sessions()?))
+}
+
+/// Async version of [`show_to_all_sessions()`].
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+pub async fn show_to_all_sessions_async(
+    notification: &Notification,
+) -> Result<Vec<(UserSession, Result<NotificationHandle>)>> {
+    Ok(show_to_sessions_async(notification, user_sessions_async().await?).await)
+}
+
+fn show_to_sessions(
+    notification: &Notification,
+    sessions: impl IntoIterator<Item = UserSession>,
+) -> Vec<(UserSession, Result<NotificationHandle>)> {
+    let mut results: Vec<(UserSession, Result<NotificationHandle>)> = Vec::new();
+    for session in distinct_buses(sessions) {
+        let result = session.show(notification);
+        results.push((session, result));
+    }
+    results
+}
+
+#[cfg(any(feature = "zbus", all(feature = "dbus", feature = "async")))]
+async fn show_to_sessions_async(
+    notification: &Notification,
+    sessions: impl IntoIterator<Item = UserSession>,
+) -> Vec<(UserSession, Result<NotificationHandle>)> {
+    let mut results: Vec<(UserSession, Result<NotificationHandle>)> = Vec::new();
+    for session in distinct_buses(sessions) {
+        let result = session.show_async(notification).await;
+        results.push((session, result));
+    }
+    results
+}
+
+/// Only the first session of every session bus.
+fn distinct_buses(sessions: impl IntoIterator<Item = UserSession>) -> Vec<UserSession> {
+    let mut distinct: Vec<UserSession> = Vec::new();
+    for session in sessions {
+        if !distinct.iter().any(|seen| seen.address == session.address) {
+            distinct.push(session);
+        }
+    }
+    distinct
+}
+
+/// Every `<uid>/bus` socket in `runtime_dir` as a session.
+fn runtime_dir_sessions(runtime_dir: &Path) -> Result<Vec<UserSession>> {
+    let mut sessions = Vec::new();
+    for entry in std::fs::read_dir(runtime_dir)? {
+        let Ok(entry) = entry else { continue };
+        let Some(uid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if entry.path().join("bus").exists() {
+            sessions.push(UserSession::from_runtime_dir(runtime_dir, uid, None, None));
+        }
+    }
+    sessions.sort_by_key(|session| session.uid);
+    Ok(sessions)
+}
+
+#[cfg(all(feature = "zbus", not(feature = "dbus")))]
+fn logind_sessions() -> Result<Vec<LogindSession>> {
+    block_on(zbus_rs::list_graphical_sessions())
+}
+
+#[cfg(all(feature = "dbus", not(feature = "zbus")))]
+fn logind_sessions() -> Result<Vec<LogindSession>> {
+    dbus_rs::list_graphical_sessions()
+}
+
+#[cfg(all(feature = "dbus", feature = "zbus"))]
+fn logind_sessions() -> Result<Vec<LogindSession>> {
+    if super::selected_stack() == super::DbusStack::Dbus {
+        dbus_rs::list_graphical_sessions()
+    } else {
+        block_on(zbus_rs::list_graphical_sessions())
+    }
+}
+
+#[cfg(all(feature = "zbus", not(feature = "dbus")))]
+async fn logind_sessions_async() -> Result<Vec<LogindSession>> {
+    zbus_rs::list_graphical_sessions().await
+}
+
+#[cfg(all(feature = "dbus", not(feature = "zbus"), feature = "async"))]
+async fn logind_sessions_async() -> Result<Vec<LogindSession>> {
+    super::dbus_nonblock::list_graphical_sessions().await
+}
+
+#[cfg(all(feature = "dbus", feature = "zbus"))]
+async fn logind_sessions_async() -> Result<Vec<LogindSession>> {
+    if super::selected_stack() == super::DbusStack::Dbus {
+        super::dbus_nonblock::list_graphical_sessions().await
+    } else {
+        zbus_rs::list_graphical_sessions().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_graphical, runtime_dir_sessions, show_to_sessions, UserSession};
+    use crate::{error::ErrorCategory, xdg::BusAddress, Notification};
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    /// A fresh directory of its own for every test.
+    fn runtime_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("notify-rust-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn session(runtime_dir: &Path, uid: u32, id: &str) -> UserSession {
+        UserSession::from_runtime_dir(
+            runtime_dir,
+            uid,
+            Some(id.into()),
+            Some(format!("user{uid}")),
+        )
+    }
+
+    #[test]
+    fn graphical_sessions() {
+        assert!(is_graphical(true, "wayland", "user"));
+        assert!(is_graphical(true, "x11", "user"));
+        assert!(!is_graphical(false, "wayland", "user"));
+        assert!(!is_graphical(true, "tty", "user"));
+        assert!(!is_graphical(true, "wayland", "greeter"));
+    }
+
+    #[test]
+    fn sessions_from_the_runtime_dir() {
+        let dir = runtime_dir("sessions");
+        for uid in ["1001", "1000", "not-a-uid"] {
+            fs::create_dir_all(dir.join(uid)).unwrap();
+            fs::write(dir.join(uid).join("bus"), "").unwrap();
+        }
+        // logged in once, but the session bus is gone
+        fs::create_dir_all(dir.join("1002")).unwrap();
+
+        let sessions = runtime_dir_sessions(&dir).unwrap();
+        let found: Vec<_> = sessions.iter().map(|s| (s.uid, &s.id, &s.user)).collect();
+        assert_eq!(found, [(1000, &None, &None), (1001, &None, &None)]);
+        assert_eq!(
+            sessions[0].address,
+            BusAddress::Address(format!("unix:path={}/1000/bus", dir.display()))
+        );
+
+        let missing = runtime_dir_sessions(&dir.join("missing")).unwrap_err();
+        assert!(std::error::Error::source(&missing).is_some());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sends_to_each_session_bus_once() {
+        let dir = runtime_dir("show");
+        let sessions = [
+            session(&dir, 1000, "1"),
+            session(&dir, 1001, "2"),
+            // a second login of the same user on the same session bus
+            session(&dir, 1000, "3"),
+        ];
+        let notification = Notification::new().summary("to everybody").finalize();
+
+        let results = show_to_sessions(&notification, sessions);
+        let sent_to: Vec<_> = results.iter().map(|(s, _)| s.id.as_deref()).collect();
+        assert_eq!(sent_to, [Some("1"), Some("2")]);
+        // one unreachable session does not keep the others from being tried
+        for (_, result) in results {
+            let error = result.unwrap_err();
+            assert_eq!(error.category(), ErrorCategory::Connection, "{error}");
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
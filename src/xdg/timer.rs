@@ -0,0 +1,15 @@
+//! A timer that works with any async runtime, or none at all.
+
+use std::{future::Future, time::Duration};
+
+/// Runs `future` to completion unless `timeout` passes first, in which case `None` is returned.
+///
+/// The timer is one of [`async_io`]'s, which are all driven by the same reactor,
+/// so this does not depend on the timer of any particular executor.
+pub(crate) async fn timeout<T>(timeout: Duration, future: impl Future<Output = T>) -> Option<T> {
+    futures_lite::future::or(async { Some(future.await) }, async {
+        async_io::Timer::after(timeout).await;
+        None
+    })
+    .await
+}
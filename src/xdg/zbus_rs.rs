@@ -1,147 +1,413 @@
-use crate::{error::*, notification::Notification, xdg};
+use crate::{error::*, miniver::Version, notification::Notification, xdg};
 use futures_lite::stream::StreamExt;
-use zbus::MatchRule;
 
-use super::bus::NotificationBus;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use super::{
+    bus::NotificationBus,
+    events::ActivationTokenSlot,
+    expiry::{Expiries, ExpiringServer},
+    router::{Route, RoutedConnection},
+    session::{self, LogindSession},
+    BusAddress, ServerRestartPolicy,
+};
 use crate::response::{CloseReason, NotificationResponse, ResponseHandler};
 
-pub mod bus {
-
-    use crate::xdg::NOTIFICATION_DEFAULT_BUS;
-
-    fn skip_first_slash(s: &str) -> &str {
-        if let Some('/') = s.chars().next() {
-            &s[1..]
-        } else {
-            s
-        }
-    }
-
-    use std::path::PathBuf;
-
-    type BusNameType = zbus::names::WellKnownName<'static>;
-
-    #[derive(Clone, Debug)]
-    pub struct NotificationBus(BusNameType);
-
-    impl Default for NotificationBus {
-        #[cfg(feature = "zbus")]
-        fn default() -> Self {
-            Self(zbus::names::WellKnownName::from_static_str(NOTIFICATION_DEFAULT_BUS).unwrap())
-        }
-    }
-
-    impl NotificationBus {
-        fn namespaced_custom(custom_path: &str) -> Option<String> {
-            // abusing path for semantic join
-            skip_first_slash(
-                PathBuf::from("/de/hoodie/Notification")
-                    .join(custom_path)
-                    .to_str()?,
-            )
-            .replace('/', ".")
-            .into()
-        }
-
-        pub fn custom(custom_path: &str) -> Option<Self> {
-            let name =
-                zbus::names::WellKnownName::try_from(Self::namespaced_custom(custom_path)?).ok()?;
-            Some(Self(name))
-        }
-
-        pub fn into_name(self) -> BusNameType {
-            self.0
-        }
-    }
-}
-
 /// A handle to a shown notification.
 ///
 /// This keeps a connection alive to ensure actions work on certain desktops.
 #[derive(Debug)]
 pub struct ZbusNotificationHandle {
-    pub(crate) id: u32,
-    pub(crate) connection: zbus::Connection,
+    /// Shared with the listeners and the controller, which follow the notification when it is shown again.
+    pub(crate) id: Arc<AtomicU32>,
+    pub(crate) connection: RoutedConnection,
     pub(crate) notification: Notification,
+    /// Unique name of the server that showed the notification, `None` on peer-to-peer connections.
+    pub(crate) server: Option<String>,
+    pub(crate) restart_policy: ServerRestartPolicy,
+    /// Routed to since the notification was shown, taken by the first listener.
+    pub(crate) responses: Mutex<Option<Route>>,
+    /// Kept between calls to [`next_response()`](Self::next_response), so nothing gets lost in between.
+    waiting: Mutex<Option<ResponseListener>>,
+    /// Those of the connection, shared with the listeners and the controller.
+    expiries: Arc<Expiries>,
+    /// Filled in by the listeners.
+    pub(crate) activation_token: ActivationTokenSlot,
 }
 
 impl ZbusNotificationHandle {
+    /// Follows the notification that was just shown as `id` over `connection`, its callbacks included.
     pub(crate) fn new(
         id: u32,
-        connection: zbus::Connection,
+        connection: RoutedConnection,
         notification: Notification,
-    ) -> ZbusNotificationHandle {
-        ZbusNotificationHandle {
+        server: Option<String>,
+        restart_policy: ServerRestartPolicy,
+    ) -> Result<ZbusNotificationHandle> {
+        let id = Arc::new(AtomicU32::new(id));
+        let responses = connection.follow(&id, &notification, server.clone())?;
+        let expiries = connection.expiries().clone();
+        expiries.shown(&id, server.as_deref(), &notification, || ExpiringAt {
+            connection: (*connection).clone(),
+            bus: notification.bus.clone(),
+        });
+        Ok(ZbusNotificationHandle {
             id,
             connection,
             notification,
+            server,
+            restart_policy,
+            responses: Mutex::new(Some(responses)),
+            waiting: Mutex::new(None),
+            expiries,
+            activation_token: Default::default(),
+        })
+    }
+
+    pub(crate) fn id(&self) -> u32 {
+        self.id.load(Ordering::SeqCst)
+    }
+
+    pub async fn wait_for_action(&self, invocation_closure: impl ResponseHandler) -> Result<()> {
+        wait_for_response(self.listen(), invocation_closure).await
+    }
+
+    pub(crate) fn listen(&self) -> ResponseListener {
+        let listener = ResponseListener::new(
+            &self.connection,
+            &self.notification.bus,
+            self.id.clone(),
+            self.restart(),
+        )
+        .expiring(self.expiries.clone())
+        .recording_tokens(self.activation_token.clone());
+        match self
+            .responses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+        {
+            Some(responses) => listener.subscribed_with(responses),
+            None => listener,
         }
     }
 
-    pub async fn wait_for_action(&self, invocation_closure: impl ResponseHandler) {
-        wait_for_action_signal(&self.connection, self.id, invocation_closure).await;
+    /// The next action or close, `None` if there was none within `timeout` or the notification is already closed.
+    ///
+    /// With `close_on_timeout` the notification is closed once `timeout` passes and reported as expired.
+    /// Dropping the future before it completes loses no response, the next call picks them up.
+    pub(crate) async fn next_response(
+        &self,
+        timeout: Option<Duration>,
+        close_on_timeout: bool,
+    ) -> Result<Option<NotificationResponse>> {
+        let taken = self
+            .waiting
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let mut parked = Parked {
+            slot: &self.waiting,
+            listener: Some(taken.unwrap_or_else(|| self.listen())),
+        };
+        let Some(listener) = parked.listener.as_mut() else {
+            return Ok(None);
+        };
+        // outside of the timeout, a short one would cancel the subscription over and over
+        listener.ready().await?;
+        let next = listener.next();
+        let Some(timeout) = timeout else {
+            return Ok(next.await);
+        };
+        Ok(match xdg::timer::timeout(timeout, next).await {
+            Some(response) => response,
+            None if close_on_timeout => {
+                self.close().await;
+                listener.close();
+                Some(NotificationResponse::Closed(CloseReason::Expired))
+            }
+            None => None,
+        })
+    }
+
+    fn restart(&self) -> Option<Restart> {
+        self.server.clone().map(|server| Restart {
+            server,
+            notification: self.notification.clone(),
+            policy: self.restart_policy,
+        })
     }
 
     pub async fn close_fallible(&self) -> Result<()> {
-        self.connection
-            .call_method(
-                Some(self.notification.bus.clone().into_name()),
-                xdg::NOTIFICATION_OBJECTPATH,
-                Some(xdg::NOTIFICATION_INTERFACE),
-                "CloseNotification",
-                &(self.id),
-            )
-            .await?;
-        Ok(())
+        self.expiries.cancel(&self.id);
+        close_notification_via_connection_at_bus(
+            self.id(),
+            &self.connection,
+            self.notification.bus.clone(),
+        )
+        .await
     }
 
     pub async fn close(&self) {
         let _ = self.close_fallible().await;
     }
 
+    /// Closes the notification on the executor of the connection, without waiting for it.
+    pub(crate) fn close_in_background(&self) {
+        self.expiries.cancel(&self.id);
+        let connection = self.connection.clone();
+        let bus = self.notification.bus.clone();
+        let id = self.id();
+        self.connection
+            .executor()
+            .spawn(
+                async move {
+                    if let Err(error) =
+                        close_notification_via_connection_at_bus(id, &connection, bus).await
+                    {
+                        log::warn!("failed to close notification {id}: {error}");
+                    }
+                },
+                "close notification",
+            )
+            .detach();
+    }
+
     pub fn on_close<F>(self, closure: F)
     where
         F: FnOnce(CloseReason),
     {
-        zbus::block_on(self.wait_for_action(|action: &NotificationResponse| {
-            if let NotificationResponse::Closed(reason) = action {
-                closure(*reason);
-            }
-        }));
+        let _ = super::blocking::block_on_responding(
+            |recorder| self.wait_for_action(recorder),
+            |action: &NotificationResponse| {
+                if let NotificationResponse::Closed(reason) = action {
+                    closure(*reason);
+                }
+            },
+        );
     }
 
     pub fn update_fallible(&mut self) -> Result<()> {
-        self.id = zbus::block_on(send_notification_via_connection(
+        super::blocking::block_on(self.update_async())
+    }
+
+    pub(crate) async fn update_async(&mut self) -> Result<()> {
+        let old_id = self.id();
+        let id = replaces_id(
+            &self.connection,
+            &self.notification.bus,
+            old_id,
+            self.server.as_deref(),
+            self.restart_policy,
+        )
+        .await?;
+        let (id, server) = send_subscribed(
             &self.notification,
-            self.id,
+            id,
             &self.connection,
-        ))?;
+            &self.notification.bus,
+        )
+        .await?;
+        self.connection
+            .renumber(&self.id, id, &self.notification, server.clone())?;
+        self.server = server;
+        self.expiries
+            .shown(&self.id, self.server.as_deref(), &self.notification, || {
+                ExpiringAt {
+                    connection: (*self.connection).clone(),
+                    bus: self.notification.bus.clone(),
+                }
+            });
         Ok(())
     }
 
     pub fn update(&mut self) -> Result<()> {
         self.update_fallible()
     }
+
+    /// A controller for this notification that shares its connection.
+    pub(crate) fn controller(&self) -> ZbusController {
+        ZbusController {
+            id: self.id.clone(),
+            connection: self.connection.clone(),
+            bus: self.notification.bus.clone(),
+            restart_policy: self.restart_policy,
+            expiries: self.expiries.clone(),
+            state: Mutex::new(Controlled {
+                id: self.id(),
+                server: self.server.clone(),
+                notification: self.notification.clone(),
+            }),
+        }
+    }
 }
 
-async fn send_notification_via_connection(
-    notification: &Notification,
+/// Where [`Expiries`] closes a notification shown through zbus.
+struct ExpiringAt {
+    connection: zbus::Connection,
+    bus: NotificationBus,
+}
+
+impl ExpiringServer for ExpiringAt {
+    fn server_information(&self) -> Result<xdg::ServerInformation> {
+        zbus::block_on(get_server_information_via_connection_at_bus(
+            &self.connection,
+            self.bus.clone(),
+        ))
+    }
+
+    fn close(&self, id: u32) -> Result<()> {
+        zbus::block_on(close_notification_via_connection_at_bus(
+            id,
+            &self.connection,
+            self.bus.clone(),
+        ))
+    }
+}
+
+/// Updates and closes a notification from any thread or task, see [`ZbusNotificationHandle::controller()`].
+#[derive(Debug)]
+pub(crate) struct ZbusController {
+    id: Arc<AtomicU32>,
+    connection: RoutedConnection,
+    bus: NotificationBus,
+    restart_policy: ServerRestartPolicy,
+    expiries: Arc<Expiries>,
+    state: Mutex<Controlled>,
+}
+
+/// What a controller last sent.
+#[derive(Debug)]
+struct Controlled {
     id: u32,
+    server: Option<String>,
+    notification: Notification,
+}
+
+/// The notification a controller is about to show again, see [`ZbusController::change()`].
+pub(crate) struct Changed {
+    notification: Notification,
+    server: Option<String>,
+    last_id: u32,
+}
+
+impl ZbusController {
+    pub(crate) fn id(&self) -> u32 {
+        self.id.load(Ordering::SeqCst)
+    }
+
+    /// Applies `change` to the notification and shows it again.
+    pub(crate) async fn update(&self, change: impl FnOnce(&mut Notification)) -> Result<()> {
+        let changed = self.change(change);
+        self.show_again(changed).await
+    }
+
+    /// Applies `change` to the notification right away, returns what to show.
+    pub(crate) fn change(&self, change: impl FnOnce(&mut Notification)) -> Changed {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        change(&mut state.notification);
+        Changed {
+            notification: state.notification.clone(),
+            server: state.server.clone(),
+            last_id: state.id,
+        }
+    }
+
+    /// Shows the notification again after [`change()`](Self::change).
+    ///
+    /// Not locked while waiting for the server, the guard cannot be held across an `await`.
+    pub(crate) async fn show_again(&self, changed: Changed) -> Result<()> {
+        let Changed {
+            notification,
+            mut server,
+            last_id,
+        } = changed;
+        let current = self.id();
+        if current != last_id {
+            // a listener showed it again after the server restarted
+            server = name_owner(&self.connection, self.bus.name()).await?;
+        }
+        let id = replaces_id(
+            &self.connection,
+            &self.bus,
+            current,
+            server.as_deref(),
+            self.restart_policy,
+        )
+        .await?;
+        let (id, server) = send_notification_via_connection_at_bus(
+            &notification,
+            id,
+            &self.connection,
+            self.bus.clone(),
+        )
+        .await?;
+        self.connection
+            .renumber(&self.id, id, &notification, server.clone())?;
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.id = id;
+        state.server = server;
+        self.expiries
+            .shown(&self.id, state.server.as_deref(), &notification, || {
+                ExpiringAt {
+                    connection: (*self.connection).clone(),
+                    bus: self.bus.clone(),
+                }
+            });
+        Ok(())
+    }
+
+    pub(crate) async fn close(&self) -> Result<()> {
+        self.expiries.cancel(&self.id);
+        close_notification_via_connection_at_bus(self.id(), &self.connection, self.bus.clone())
+            .await
+    }
+}
+
+/// The id to show a notification again with.
+///
+/// That is `id` while `server` still owns the bus name, otherwise it depends on the restart policy:
+/// the old id means nothing to a new server, or worse, something else.
+async fn replaces_id(
     connection: &zbus::Connection,
+    bus: &NotificationBus,
+    id: u32,
+    server: Option<&str>,
+    restart_policy: ServerRestartPolicy,
 ) -> Result<u32> {
-    send_notification_via_connection_at_bus(notification, id, connection, Default::default()).await
+    let Some(server) = server else {
+        return Ok(id);
+    };
+    let owner = name_owner(connection, bus.name()).await?;
+    if owner.as_deref() == Some(server) {
+        return Ok(id);
+    }
+    match restart_policy {
+        ServerRestartPolicy::Vanish => Err(ErrorKind::ServerVanished.into()),
+        ServerRestartPolicy::Reshow => Ok(0),
+    }
 }
 
-async fn send_notification_via_connection_at_bus(
+/// Sends the notification, returns its id and the unique name of the server that showed it.
+pub(crate) async fn send_notification_via_connection_at_bus(
     notification: &Notification,
     id: u32,
-    connection: &zbus::Connection,
+    connection: &RoutedConnection,
     bus: NotificationBus,
-) -> Result<u32> {
-    let reply: u32 = connection
+) -> Result<(u32, Option<String>)> {
+    let spec_version = spec_version(notification, connection, &bus).await;
+    let (name, path) = bus.into_parts();
+    let reply = connection
         .call_method(
-            Some(bus.into_name()),
-            xdg::NOTIFICATION_OBJECTPATH,
+            Some(name),
+            path,
             Some(xdg::NOTIFICATION_INTERFACE),
             "Notify",
             &(
@@ -151,14 +417,88 @@ async fn send_notification_via_connection_at_bus(
                 &notification.summary,
                 &notification.body,
                 &notification.actions,
-                crate::hints::hints_to_map(notification),
+                crate::hints::hints_to_map(notification, spec_version),
                 i32::from(notification.timeout),
             ),
         )
-        .await?
-        .body()
-        .deserialize()?;
-    Ok(reply)
+        .await?;
+    let id: u32 = reply.body().deserialize()?;
+    let server = reply.header().sender().map(ToString::to_string);
+    Ok((id, server))
+}
+
+/// The spec version of the server at `bus`, which decides the name of the image data hint.
+///
+/// Only asked for when `notification` carries image data.
+async fn spec_version(
+    notification: &Notification,
+    connection: &RoutedConnection,
+    bus: &NotificationBus,
+) -> Version {
+    if !notification.has_image_data() {
+        return xdg::FALLBACK_SPEC_VERSION;
+    }
+    let ask = get_server_information_via_connection_at_bus(connection, bus.clone());
+    // a peer is the server, on a bus the name may have changed hands since
+    let server = match name_owner(connection, bus.name()).await {
+        Ok(Some(owner)) => owner,
+        Ok(None) if !connection.is_bus() => String::new(),
+        Ok(None) => return xdg::negotiated_spec_version(ask.await),
+        Err(error) => return xdg::negotiated_spec_version(Err(error)),
+    };
+    let versions = connection.spec_versions();
+    match versions.get(&server) {
+        Some(version) => version,
+        None => versions.negotiate(server, ask.await),
+    }
+}
+
+/// Subscribes to the responses of the server, then sends the notification.
+///
+/// The subscription is made once per connection and bus, signals the server sends right away,
+/// e.g. because of a tiny timeout, are kept by the router of the connection until the notification is followed.
+pub(crate) async fn send_subscribed(
+    notification: &Notification,
+    id: u32,
+    connection: &RoutedConnection,
+    bus: &NotificationBus,
+) -> Result<(u32, Option<String>)> {
+    connection.subscribe(bus).await?;
+    send_notification_via_connection_at_bus(notification, id, connection, bus.clone()).await
+}
+
+/// Returns the unique name of the current owner of `name`, if any.
+async fn name_owner(connection: &zbus::Connection, name: &str) -> Result<Option<String>> {
+    if !connection.is_bus() {
+        return Ok(None);
+    }
+    let proxy = zbus::fdo::DBusProxy::new(connection).await?;
+    let name = zbus::names::BusName::try_from(name).map_err(zbus::Error::from)?;
+    match proxy.get_name_owner(name).await {
+        Ok(owner) => Ok(Some(owner.to_string())),
+        Err(zbus::fdo::Error::NameHasNoOwner(_)) => Ok(None),
+        Err(error) => Err(zbus::Error::from(error).into()),
+    }
+}
+
+/// Connects to `address`, every method call on the connection fails after `call_timeout`.
+pub(crate) async fn connect(
+    address: &BusAddress,
+    call_timeout: Option<Duration>,
+) -> Result<zbus::Connection> {
+    let builder = match address {
+        BusAddress::Session => zbus::connection::Builder::session()?,
+        BusAddress::System => zbus::connection::Builder::system()?,
+        BusAddress::Address(address) => zbus::connection::Builder::address(address.as_str())?,
+        BusAddress::PeerToPeer(address) => {
+            zbus::connection::Builder::address(address.as_str())?.p2p()
+        }
+    };
+    let builder = match call_timeout {
+        Some(timeout) => builder.method_timeout(timeout),
+        None => builder,
+    };
+    Ok(builder.build().await?)
 }
 
 pub async fn connect_and_send_notification(
@@ -172,24 +512,55 @@ pub(crate) async fn connect_and_send_notification_at_bus(
     notification: &Notification,
     bus: NotificationBus,
 ) -> Result<ZbusNotificationHandle> {
-    let connection = zbus::Connection::session().await?;
-    let inner_id = notification.id.unwrap_or(0);
-    let id =
-        send_notification_via_connection_at_bus(notification, inner_id, &connection, bus).await?;
-
-    Ok(ZbusNotificationHandle::new(
+    let connection = RoutedConnection::new(zbus::Connection::session().await?);
+    let (id, server) = send_subscribed(
+        notification,
+        notification.id.unwrap_or(0),
+        &connection,
+        &bus,
+    )
+    .await?;
+    ZbusNotificationHandle::new(
         id,
         connection,
         notification.clone(),
-    ))
+        server,
+        Default::default(),
+    )
+}
+
+pub(crate) async fn close_notification_via_connection_at_bus(
+    id: u32,
+    connection: &zbus::Connection,
+    bus: NotificationBus,
+) -> Result<()> {
+    let (name, path) = bus.into_parts();
+    connection
+        .call_method(
+            Some(name),
+            path,
+            Some(xdg::NOTIFICATION_INTERFACE),
+            "CloseNotification",
+            &(id),
+        )
+        .await?;
+    Ok(())
 }
 
 pub async fn get_capabilities_at_bus(bus: NotificationBus) -> Result<Vec<String>> {
     let connection = zbus::Connection::session().await?;
+    get_capabilities_via_connection_at_bus(&connection, bus).await
+}
+
+pub(crate) async fn get_capabilities_via_connection_at_bus(
+    connection: &zbus::Connection,
+    bus: NotificationBus,
+) -> Result<Vec<String>> {
+    let (name, path) = bus.into_parts();
     let info: Vec<String> = connection
         .call_method(
-            Some(bus.into_name()),
-            xdg::NOTIFICATION_OBJECTPATH,
+            Some(name),
+            path,
             Some(xdg::NOTIFICATION_INTERFACE),
             "GetCapabilities",
             &(),
@@ -206,10 +577,18 @@ pub async fn get_capabilities() -> Result<Vec<String>> {
 
 pub async fn get_server_information_at_bus(bus: NotificationBus) -> Result<xdg::ServerInformation> {
     let connection = zbus::Connection::session().await?;
+    get_server_information_via_connection_at_bus(&connection, bus).await
+}
+
+pub(crate) async fn get_server_information_via_connection_at_bus(
+    connection: &zbus::Connection,
+    bus: NotificationBus,
+) -> Result<xdg::ServerInformation> {
+    let (name, path) = bus.into_parts();
     let info: xdg::ServerInformation = connection
         .call_method(
-            Some(bus.into_name()),
-            xdg::NOTIFICATION_OBJECTPATH,
+            Some(name),
+            path,
             Some(xdg::NOTIFICATION_INTERFACE),
             "GetServerInformation",
             &(),
@@ -225,66 +604,364 @@ pub async fn get_server_information() -> Result<xdg::ServerInformation> {
     get_server_information_at_bus(Default::default()).await
 }
 
+pub(crate) async fn list_graphical_sessions() -> Result<Vec<LogindSession>> {
+    let connection = zbus::Connection::system().await?;
+    let sessions: Vec<(String, u32, String, String, zbus::zvariant::OwnedObjectPath)> = connection
+        .call_method(
+            Some(session::LOGIND_BUS),
+            session::LOGIND_OBJECTPATH,
+            Some(session::LOGIND_MANAGER_INTERFACE),
+            "ListSessions",
+            &(),
+        )
+        .await?
+        .body()
+        .deserialize()?;
+
+    let mut graphical = Vec::new();
+    for (id, uid, user, _seat, path) in sessions {
+        let properties: HashMap<String, zbus::zvariant::OwnedValue> = connection
+            .call_method(
+                Some(session::LOGIND_BUS),
+                path.as_str(),
+                Some("org.freedesktop.DBus.Properties"),
+                "GetAll",
+                &(session::LOGIND_SESSION_INTERFACE),
+            )
+            .await?
+            .body()
+            .deserialize()?;
+
+        let active = properties
+            .get("Active")
+            .and_then(|v| bool::try_from(v).ok())
+            .unwrap_or(false);
+        let string = |key: &str| {
+            properties
+                .get(key)
+                .and_then(|v| <&str>::try_from(v).ok())
+                .unwrap_or_default()
+        };
+        if session::is_graphical(active, string("Type"), string("Class")) {
+            graphical.push(LogindSession { id, uid, user });
+        }
+    }
+    Ok(graphical)
+}
+
+/// Waits until `name` has an owner on the bus, returns `false` if that did not happen within `timeout`.
+///
+/// Peer-to-peer connections have no names, the peer is taken to be there.
+pub(crate) async fn wait_for_name_owner(
+    connection: &zbus::Connection,
+    name: &str,
+    timeout: Duration,
+) -> Result<bool> {
+    if !connection.is_bus() {
+        return Ok(true);
+    }
+    let proxy = zbus::fdo::DBusProxy::new(connection).await?;
+
+    // subscribe before asking, so an owner showing up in between is not missed
+    let mut owner_changes = proxy
+        .receive_name_owner_changed_with_args(&[(0, name)])
+        .await?;
+    let bus_name = zbus::names::BusName::try_from(name).map_err(zbus::Error::from)?;
+    if proxy
+        .name_has_owner(bus_name)
+        .await
+        .map_err(zbus::Error::from)?
+    {
+        return Ok(true);
+    }
+
+    let appeared = xdg::timer::timeout(timeout, async {
+        while let Some(signal) = owner_changes.next().await {
+            if matches!(signal.args(), Ok(args) if args.new_owner().is_some()) {
+                return true;
+            }
+        }
+        false
+    })
+    .await;
+    Ok(appeared.unwrap_or(false))
+}
+
+/// Whether somebody owns `name` right now, without waiting.
+pub(crate) async fn has_name_owner(connection: &zbus::Connection, name: &str) -> Result<bool> {
+    // peer-to-peer connections talk to their server directly
+    Ok(!connection.is_bus() || name_owner(connection, name).await?.is_some())
+}
+
 /// Listens for the `ActionInvoked(UInt32, String)` Signal.
 ///
 /// No need to use this, check out `Notification::show_and_wait_for_action(FnOnce(action:&str))`
-pub async fn handle_action(id: u32, func: impl ResponseHandler) {
-    let connection = zbus::Connection::session().await.unwrap();
-    wait_for_action_signal(&connection, id, func).await;
+pub async fn handle_action(id: u32, func: impl ResponseHandler) -> Result<()> {
+    let connection = RoutedConnection::new(zbus::Connection::session().await?);
+    wait_for_action_signal(&connection, &Default::default(), id, func).await
 }
 
-async fn wait_for_action_signal(
-    connection: &zbus::Connection,
+// Listens for the `ActionInvoked(UInt32, String)` signal.
+pub(crate) async fn wait_for_action_signal(
+    connection: &RoutedConnection,
+    bus: &NotificationBus,
     id: u32,
     handler: impl ResponseHandler,
-) {
-    let action_signal_rule = MatchRule::builder()
-        .msg_type(zbus::message::Type::Signal)
-        .interface(xdg::NOTIFICATION_INTERFACE)
-        .unwrap()
-        .member("ActionInvoked")
-        .unwrap()
-        .build();
-
-    let proxy = zbus::fdo::DBusProxy::new(connection).await.unwrap();
-    proxy.add_match_rule(action_signal_rule).await.unwrap();
-
-    let close_signal_rule = MatchRule::builder()
-        .msg_type(zbus::message::Type::Signal)
-        .interface(xdg::NOTIFICATION_INTERFACE)
-        .unwrap()
-        .member("NotificationClosed")
-        .unwrap()
-        .build();
-    proxy.add_match_rule(close_signal_rule).await.unwrap();
-
-    while let Ok(Some(msg)) = zbus::MessageStream::from(connection).try_next().await {
-        let header = msg.header();
-        if let zbus::message::Type::Signal = header.message_type() {
+) -> Result<()> {
+    let id = Arc::new(AtomicU32::new(id));
+    let mut listener = ResponseListener::new(connection, bus, id, None);
+    listener.subscribe().await?;
+    wait_for_response(listener, handler).await
+}
+
+/// Puts a listener back into its slot when dropped, even if that happens half way through an `await`.
+struct Parked<'a> {
+    slot: &'a Mutex<Option<ResponseListener>>,
+    listener: Option<ResponseListener>,
+}
+
+impl Drop for Parked<'_> {
+    fn drop(&mut self) {
+        *self.slot.lock().unwrap_or_else(|e| e.into_inner()) = self.listener.take();
+    }
+}
+
+/// The server that showed a notification, and what to do once it is gone.
+#[derive(Debug)]
+pub(crate) struct Restart {
+    pub server: String,
+    pub notification: Notification,
+    pub policy: ServerRestartPolicy,
+}
+
+/// Calls `handler` with the first action or close of the notification `listener` follows.
+async fn wait_for_response(
+    mut listener: ResponseListener,
+    handler: impl ResponseHandler,
+) -> Result<()> {
+    if let Some(response) = listener.next().await {
+        handler.call(&response);
+        return Ok(());
+    }
+    if listener.vanished() {
+        return Err(ErrorKind::ServerVanished.into());
+    }
+    Ok(())
+}
+
+/// Follows the signals of one notification until it is closed.
+///
+/// Only signals from the server that showed the notification and from the object path of its bus count.
+/// They are routed here by the router of the connection, see [`RoutedConnection::route()`].
+///
+/// With a [`Restart`] it also watches the server that showed the notification and applies its policy
+/// when the server goes away.
+#[derive(Debug)]
+pub(crate) struct ResponseListener {
+    connection: RoutedConnection,
+    bus: NotificationBus,
+    /// Shared with the handle, so it learns about the id the notification got when shown again
+    id: Arc<AtomicU32>,
+    restart: Option<Restart>,
+    /// `None` while the name has no owner
+    server: Option<String>,
+    responses: Option<Route>,
+    owner_changes: Option<Route>,
+    /// Those of the connection the notification was shown over, see [`Expiries`].
+    expiries: Option<Arc<Expiries>>,
+    activation_token: ActivationTokenSlot,
+    closed: bool,
+    /// Set once the server went away and took the notification with it.
+    vanished: bool,
+}
+
+impl ResponseListener {
+    pub(crate) fn new(
+        connection: &RoutedConnection,
+        bus: &NotificationBus,
+        id: Arc<AtomicU32>,
+        restart: Option<Restart>,
+    ) -> ResponseListener {
+        ResponseListener {
+            connection: connection.clone(),
+            bus: bus.clone(),
+            id,
+            server: restart.as_ref().map(|restart| restart.server.clone()),
+            restart,
+            responses: None,
+            owner_changes: None,
+            expiries: None,
+            activation_token: Default::default(),
+            closed: false,
+            vanished: false,
+        }
+    }
+
+    /// Uses the responses routed to the handle since the notification was shown.
+    pub(crate) fn subscribed_with(mut self, responses: Route) -> ResponseListener {
+        self.responses = Some(responses);
+        self
+    }
+
+    /// Reports the close by `expiries` as expired.
+    pub(crate) fn expiring(mut self, expiries: Arc<Expiries>) -> ResponseListener {
+        self.expiries = Some(expiries);
+        self
+    }
+
+    /// Keeps the activation tokens in `slot`, where the handle finds them.
+    pub(crate) fn recording_tokens(mut self, slot: ActivationTokenSlot) -> ResponseListener {
+        self.activation_token = slot;
+        self
+    }
+
+    async fn subscribe(&mut self) -> Result<()> {
+        if let Some(ref restart) = self.restart {
+            if self.owner_changes.is_none() && self.connection.is_bus() {
+                let name = restart.notification.bus.name();
+                self.connection.watch_owner(name).await?;
+                self.owner_changes = Some(self.connection.route_owner_changes(name));
+            }
+        }
+        if self.responses.is_none() {
+            self.connection.subscribe(&self.bus).await?;
+            // the rule names the well-known name, which another bus on the same path may share
+            let server = match self.server {
+                Some(ref server) => Some(server.clone()),
+                None => name_owner(&self.connection, self.bus.name()).await?,
+            };
+            self.responses = Some(self.connection.route(&self.bus, self.id.clone(), server));
+        }
+        Ok(())
+    }
+
+    /// Subscribes to the signals unless that was done already, closes the listener if it fails.
+    pub(crate) async fn ready(&mut self) -> Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.subscribe().await.inspect_err(|_| self.close())
+    }
+
+    /// The next response, `None` once the notification is closed.
+    pub(crate) async fn next(&mut self) -> Option<NotificationResponse> {
+        if let Err(error) = self.ready().await {
+            log::warn!("failed to subscribe to notification signals: {error}");
+        }
+        if self.closed {
+            return None;
+        }
+        let mut response = self.receive().await;
+        if let (Some(NotificationResponse::Closed(ref mut reason)), Some(expiries)) =
+            (&mut response, &self.expiries)
+        {
+            *reason = expiries.closed(&self.id, *reason);
+        }
+        if matches!(response, None | Some(NotificationResponse::Closed(_))) {
+            self.close();
+        }
+        response
+    }
+
+    /// Whether the listener stopped because the server went away.
+    pub(crate) fn vanished(&self) -> bool {
+        self.vanished
+    }
+
+    /// Gives up on the notification, the server that showed it is gone.
+    fn vanish(&mut self) -> Option<NotificationResponse> {
+        self.vanished = true;
+        self.close();
+        None
+    }
+
+    /// Drops the routes, no more responses are reported.
+    pub(crate) fn close(&mut self) {
+        self.closed = true;
+        self.responses = None;
+        self.owner_changes = None;
+    }
+
+    async fn receive(&mut self) -> Option<NotificationResponse> {
+        loop {
+            let responses = self.responses.as_ref()?;
+            let msg = match self.owner_changes {
+                Some(ref owner_changes) => {
+                    futures_lite::future::or(responses.next(), owner_changes.next()).await
+                }
+                None => responses.next().await,
+            };
+            let msg = msg?;
+            let header = msg.header();
+            let from_server = match (&self.server, header.sender()) {
+                (Some(server), Some(sender)) => sender.as_str() == server,
+                (None, _) => self.restart.is_none(),
+                (_, None) => true,
+            };
             match header.member() {
-                Some(name) if name == "ActionInvoked" => {
+                Some(name) if name == "ActionInvoked" && from_server => {
                     match msg.body().deserialize::<(u32, String)>() {
-                        Ok((nid, action)) if nid == id => {
-                            let response = if action == "default" {
+                        Ok((nid, action)) if nid == self.id.load(Ordering::SeqCst) => {
+                            return Some(if action == "default" {
                                 NotificationResponse::Default
                             } else {
                                 NotificationResponse::Action(action)
-                            };
-                            handler.call(&response);
-                            break;
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+                Some(name) if name == "ActivationToken" && from_server => {
+                    match msg.body().deserialize::<(u32, String)>() {
+                        Ok((nid, token)) if nid == self.id.load(Ordering::SeqCst) => {
+                            self.activation_token.set(token);
                         }
                         _ => {}
                     }
                 }
-                Some(name) if name == "NotificationClosed" => {
+                Some(name) if name == "NotificationClosed" && from_server => {
                     match msg.body().deserialize::<(u32, u32)>() {
-                        Ok((nid, reason)) if nid == id => {
-                            handler.call(&NotificationResponse::Closed(reason.into()));
-                            break;
+                        Ok((nid, reason)) if nid == self.id.load(Ordering::SeqCst) => {
+                            return Some(NotificationResponse::Closed(reason.into()));
                         }
                         _ => {}
                     }
                 }
+                Some(name) if name == "NameOwnerChanged" => {
+                    let Some(ref restart) = self.restart else {
+                        continue;
+                    };
+                    let Ok((name, old_owner, new_owner)) =
+                        msg.body().deserialize::<(String, String, String)>()
+                    else {
+                        continue;
+                    };
+                    if name != restart.notification.bus.name() {
+                        continue;
+                    }
+                    if self.server.as_deref() == Some(old_owner.as_str()) {
+                        if restart.policy == ServerRestartPolicy::Vanish {
+                            return self.vanish();
+                        }
+                        self.server = None;
+                    }
+                    if self.server.is_none() && !new_owner.is_empty() {
+                        let Ok((new_id, new_server)) =
+                            send_subscribed(&restart.notification, 0, &self.connection, &self.bus)
+                                .await
+                        else {
+                            return self.vanish();
+                        };
+                        if self
+                            .connection
+                            .renumber(&self.id, new_id, &restart.notification, new_server.clone())
+                            .is_err()
+                        {
+                            return self.vanish();
+                        }
+                        self.server = new_server.or(Some(new_owner));
+                    }
+                }
                 _ => {}
             }
         }
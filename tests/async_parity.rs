@@ -0,0 +1,84 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::*;
+use std::{
+    sync::{atomic::Ordering, mpsc},
+    thread,
+    time::Duration,
+};
+
+mod common;
+use common::{emitter, start_server_on_this_thread, FakeServer};
+
+// everything runs on one thread together with the server, any blocking call would stall it
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn async_parity() {
+    let (done, watchdog) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        if watchdog.recv_timeout(Duration::from_secs(20)).is_err() {
+            eprintln!("an async method blocked the executor");
+            std::process::exit(1);
+        }
+    });
+
+    futures_lite::future::block_on(async {
+        let (server, shown) = start_server_on_this_thread().await;
+        let serving = async {
+            loop {
+                server.executor().tick().await;
+            }
+        };
+        futures_lite::future::or(serving, async {
+            assert_eq!(get_capabilities_async().await.unwrap(), ["actions", "body"]);
+            assert_eq!(
+                get_capabilities_at_bus_async(NotificationBus::default())
+                    .await
+                    .unwrap(),
+                ["actions", "body"]
+            );
+            assert_eq!(get_server_information_async().await.unwrap().name, "fake");
+            assert_eq!(
+                get_server_information_at_bus_async(NotificationBus::default())
+                    .await
+                    .unwrap()
+                    .spec_version,
+                "1.2"
+            );
+
+            let notifier = Notifier::new_async().await.unwrap();
+            assert_eq!(
+                notifier.capabilities_async().await.unwrap(),
+                ["actions", "body"]
+            );
+            assert_eq!(
+                notifier.server_information_async().await.unwrap().vendor,
+                "notify-rust"
+            );
+            let handle = notifier
+                .show_async(Notification::new().summary("closed by id"))
+                .await
+                .unwrap();
+            notifier.close_async(handle.id()).await.unwrap();
+
+            let mut handle = Notification::new()
+                .summary("before")
+                .show_async()
+                .await
+                .unwrap();
+            handle.summary("after");
+            handle.update_async().await.unwrap();
+            assert_eq!(handle.id(), shown.load(Ordering::SeqCst));
+            FakeServer::notification_closed(&emitter(&server), handle.id(), 2)
+                .await
+                .unwrap();
+            let (reason, closed) = mpsc::channel();
+            handle
+                .on_close_async(move |r: CloseReason| reason.send(r).unwrap())
+                .await;
+            assert_eq!(closed.try_recv(), Ok(CloseReason::Dismissed));
+        })
+        .await;
+    });
+    done.send(()).unwrap();
+}
@@ -0,0 +1,44 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "tokio"))]
+
+use notify_rust::*;
+use std::{cell::RefCell, rc::Rc, sync::atomic::Ordering};
+
+mod common;
+use common::{emitter, start_server_on_this_thread, FakeServer};
+
+// one test, since every part needs to own the notification server name
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn blocking_api_inside_tokio() {
+    // the server gets a runtime of its own, which keeps serving while the test's runtime is blocked
+    let serving = tokio::runtime::Runtime::new().unwrap();
+    let (server, shown) = serving.block_on(start_server_on_this_thread());
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        assert_eq!(blocking_mode(), BlockingMode::Auto);
+        let handle = Notification::new()
+            .summary("from a task")
+            .action("ok", "Ok")
+            .show()
+            .unwrap();
+        let id = shown.load(Ordering::SeqCst);
+        assert_eq!(handle.id(), id);
+        assert_eq!(get_capabilities().unwrap(), ["actions", "body"]);
+
+        // handlers need not be `Send`, they are called on this thread
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| serving.block_on(FakeServer::action_invoked(&emitter(&server), id, "ok")))
+                .join()
+                .unwrap()
+                .unwrap();
+        });
+        let invoked = Rc::new(RefCell::new(String::new()));
+        let recorded = invoked.clone();
+        handle.wait_for_action(move |action| recorded.borrow_mut().push_str(action));
+        assert_eq!(*invoked.borrow(), "ok");
+    });
+}
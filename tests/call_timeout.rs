@@ -0,0 +1,43 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::*;
+use std::{os::unix::net::UnixListener, time::Duration};
+
+/// Accepts one peer-to-peer connection and never answers any method call on it.
+fn silent_peer(name: &str) -> BusAddress {
+    let socket = std::env::temp_dir().join(format!("notify-rust-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket).unwrap();
+
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let _connection = zbus::block_on(
+            zbus::connection::Builder::async_io_unix_stream(stream)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .build(),
+        )
+        .unwrap();
+        std::thread::sleep(Duration::from_secs(5));
+    });
+
+    BusAddress::PeerToPeer(format!("unix:path={}", socket.display()))
+}
+
+#[test]
+fn call_timeout() {
+    let notifier = Notifier::builder()
+        .address(silent_peer("call_timeout"))
+        .call_timeout(Duration::from_millis(100))
+        .connect()
+        .unwrap();
+
+    let error = notifier.capabilities().unwrap_err();
+    assert!(error.is_call_timeout());
+    assert_eq!(error.category(), error::ErrorCategory::Timeout);
+    assert!(notifier
+        .show(Notification::new().summary("nobody home"))
+        .unwrap_err()
+        .is_call_timeout());
+}
@@ -0,0 +1,231 @@
+//! A stand-in notification server for tests that need to control what the server does.
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use zbus::{object_server::SignalEmitter, zvariant::OwnedValue};
+
+pub const OBJECT_PATH: &str = "/org/freedesktop/Notifications";
+
+/// Counts the notifications it was asked to show, never closes any by itself unless told to.
+pub struct FakeServer {
+    shown: Arc<AtomicU32>,
+    /// Closes every notification as expired before even replying to `Notify`.
+    close_at_once: bool,
+    /// The ids it was asked to close.
+    closed: Mutex<Vec<u32>>,
+    /// What it answers `GetServerInformation` with.
+    name: Mutex<String>,
+    spec_version: Mutex<String>,
+    /// The names of the hints of every notification it was asked to show.
+    hints: Mutex<Vec<Vec<String>>>,
+}
+
+#[zbus::interface(name = "org.freedesktop.Notifications")]
+impl FakeServer {
+    #[allow(clippy::too_many_arguments)]
+    async fn notify(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        _app_name: &str,
+        _replaces_id: u32,
+        _app_icon: &str,
+        _summary: &str,
+        _body: &str,
+        _actions: Vec<&str>,
+        hints: HashMap<&str, OwnedValue>,
+        _expire_timeout: i32,
+    ) -> u32 {
+        let mut names: Vec<String> = hints.into_keys().map(String::from).collect();
+        names.sort();
+        self.hints.lock().unwrap().push(names);
+        let id = self.shown.fetch_add(1, Ordering::SeqCst) + 1;
+        if self.close_at_once {
+            FakeServer::notification_closed(&emitter, id, 1)
+                .await
+                .unwrap();
+        }
+        id
+    }
+
+    fn get_capabilities(&self) -> Vec<&str> {
+        vec!["actions", "body"]
+    }
+
+    fn get_server_information(&self) -> (String, String, String, String) {
+        let name = self.name.lock().unwrap().clone();
+        let spec_version = self.spec_version.lock().unwrap().clone();
+        (name, "notify-rust".into(), "0.0.0".into(), spec_version)
+    }
+
+    async fn close_notification(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        id: u32,
+    ) {
+        self.closed.lock().unwrap().push(id);
+        FakeServer::notification_closed(&emitter, id, 3)
+            .await
+            .unwrap();
+    }
+
+    #[zbus(signal)]
+    pub async fn action_invoked(
+        emitter: &SignalEmitter<'_>,
+        id: u32,
+        action_key: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn activation_token(
+        emitter: &SignalEmitter<'_>,
+        id: u32,
+        activation_token: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn notification_closed(
+        emitter: &SignalEmitter<'_>,
+        id: u32,
+        reason: u32,
+    ) -> zbus::Result<()>;
+}
+
+/// Claims the notification server name on the session bus.
+///
+/// Returns the connection, dropping it releases the name, and the number of notifications shown so far.
+pub fn start_server() -> (zbus::Connection, Arc<AtomicU32>) {
+    serve(false)
+}
+
+/// Like [`start_server()`], but the server closes every notification right away.
+pub fn start_closing_server() -> (zbus::Connection, Arc<AtomicU32>) {
+    serve(true)
+}
+
+/// Like [`start_server()`], but claims `name` at the same object path.
+pub fn start_server_named(name: &str) -> (zbus::Connection, Arc<AtomicU32>) {
+    zbus::block_on(serve_on(name, false, true))
+}
+
+fn serve(close_at_once: bool) -> (zbus::Connection, Arc<AtomicU32>) {
+    zbus::block_on(serve_on(
+        "org.freedesktop.Notifications",
+        close_at_once,
+        true,
+    ))
+}
+
+/// Like [`start_server()`], but the server only answers while the executor of the connection is ticked,
+/// so it stalls along with a blocked thread.
+pub async fn start_server_on_this_thread() -> (zbus::Connection, Arc<AtomicU32>) {
+    serve_on("org.freedesktop.Notifications", false, false).await
+}
+
+async fn serve_on(
+    name: &str,
+    close_at_once: bool,
+    internal_executor: bool,
+) -> (zbus::Connection, Arc<AtomicU32>) {
+    let shown = Arc::new(AtomicU32::new(0));
+    let connection = zbus::connection::Builder::session()
+        .unwrap()
+        .name(name)
+        .unwrap()
+        .serve_at(
+            OBJECT_PATH,
+            FakeServer {
+                shown: shown.clone(),
+                close_at_once,
+                closed: Mutex::new(Vec::new()),
+                name: Mutex::new("fake".into()),
+                spec_version: Mutex::new("1.2".into()),
+                hints: Mutex::new(Vec::new()),
+            },
+        )
+        .unwrap()
+        .internal_executor(internal_executor)
+        .build()
+        .await
+        .unwrap();
+    (connection, shown)
+}
+
+/// Waits for the first notification and returns its id.
+pub fn wait_until_shown(shown: &AtomicU32) -> u32 {
+    loop {
+        match shown.load(Ordering::SeqCst) {
+            0 => thread::sleep(Duration::from_millis(10)),
+            id => return id,
+        }
+    }
+}
+
+pub fn emitter(connection: &zbus::Connection) -> SignalEmitter<'_> {
+    SignalEmitter::new(connection, OBJECT_PATH).unwrap()
+}
+
+/// Runs `f` on the server on `connection`.
+fn with_server<T>(connection: &zbus::Connection, f: impl FnOnce(&FakeServer) -> T) -> T {
+    zbus::block_on(async {
+        let server = connection
+            .object_server()
+            .interface::<_, FakeServer>(OBJECT_PATH)
+            .await
+            .unwrap();
+        let server = server.get().await;
+        f(&server)
+    })
+}
+
+/// The ids the server on `connection` was asked to close so far.
+pub fn closed(connection: &zbus::Connection) -> Vec<u32> {
+    with_server(connection, |server| server.closed.lock().unwrap().clone())
+}
+
+/// Makes the server on `connection` claim to follow another version of the spec.
+pub fn set_spec_version(connection: &zbus::Connection, spec_version: &str) {
+    with_server(connection, |server| {
+        *server.spec_version.lock().unwrap() = spec_version.into();
+    });
+}
+
+/// Makes the server on `connection` claim to be another server.
+pub fn set_server_name(connection: &zbus::Connection, name: &str) {
+    with_server(connection, |server| {
+        *server.name.lock().unwrap() = name.into();
+    });
+}
+
+/// The sorted hint names of every notification the server on `connection` was asked to show so far.
+pub fn hints(connection: &zbus::Connection) -> Vec<Vec<String>> {
+    with_server(connection, |server| server.hints.lock().unwrap().clone())
+}
+
+/// The number of match rules the bus daemon holds for all of its connections.
+pub fn match_rules() -> u32 {
+    zbus::block_on(async {
+        let connection = zbus::Connection::session().await.unwrap();
+        let stats: HashMap<String, OwnedValue> = connection
+            .call_method(
+                Some("org.freedesktop.DBus"),
+                "/org/freedesktop/DBus",
+                Some("org.freedesktop.DBus.Debug.Stats"),
+                "GetStats",
+                &(),
+            )
+            .await
+            .unwrap()
+            .body()
+            .deserialize()
+            .unwrap();
+        u32::try_from(&stats["MatchRules"]).unwrap()
+    })
+}
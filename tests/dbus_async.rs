@@ -0,0 +1,118 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async", feature = "d"))]
+
+use notify_rust::*;
+use std::{
+    sync::{atomic::Ordering, mpsc},
+    thread,
+    time::Duration,
+};
+
+mod common;
+use common::{emitter, start_server_on_this_thread, FakeServer};
+
+// everything runs on one thread together with the server, any blocking call would stall it
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn dbus_async() {
+    // only this test runs in this binary
+    std::env::set_var("DBUSRS", "1");
+
+    let (done, watchdog) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        if watchdog.recv_timeout(Duration::from_secs(20)).is_err() {
+            eprintln!("an async method blocked the executor");
+            std::process::exit(1);
+        }
+    });
+
+    futures_lite::future::block_on(async {
+        let (server, shown) = start_server_on_this_thread().await;
+        let serving = async {
+            loop {
+                server.executor().tick().await;
+            }
+        };
+        futures_lite::future::or(serving, async {
+            let mut handle = Notification::new()
+                .summary("Deploy?")
+                .action("deploy", "Deploy")
+                .show_async()
+                .await
+                .unwrap();
+            assert_eq!(handle.id(), shown.load(Ordering::SeqCst));
+
+            // sent before anybody waits, subscribed to before the notification was shown
+            FakeServer::action_invoked(&emitter(&server), handle.id(), "deploy")
+                .await
+                .unwrap();
+            let (action, invoked) = mpsc::channel();
+            handle
+                .wait_for_action_async(|response: &NotificationResponse| {
+                    action.send(response.clone()).unwrap();
+                })
+                .await
+                .unwrap();
+            assert_eq!(
+                invoked.try_recv(),
+                Ok(NotificationResponse::Action("deploy".into()))
+            );
+
+            assert_eq!(
+                handle
+                    .wait_for_response_timeout_async(Duration::from_millis(50))
+                    .await
+                    .unwrap(),
+                None
+            );
+
+            handle.summary("Deploying");
+            handle.update_async().await.unwrap();
+            assert_eq!(handle.id(), shown.load(Ordering::SeqCst));
+
+            handle.close_async().await;
+            assert_eq!(
+                handle.wait_for_response_async().await.unwrap(),
+                Some(NotificationResponse::Closed(CloseReason::CloseAction))
+            );
+
+            // shown by the blocking API, the responses before the first async call are not lost
+            let mut handle =
+                async_std::task::spawn_blocking(|| Notification::new().summary("Building").show())
+                    .await
+                    .unwrap();
+            assert_eq!(handle.dbus_stack(), DbusStack::Dbus);
+            FakeServer::action_invoked(&emitter(&server), handle.id(), "cancel")
+                .await
+                .unwrap();
+            assert_eq!(
+                handle.wait_for_response_async().await.unwrap(),
+                Some(NotificationResponse::Action("cancel".into()))
+            );
+            // the same action twice is reported twice
+            for _ in 0..2 {
+                FakeServer::action_invoked(&emitter(&server), handle.id(), "cancel")
+                    .await
+                    .unwrap();
+            }
+            for _ in 0..2 {
+                assert_eq!(
+                    handle.wait_for_response_async().await.unwrap(),
+                    Some(NotificationResponse::Action("cancel".into()))
+                );
+            }
+            handle.summary("Built");
+            handle.update_async().await.unwrap();
+            assert_eq!(handle.id(), shown.load(Ordering::SeqCst));
+            FakeServer::notification_closed(&emitter(&server), handle.id(), 2)
+                .await
+                .unwrap();
+            let (reason, closed) = mpsc::channel();
+            handle
+                .on_close_async(move |r: CloseReason| reason.send(r).unwrap())
+                .await;
+            assert_eq!(closed.try_recv(), Ok(CloseReason::Dismissed));
+        })
+        .await;
+    });
+    done.send(()).unwrap();
+}
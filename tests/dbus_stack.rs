@@ -0,0 +1,71 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "z", feature = "d"))]
+
+use notify_rust::*;
+use std::{sync::atomic::Ordering, time::Duration};
+
+mod common;
+use common::{emitter, start_server, FakeServer};
+
+// one test, since every part needs to own the notification server name
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn dbus_stack_selection() {
+    let (server, shown) = start_server();
+
+    // the same scenario on both stacks, whatever `$DBUSRS` says
+    for stack in [DbusStack::Zbus, DbusStack::Dbus] {
+        set_dbus_stack(Some(stack)).unwrap();
+        assert_eq!(dbus_stack(), Some(stack));
+        assert_eq!(get_capabilities().unwrap(), ["actions", "body"]);
+
+        let handle = Notification::new().summary("blocking").show().unwrap();
+        assert_eq!(handle.dbus_stack(), stack);
+        let id = shown.load(Ordering::SeqCst);
+        zbus::block_on(FakeServer::action_invoked(&emitter(&server), id, "ok")).unwrap();
+        assert_eq!(
+            handle.wait_for_response_timeout(Duration::from_secs(5)),
+            Some(NotificationResponse::Action("ok".into()))
+        );
+
+        let handle = zbus::block_on(Notification::new().summary("async").show_async()).unwrap();
+        assert_eq!(handle.dbus_stack(), stack);
+        assert_eq!(handle.id(), shown.load(Ordering::SeqCst));
+    }
+
+    // a notifier sticks to its own choice
+    set_dbus_stack(Some(DbusStack::Zbus)).unwrap();
+    let notifier = Notifier::builder()
+        .dbus_stack(DbusStack::Dbus)
+        .connect()
+        .unwrap();
+    assert_eq!(notifier.dbus_stack(), DbusStack::Dbus);
+    let handle = notifier
+        .show(Notification::new().summary("notifier"))
+        .unwrap();
+    assert_eq!(handle.dbus_stack(), DbusStack::Dbus);
+    let notifier = zbus::block_on(
+        Notifier::builder()
+            .dbus_stack(DbusStack::Dbus)
+            .connect_async(),
+    )
+    .unwrap();
+    assert_eq!(notifier.dbus_stack(), DbusStack::Dbus);
+    let handle =
+        zbus::block_on(notifier.show_async(Notification::new().summary("async notifier"))).unwrap();
+    assert_eq!(handle.dbus_stack(), DbusStack::Dbus);
+    assert_eq!(handle.id(), shown.load(Ordering::SeqCst));
+    zbus::block_on(notifier.close_async(handle.id())).unwrap();
+    let handle = notifier
+        .show(Notification::new().summary("blocking on an async notifier"))
+        .unwrap();
+    assert_eq!(handle.id(), shown.load(Ordering::SeqCst));
+
+    // back to the environment variable
+    set_dbus_stack(None).unwrap();
+    let from_env = if std::env::var_os("DBUSRS").is_some() {
+        DbusStack::Dbus
+    } else {
+        DbusStack::Zbus
+    };
+    assert_eq!(dbus_stack(), Some(from_env));
+}
@@ -0,0 +1,91 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::*;
+use std::{sync::mpsc, thread, time::Duration};
+
+mod common;
+use common::{emitter, start_server, FakeServer};
+
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn action_callbacks() {
+    let (server, _) = start_server();
+    let (invoked, received) = mpsc::channel();
+    let notifier = Notifier::new().unwrap();
+    // the callbacks are dropped along with the last handle
+    let mut handles = Vec::new();
+    for i in 0..3 {
+        let invoked = invoked.clone();
+        let handle = notifier
+            .show(Notification::new().summary(&format!("#{i}")).action_with(
+                "next",
+                "Next",
+                move |ctx| {
+                    invoked
+                        .send((
+                            i,
+                            ctx.action().to_owned(),
+                            ctx.activation_token().map(str::to_owned),
+                        ))
+                        .unwrap();
+                },
+            ))
+            .unwrap();
+        handles.push(handle);
+    }
+    let ids: Vec<u32> = handles.iter().map(NotificationHandle::id).collect();
+
+    // not shown by a notifier, dispatched on a connection of its own
+    let invoked_plain = invoked.clone();
+    let plain = Notification::new()
+        .summary("plain")
+        .action_with("default", "Open", move |ctx| {
+            invoked_plain
+                .send((3, ctx.action().to_owned(), None))
+                .unwrap();
+        })
+        .show()
+        .unwrap();
+
+    let emitter = emitter(&server);
+    zbus::block_on(async {
+        FakeServer::action_invoked(&emitter, ids[1], "next").await?;
+        FakeServer::activation_token(&emitter, ids[2], "token").await?;
+        FakeServer::action_invoked(&emitter, ids[2], "next").await?;
+        FakeServer::action_invoked(&emitter, ids[1], "next").await?;
+        // deregisters
+        FakeServer::notification_closed(&emitter, ids[1], 2).await?;
+        FakeServer::action_invoked(&emitter, ids[1], "next").await?;
+        FakeServer::action_invoked(&emitter, plain.id(), "default").await
+    })
+    .unwrap();
+
+    // the two dispatchers run side by side, only the order per connection is given
+    let timeout = Duration::from_secs(2);
+    let mut responses: Vec<_> = (0..4)
+        .map(|_| received.recv_timeout(timeout).unwrap())
+        .collect();
+    let plain = responses.iter().position(|(i, ..)| *i == 3).unwrap();
+    assert_eq!(responses.remove(plain), (3, "default".into(), None));
+    assert_eq!(
+        responses,
+        vec![
+            (1, "next".into(), None),
+            (2, "next".into(), Some("token".into())),
+            (1, "next".into(), None),
+        ]
+    );
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(received.try_recv().ok(), None);
+
+    // deregisters too, even if the server never closes the notification
+    drop(handles.remove(0));
+    zbus::block_on(FakeServer::action_invoked(&emitter, ids[0], "next")).unwrap();
+    zbus::block_on(FakeServer::action_invoked(&emitter, ids[2], "next")).unwrap();
+    assert_eq!(
+        received.recv_timeout(timeout).unwrap(),
+        (2, "next".into(), None)
+    );
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(received.try_recv().ok(), None);
+}
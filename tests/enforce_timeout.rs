@@ -0,0 +1,85 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::*;
+use std::{thread, time::Duration};
+
+mod common;
+use common::{closed, set_server_name, start_server};
+
+fn times_closed(server: &zbus::Connection, id: u32) -> usize {
+    closed(server)
+        .into_iter()
+        .filter(|&closed| closed == id)
+        .count()
+}
+
+// one test, since every part needs to own the notification server name
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn enforce_timeout() {
+    let (server, _shown) = start_server();
+    let mut notification = Notification::new()
+        .summary("transient")
+        .timeout(Timeout::Milliseconds(100))
+        .enforce_timeout(true)
+        .finalize();
+
+    // the fake server never expires anything, waiting reports our close as expired
+    let handle = notification.show().unwrap();
+    assert_eq!(
+        handle.wait_for_response_timeout(Duration::from_secs(5)),
+        Some(NotificationResponse::Closed(CloseReason::Expired))
+    );
+    assert_eq!(times_closed(&server, handle.id()), 1);
+
+    zbus::block_on(async {
+        let handle = notification.show_async().await.unwrap();
+        assert_eq!(
+            handle
+                .wait_for_response_timeout_async(Duration::from_secs(5))
+                .await
+                .unwrap(),
+            Some(NotificationResponse::Closed(CloseReason::Expired))
+        );
+    });
+
+    // nobody needs to keep the handle
+    let id = notification.show().unwrap().id();
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(times_closed(&server, id), 1);
+
+    // closed before, nothing left to do
+    let handle = notification.show().unwrap();
+    let id = handle.id();
+    handle.close();
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(times_closed(&server, id), 1);
+
+    // showing it again starts over
+    notification.timeout(Timeout::Milliseconds(400));
+    let mut handle = notification.show().unwrap();
+    thread::sleep(Duration::from_millis(250));
+    handle.update().unwrap();
+    thread::sleep(Duration::from_millis(250));
+    assert_eq!(times_closed(&server, handle.id()), 0);
+    thread::sleep(Duration::from_millis(400));
+    assert_eq!(times_closed(&server, handle.id()), 1);
+
+    // opt-in only
+    notification
+        .timeout(Timeout::Milliseconds(100))
+        .enforce_timeout(false);
+    let handle = notification.show().unwrap();
+    assert_eq!(
+        handle.wait_for_response_timeout(Duration::from_millis(300)),
+        None
+    );
+    assert_eq!(times_closed(&server, handle.id()), 0);
+
+    // a server known to expire notifications is left to it
+    set_server_name(&server, "dunst");
+    notification.enforce_timeout(true);
+    let handle = notification.show().unwrap();
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(times_closed(&server, handle.id()), 0);
+}
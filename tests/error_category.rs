@@ -0,0 +1,45 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::{error::ErrorCategory, *};
+use std::error::Error as _;
+
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn error_category() {
+    // nobody there, and the bus cannot start anybody
+    let notification = Notification::new().summary("nobody home").finalize();
+    let error = notification.show().unwrap_err();
+    assert_eq!(
+        error.category(),
+        ErrorCategory::ServerUnavailable,
+        "{error}"
+    );
+    assert!(error.source().is_some());
+
+    let notifier = Notifier::new().unwrap();
+    let error = notifier.show(&notification).unwrap_err();
+    assert_eq!(
+        error.category(),
+        ErrorCategory::ServerUnavailable,
+        "{error}"
+    );
+    let error = zbus::block_on(notifier.show_async(&notification)).unwrap_err();
+    assert_eq!(
+        error.category(),
+        ErrorCategory::ServerUnavailable,
+        "{error}"
+    );
+
+    let error = NotificationBus::new("no dots", "/org/example/Notifications").unwrap_err();
+    assert_eq!(error.category(), ErrorCategory::InvalidArgument);
+    let error = Urgency::try_from("loud").unwrap_err();
+    assert_eq!(error.category(), ErrorCategory::InvalidArgument);
+    assert!(error.source().is_none());
+
+    #[cfg(feature = "images_no_default_features")]
+    {
+        let error = error::Error::from(Image::from_rgb(2, 2, vec![0; 3]).unwrap_err());
+        assert_eq!(error.category(), ErrorCategory::Image);
+        assert!(error.source().is_some());
+    }
+}
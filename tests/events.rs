@@ -0,0 +1,85 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::*;
+use std::{thread, time::Duration};
+
+mod common;
+use common::{emitter, start_server, FakeServer};
+
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn events() {
+    let (server, _) = start_server();
+    let handle = Notification::new()
+        .summary("Now playing")
+        .action("play-pause", "Play/Pause")
+        .action("next", "Next")
+        .hint(Hint::Resident(true))
+        .show()
+        .unwrap();
+    let id = handle.id();
+
+    let responding = server.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        let emitter = emitter(&responding);
+        zbus::block_on(async {
+            FakeServer::action_invoked(&emitter, id, "play-pause").await?;
+            FakeServer::activation_token(&emitter, id, "token").await?;
+            FakeServer::action_invoked(&emitter, id, "next").await?;
+            // somebody else's notification
+            FakeServer::notification_closed(&emitter, id + 1, 2).await?;
+            FakeServer::notification_closed(&emitter, id, 2).await
+        })
+        .unwrap();
+        thread::sleep(Duration::from_secs(1));
+    });
+
+    let events = handle
+        .events()
+        .into_iter()
+        .map(|response| (response, handle.activation_token()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![
+            (NotificationResponse::Action("play-pause".into()), None),
+            (
+                NotificationResponse::Action("next".into()),
+                Some("token".into())
+            ),
+            (
+                NotificationResponse::Closed(CloseReason::Dismissed),
+                Some("token".into())
+            ),
+        ]
+    );
+
+    // shown by the blocking API and awaited, with either stack
+    let handle = Notification::new().summary("Now playing").show().unwrap();
+    let id = handle.id();
+    let mut events = handle.events();
+    let closing = async {
+        let emitter = emitter(&server);
+        FakeServer::action_invoked(&emitter, id, "next").await?;
+        FakeServer::notification_closed(&emitter, id, 2).await
+    };
+    let events = zbus::block_on(async {
+        let mut received = Vec::new();
+        let (closed, ()) = futures_lite::future::zip(closing, async {
+            while let Some(response) = futures_lite::StreamExt::next(&mut events).await {
+                received.push(response);
+            }
+        })
+        .await;
+        closed.map(|()| received)
+    })
+    .unwrap();
+    assert_eq!(
+        events,
+        [
+            NotificationResponse::Action("next".into()),
+            NotificationResponse::Closed(CloseReason::Dismissed)
+        ]
+    );
+}
@@ -0,0 +1,69 @@
+#![cfg(all(
+    unix,
+    not(target_os = "macos"),
+    feature = "async",
+    feature = "images_no_default_features"
+))]
+
+use notify_rust::*;
+use std::sync::atomic::Ordering;
+
+mod common;
+use common::{hints, set_spec_version, start_server};
+
+fn image_hint(server: &zbus::Connection) -> String {
+    let hints = hints(server);
+    let last = hints.last().unwrap();
+    assert_eq!(last.len(), 2, "{last:?}");
+    last.iter()
+        .find(|name| *name != "category")
+        .unwrap()
+        .clone()
+}
+
+// one test, since every part needs to own the notification server name
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn image_data() {
+    let (server, shown) = start_server();
+    let notifier = Notifier::new().unwrap();
+
+    // the fake server deserializes the hints, a malformed image makes the call fail
+    let pixels = (0..64 * 64 * 4).map(|i| i as u8).collect();
+    let image = Image::from_rgba(64, 64, pixels).unwrap();
+    let mut notification = Notification::new()
+        .summary("image data")
+        .hint(Hint::ImageData(image.clone()))
+        .hint(Hint::Category("test".into()))
+        .finalize();
+    let mut handle = notifier.show(&notification).unwrap();
+    assert_eq!(image_hint(&server), "image-data");
+
+    // the version is asked once per server, a changed answer only counts after a restart
+    set_spec_version(&server, "1.1");
+    handle.image_data(image);
+    handle.update().unwrap();
+    assert_eq!(image_hint(&server), "image-data");
+    assert_eq!(shown.load(Ordering::SeqCst), 2);
+
+    drop(server);
+    let (server, _) = start_server();
+    set_spec_version(&server, "1.0");
+    for result in notifier.show_all(&[notification.clone(), notification.clone()]) {
+        result.unwrap();
+    }
+    assert_eq!(image_hint(&server), "icon_data");
+
+    // the async API asks on its own connection without blocking
+    set_spec_version(&server, "1.2");
+    notification.summary("async image data");
+    zbus::block_on(notification.show_async()).unwrap();
+    assert_eq!(image_hint(&server), "image-data");
+
+    // a server that does not tell gets the name from spec 1.1
+    drop(server);
+    let (server, _) = start_server();
+    set_spec_version(&server, "not a version");
+    notifier.show(&notification).unwrap();
+    assert_eq!(image_hint(&server), "image_data");
+}
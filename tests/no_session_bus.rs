@@ -0,0 +1,43 @@
+#![cfg(all(unix, not(target_os = "macos")))]
+
+use notify_rust::*;
+
+// one test, since the environment is shared by the whole process
+#[test]
+fn no_session_bus() {
+    let socket = std::env::temp_dir().join(format!("notify-rust-{}-no-bus", std::process::id()));
+    std::env::set_var(
+        "DBUS_SESSION_BUS_ADDRESS",
+        format!("unix:path={}", socket.display()),
+    );
+
+    let notification = Notification::new().summary("nobody home").finalize();
+    assert!(!notification.appname.is_empty());
+    assert!(!NotificationBus::default().name().is_empty());
+    #[allow(deprecated)]
+    let at_invalid_bus = Notification::at_bus("1 not a name");
+
+    for stack in [DbusStack::Zbus, DbusStack::Dbus] {
+        if !stack.is_available() {
+            continue;
+        }
+        set_dbus_stack(Some(stack)).unwrap();
+        assert!(notification.show().is_err(), "{stack:?}");
+        assert!(at_invalid_bus.show().is_err(), "{stack:?}");
+        assert!(Notifier::new().is_err(), "{stack:?}");
+        assert!(get_capabilities().is_err(), "{stack:?}");
+        assert!(get_server_information().is_err(), "{stack:?}");
+        assert!(
+            handle_action(1, |_: &ActionResponse| {}).is_err(),
+            "{stack:?}"
+        );
+    }
+
+    #[cfg(feature = "zbus")]
+    zbus::block_on(async {
+        assert!(notification.show_async().await.is_err());
+        assert!(handle_action_async(1, |_: &ActionResponse| {})
+            .await
+            .is_err());
+    });
+}
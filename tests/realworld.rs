@@ -46,6 +46,20 @@ mod realworld {
         }
     }
 
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn pipelined_burst() {
+        let notifications = ["These are sent", "without waiting", "for each other."].map(|msg| {
+            Notification::new()
+                .summary("pipelined burst")
+                .body(msg)
+                .finalize()
+        });
+        for result in Notifier::new().unwrap().show_all(&notifications) {
+            result.unwrap();
+        }
+    }
+
     #[test]
     #[cfg(all(unix, not(target_os = "macos")))]
     fn closing() {
@@ -57,6 +71,61 @@ mod realworld {
             .close();
     }
 
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn notifier() {
+        let notifier = Notifier::new().unwrap();
+        assert!(!notifier.server_information().unwrap().name.is_empty());
+        assert!(!notifier.capabilities().unwrap().is_empty());
+
+        let handle = notifier
+            .show(
+                Notification::new()
+                    .summary("notifier")
+                    .body("shared connection"),
+            )
+            .unwrap();
+        notifier.close(handle.id()).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn wait_for_server() {
+        let notifier = Notifier::builder()
+            .wait_for_server(Duration::from_secs(1))
+            .connect()
+            .unwrap();
+        assert!(notifier.wait_for_server(Duration::from_secs(1)).unwrap());
+        notifier
+            .show(Notification::new().summary("server is there"))
+            .unwrap();
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn notifier_at_missing_address() {
+        let address = BusAddress::Address("unix:path=/nonexistent/notify-rust/bus".into());
+        assert!(Notifier::connect(address).is_err());
+
+        let address = BusAddress::PeerToPeer("unix:path=/nonexistent/notify-rust/bus".into());
+        assert!(address.is_peer_to_peer());
+        assert!(Notifier::connect(address).is_err());
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn notification_bus() {
+        let bus = NotificationBus::new("org.example.Notifications", "/org/example/Notifications")
+            .unwrap();
+        assert_eq!(bus.name(), "org.example.Notifications");
+        assert_eq!(bus.path(), "/org/example/Notifications");
+
+        assert!(NotificationBus::new(":1.42", "/org/example/Notifications").is_err());
+        assert!(NotificationBus::new("no dots", "/org/example/Notifications").is_err());
+        assert!(NotificationBus::new("org.example.Notifications", "relative/path").is_err());
+        assert!(NotificationBus::new("org.example.Notifications", "/trailing/").is_err());
+    }
+
     #[test]
     #[cfg(all(unix, not(target_os = "macos")))]
     fn capabilities() {
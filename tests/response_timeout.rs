@@ -0,0 +1,73 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::*;
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+mod common;
+use common::{closed, emitter, start_server, FakeServer};
+
+// one test, since every part needs to own the notification server name
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn response_timeout() {
+    let (server, shown) = start_server();
+
+    // nothing happens, nothing is reported
+    let handle = Notification::new().summary("ignored").show().unwrap();
+    let started = Instant::now();
+    assert_eq!(
+        handle.wait_for_response_timeout(Duration::from_millis(200)),
+        None
+    );
+    assert!(started.elapsed() >= Duration::from_millis(200));
+
+    // responses that arrive between two waits are not lost
+    let id = shown.load(Ordering::SeqCst);
+    zbus::block_on(FakeServer::action_invoked(&emitter(&server), id, "later")).unwrap();
+    assert_eq!(
+        handle.wait_for_response_timeout(Duration::from_secs(5)),
+        Some(NotificationResponse::Action("later".into()))
+    );
+
+    // not even when the waiting future is dropped half way
+    let cancelled = futures_lite::future::or(
+        async { Some(handle.wait_for_response_async().await.unwrap()) },
+        async {
+            futures_lite::future::yield_now().await;
+            None
+        },
+    );
+    assert_eq!(zbus::block_on(cancelled), None);
+    zbus::block_on(FakeServer::action_invoked(&emitter(&server), id, "again")).unwrap();
+    assert_eq!(
+        zbus::block_on(handle.wait_for_response_timeout_async(Duration::from_secs(5))).unwrap(),
+        Some(NotificationResponse::Action("again".into()))
+    );
+
+    // closed by the server, nothing more to wait for
+    zbus::block_on(FakeServer::notification_closed(&emitter(&server), id, 2)).unwrap();
+    assert_eq!(
+        handle.wait_for_response_timeout(Duration::from_secs(5)),
+        Some(NotificationResponse::Closed(CloseReason::Dismissed))
+    );
+    assert_eq!(
+        handle.wait_for_response_timeout(Duration::from_secs(5)),
+        None
+    );
+
+    // closed by us once the time is up
+    let mut handle = Notification::new().summary("expiring").show().unwrap();
+    handle.close_on_timeout(true);
+    assert_eq!(
+        handle.wait_for_response_timeout(Duration::from_millis(200)),
+        Some(NotificationResponse::Closed(CloseReason::Expired))
+    );
+    assert_eq!(closed(&server), [handle.id()]);
+    assert_eq!(
+        handle.wait_for_response_timeout(Duration::from_millis(200)),
+        None
+    );
+}
@@ -0,0 +1,78 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::*;
+use std::{
+    panic, thread,
+    time::{Duration, Instant},
+};
+
+mod common;
+use common::{closed, start_server};
+
+fn syncing(fail: bool) -> Result<u32, Box<dyn std::error::Error>> {
+    let syncing = Notification::new().summary("Syncing...").show_scoped()?;
+    if fail {
+        Err("sync failed")?;
+    }
+    Ok(syncing.id())
+}
+
+fn eventually_closed(server: &zbus::Connection, ids: &[u32]) -> bool {
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_secs(5) {
+        if closed(server) == ids {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+// one test, since every part needs to own the notification server name
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn scoped() {
+    let (server, shown) = start_server();
+    let last_shown = || shown.load(std::sync::atomic::Ordering::SeqCst);
+
+    // closed at the end of the scope
+    let done = syncing(false).unwrap();
+    assert_eq!(closed(&server), [done]);
+
+    // and when leaving it early with `?`
+    assert!(syncing(true).is_err());
+    let failed = last_shown();
+    assert_eq!(closed(&server), [done, failed]);
+
+    // and while unwinding
+    let result = panic::catch_unwind(|| {
+        let _syncing = Notification::new()
+            .summary("Syncing...")
+            .show_scoped()
+            .unwrap();
+        panic!("sync crashed");
+    });
+    assert!(result.is_err());
+    let crashed = last_shown();
+    assert_eq!(closed(&server), [done, failed, crashed]);
+
+    // unless it is kept
+    let kept = Notification::new()
+        .summary("Synced")
+        .show_scoped()
+        .unwrap()
+        .keep();
+    assert_eq!(closed(&server), [done, failed, crashed]);
+
+    // from async code it is closed in the background
+    let id = zbus::block_on(async {
+        let syncing = Notification::new()
+            .summary("Syncing...")
+            .show_scoped_async()
+            .await
+            .unwrap();
+        syncing.id()
+    });
+    assert!(eventually_closed(&server, &[done, failed, crashed, id]));
+    drop(kept);
+}
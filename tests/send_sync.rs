@@ -0,0 +1,12 @@
+#![cfg(all(unix, not(target_os = "macos")))]
+
+use notify_rust::{NotificationHandle, Notifier};
+
+fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+// handles can be moved to and waited on from worker threads, whichever D-Bus stack is enabled
+#[test]
+fn handles_are_send_and_sync() {
+    assert_send_sync::<NotificationHandle>();
+    assert_send_sync::<Notifier>();
+}
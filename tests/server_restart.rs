@@ -0,0 +1,47 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::*;
+use std::{thread, time::Duration};
+
+mod common;
+use common::{emitter, start_server, wait_until_shown, FakeServer};
+
+// one test, since both halves need to own the notification server name
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn server_restart() {
+    // Vanish
+    let (server, _) = start_server();
+    let handle = Notifier::new()
+        .unwrap()
+        .show(Notification::new().summary("vanish"))
+        .unwrap();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        drop(server);
+    });
+    let error = handle
+        .wait_for_response(|response: &NotificationResponse| panic!("{response:?}"))
+        .unwrap_err();
+    assert_eq!(error.category(), error::ErrorCategory::ServerVanished);
+
+    // Reshow
+    let (server, _) = start_server();
+    let handle = Notifier::builder()
+        .on_server_restart(ServerRestartPolicy::Reshow)
+        .connect()
+        .unwrap()
+        .show(Notification::new().summary("reshow"))
+        .unwrap();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        drop(server);
+        let (server, shown) = start_server();
+        let id = wait_until_shown(&shown);
+        zbus::block_on(FakeServer::notification_closed(&emitter(&server), id, 2)).unwrap();
+        thread::sleep(Duration::from_secs(1));
+    });
+    let reason = std::cell::Cell::new(None);
+    handle.on_close(|r: CloseReason| reason.set(Some(r)));
+    assert_eq!(reason.get(), Some(CloseReason::Dismissed));
+}
@@ -0,0 +1,124 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::*;
+use std::{
+    sync::{atomic::Ordering, mpsc, Arc, Mutex},
+    time::Duration,
+};
+
+mod common;
+use common::{closed, emitter, start_server, FakeServer, OBJECT_PATH};
+
+fn next(received: &mpsc::Receiver<ServiceEvent>) -> ServiceEvent {
+    received.recv_timeout(Duration::from_secs(5)).unwrap()
+}
+
+// one test, since every part needs to own the notification server name
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn service() {
+    // the callback blocks while `gate` is held, to pile up commands
+    let gate = Arc::new(Mutex::new(()));
+    let (events, received) = mpsc::channel();
+    let service = NotificationService::spawn({
+        let gate = gate.clone();
+        move |event| {
+            let _held = gate.lock().unwrap();
+            events.send(event).unwrap();
+        }
+    })
+    .unwrap();
+
+    // no server yet
+    let failed = service.send(Notification::new().summary("too early"));
+    match next(&received) {
+        ServiceEvent::Failed { ticket, .. } => assert_eq!(ticket, failed),
+        event => panic!("unexpected {event:?}"),
+    }
+
+    let (server, shown) = start_server();
+
+    // shown and subscribed from a clone
+    let clone = service.clone();
+    let subscribed = clone.send(Notification::new().summary("subscribed"));
+    clone.subscribe(subscribed);
+    let id = match next(&received) {
+        ServiceEvent::Shown { ticket, id } if ticket == subscribed => id,
+        event => panic!("unexpected {event:?}"),
+    };
+    zbus::block_on(FakeServer::action_invoked(&emitter(&server), id, "ok")).unwrap();
+    match next(&received) {
+        ServiceEvent::Response { ticket, response } => {
+            assert_eq!(ticket, subscribed);
+            assert_eq!(response, NotificationResponse::Action("ok".into()));
+        }
+        event => panic!("unexpected {event:?}"),
+    }
+
+    service.update(subscribed, Notification::new().summary("updated"));
+    assert!(matches!(
+        next(&received),
+        ServiceEvent::Updated { ticket, .. } if ticket == subscribed
+    ));
+
+    // a burst while the thread is busy is one batch, the updates before the close are skipped
+    let held = gate.lock().unwrap();
+    let burst = service.send(Notification::new().summary("burst"));
+    std::thread::sleep(Duration::from_millis(200));
+    for i in 0..10 {
+        service.update(burst, Notification::new().summary(&format!("burst #{i}")));
+    }
+    service.close(burst);
+    drop(held);
+    let burst_id = match next(&received) {
+        ServiceEvent::Shown { ticket, id } if ticket == burst => id,
+        event => panic!("unexpected {event:?}"),
+    };
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(shown.load(Ordering::SeqCst), burst_id);
+    assert_eq!(closed(&server), [burst_id]);
+
+    // forgotten a little after its timeout, the fake server never reports it closed
+    let expiring = service.send(
+        Notification::new()
+            .summary("expiring")
+            .timeout(Timeout::Milliseconds(100)),
+    );
+    assert!(matches!(
+        next(&received),
+        ServiceEvent::Shown { ticket, .. } if ticket == expiring
+    ));
+    std::thread::sleep(Duration::from_millis(1500));
+    service.update(expiring, Notification::new().summary("too late"));
+    assert!(matches!(
+        next(&received),
+        ServiceEvent::Forgotten { ticket } if ticket == expiring
+    ));
+
+    // closing a subscribed notification reports it as closed
+    service.close(subscribed);
+    match next(&received) {
+        ServiceEvent::Response { ticket, response } => {
+            assert_eq!(ticket, subscribed);
+            assert_eq!(
+                response,
+                NotificationResponse::Closed(CloseReason::CloseAction)
+            );
+        }
+        event => panic!("unexpected {event:?}"),
+    }
+
+    // closed on the bus it was shown on, nobody owns the default name anymore
+    zbus::block_on(server.request_name("org.example.Notifications")).unwrap();
+    let bus = NotificationBus::new("org.example.Notifications", OBJECT_PATH).unwrap();
+    let custom = service.send(Notification::new().summary("custom").bus(bus));
+    let custom_id = match next(&received) {
+        ServiceEvent::Shown { ticket, id } if ticket == custom => id,
+        event => panic!("unexpected {event:?}"),
+    };
+    zbus::block_on(server.release_name("org.freedesktop.Notifications")).unwrap();
+    service.close(custom);
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(received.try_recv().is_err());
+    assert_eq!(closed(&server).last(), Some(&custom_id));
+}
@@ -0,0 +1,56 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::*;
+use std::{sync::atomic::Ordering, time::Duration};
+
+mod common;
+use common::{emitter, start_server, FakeServer};
+
+// one test, since every part needs to own the notification server name
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn show_all() {
+    let (server, shown) = start_server();
+    let notifier = Notifier::new().unwrap();
+
+    let mut notifications: Vec<Notification> = (0..30)
+        .map(|i| Notification::new().summary(&format!("#{i}")).finalize())
+        .collect();
+    // one with a callback and one nobody shows, in between the others
+    notifications[10].action_with("ok", "OK", |_| {});
+    notifications[20].bus(NotificationBus::custom("nobody").unwrap());
+
+    let results = notifier.show_all(&notifications);
+    assert_eq!(results.len(), notifications.len());
+    assert!(results[20].is_err());
+    let mut ids: Vec<u32> = results
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != 20)
+        .map(|(_, result)| result.as_ref().unwrap().id())
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), 29);
+    assert_eq!(shown.load(Ordering::SeqCst), 29);
+
+    // every handle listens for its own responses
+    for handle in [&results[5], &results[29]] {
+        let handle = handle.as_ref().unwrap();
+        zbus::block_on(FakeServer::action_invoked(
+            &emitter(&server),
+            handle.id(),
+            "ok",
+        ))
+        .unwrap();
+        assert_eq!(
+            handle.wait_for_response_timeout(Duration::from_secs(5)),
+            Some(NotificationResponse::Action("ok".into()))
+        );
+    }
+
+    let results = zbus::block_on(notifier.show_all_async(&notifications[..3]));
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(shown.load(Ordering::SeqCst), 32);
+}
@@ -0,0 +1,66 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::*;
+use std::{thread, time::Duration};
+
+mod common;
+use common::{closed, start_server};
+
+fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+
+// one test, since every part needs to own the notification server name
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn split() {
+    assert_shareable::<NotificationController>();
+    let (server, _) = start_server();
+
+    let (controller, waiter) = Notification::new()
+        .summary("Downloading")
+        .body("0%")
+        .show()
+        .unwrap()
+        .split();
+    assert_eq!(controller.id(), waiter.id());
+
+    // updated and closed from elsewhere while waiting, the fake server hands out a new id on every update
+    let updates = thread::spawn({
+        let controller = controller.clone();
+        move || {
+            for percent in [50, 100] {
+                controller
+                    .update(|notification| {
+                        notification.body(&format!("{percent}%"));
+                    })
+                    .unwrap();
+            }
+            controller.close().unwrap();
+        }
+    });
+    assert_eq!(
+        waiter.wait_for_response_timeout(Duration::from_secs(5)),
+        Some(NotificationResponse::Closed(CloseReason::CloseAction))
+    );
+    updates.join().unwrap();
+    assert_eq!(controller.id(), waiter.id());
+    assert_eq!(closed(&server), [controller.id()]);
+
+    // the async versions work with either stack
+    let (controller, waiter) = Notification::new()
+        .summary("Uploading")
+        .show()
+        .unwrap()
+        .split();
+    let shown = controller.id();
+    zbus::block_on(controller.update_async(|notification| {
+        notification.body("50%");
+    }))
+    .unwrap();
+    assert_ne!(controller.id(), shown);
+    zbus::block_on(controller.close_async()).unwrap();
+    assert_eq!(
+        waiter.wait_for_response_timeout(Duration::from_secs(5)),
+        Some(NotificationResponse::Closed(CloseReason::CloseAction))
+    );
+    assert_eq!(closed(&server).last(), Some(&controller.id()));
+}
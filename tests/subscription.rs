@@ -0,0 +1,98 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::*;
+use std::{sync::mpsc, thread, time::Duration};
+use zbus::object_server::SignalEmitter;
+
+mod common;
+use common::{
+    emitter, match_rules, start_closing_server, start_server, start_server_named, FakeServer,
+    OBJECT_PATH,
+};
+
+fn wait_for_response(handle: NotificationHandle) -> NotificationResponse {
+    let (sender, receiver) = mpsc::channel();
+    handle
+        .wait_for_response(move |response: &NotificationResponse| {
+            sender.send(response.clone()).unwrap();
+        })
+        .unwrap();
+    receiver.try_recv().unwrap()
+}
+
+// one test, since every part needs to own the notification server name
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn subscription() {
+    // closed before `Notify` even returned
+    let (server, _) = start_closing_server();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let handle = Notification::new().summary("gone").show().unwrap();
+        sender.send(wait_for_response(handle)).unwrap();
+    });
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(5)),
+        Ok(NotificationResponse::Closed(CloseReason::Expired))
+    );
+    drop(server);
+
+    // only the server that showed the notification is listened to, and only at its object path
+    let (server, shown) = start_server();
+    let handle = Notification::new().summary("spoofed").show().unwrap();
+    let id = shown.load(std::sync::atomic::Ordering::SeqCst);
+    let signals = thread::spawn(move || {
+        let impostor = zbus::block_on(zbus::Connection::session()).unwrap();
+        zbus::block_on(FakeServer::action_invoked(
+            &emitter(&impostor),
+            id,
+            "spoofed",
+        ))
+        .unwrap();
+        let elsewhere = SignalEmitter::new(&server, "/org/freedesktop/Elsewhere").unwrap();
+        zbus::block_on(FakeServer::action_invoked(&elsewhere, id, "elsewhere")).unwrap();
+        zbus::block_on(FakeServer::notification_closed(&emitter(&server), id, 2)).unwrap();
+        server
+    });
+    assert_eq!(
+        wait_for_response(handle),
+        NotificationResponse::Closed(CloseReason::Dismissed)
+    );
+    drop(signals.join().unwrap());
+
+    // another server at the same object path cannot answer for ours, though both are subscribed to
+    let (server, shown) = start_server();
+    let (other, _) = start_server_named("org.example.Notifications");
+    let notifier = Notifier::new().unwrap();
+    let bus = NotificationBus::new("org.example.Notifications", OBJECT_PATH).unwrap();
+    let _elsewhere = notifier
+        .show(Notification::new().summary("elsewhere").bus(bus))
+        .unwrap();
+    let handle = notifier.show(Notification::new().summary("ours")).unwrap();
+    // both servers count from one
+    let id = shown.load(std::sync::atomic::Ordering::SeqCst);
+    zbus::block_on(FakeServer::action_invoked(&emitter(&other), id, "other")).unwrap();
+    zbus::block_on(FakeServer::notification_closed(&emitter(&server), id, 2)).unwrap();
+    assert_eq!(
+        wait_for_response(handle),
+        NotificationResponse::Closed(CloseReason::Dismissed)
+    );
+    drop((notifier, other, server));
+
+    // showing more notifications adds no more rules, and the rules are gone with the connection
+    let (server, shown) = start_server();
+    let rules = match_rules();
+    let notifier = Notifier::new().unwrap();
+    let mut subscribed = None;
+    for summary in ["first", "second"] {
+        let handle = notifier.show(Notification::new().summary(summary)).unwrap();
+        let id = shown.load(std::sync::atomic::Ordering::SeqCst);
+        zbus::block_on(FakeServer::notification_closed(&emitter(&server), id, 2)).unwrap();
+        wait_for_response(handle);
+        let now = match_rules();
+        assert_eq!(*subscribed.get_or_insert(now), now);
+    }
+    drop(notifier);
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(match_rules(), rules);
+}
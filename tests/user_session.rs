@@ -0,0 +1,29 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::*;
+use std::sync::atomic::Ordering;
+
+mod common;
+use common::start_server;
+
+// one test, since every part needs to own the notification server name
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn user_session() {
+    let (_server, shown) = start_server();
+    // the session bus of the test stands in for the one of a logged in user
+    let session = UserSession {
+        id: Some("1".into()),
+        uid: 1000,
+        user: Some("user".into()),
+        address: BusAddress::Address(std::env::var("DBUS_SESSION_BUS_ADDRESS").unwrap()),
+    };
+    let notification = Notification::new().summary("Backup failed").finalize();
+
+    let handle = session.show(&notification).unwrap();
+    assert_eq!(handle.id(), shown.load(Ordering::SeqCst));
+
+    let handle = zbus::block_on(session.show_async(&notification)).unwrap();
+    assert_eq!(handle.id(), shown.load(Ordering::SeqCst));
+    assert_eq!(shown.load(Ordering::SeqCst), 2);
+}
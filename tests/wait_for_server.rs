@@ -0,0 +1,55 @@
+#![cfg(all(unix, not(target_os = "macos"), feature = "async"))]
+
+use notify_rust::{error::ErrorCategory, *};
+use std::{
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+
+mod common;
+use common::start_server;
+
+// one test, since every part needs to own the notification server name
+#[test]
+#[ignore = "needs a session bus without a notification server"]
+fn queued_until_the_server_shows_up() {
+    // gives up before the server is there
+    let impatient = Notifier::builder()
+        .wait_for_server(Duration::from_millis(200))
+        .connect()
+        .unwrap();
+    impatient
+        .send(Notification::new().summary("dropped"))
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+
+    let patient = Notifier::builder()
+        .wait_for_server(Duration::from_secs(5))
+        .connect()
+        .unwrap();
+    patient.send(Notification::new().summary("first")).unwrap();
+    patient.send(Notification::new().summary("second")).unwrap();
+    // without a handle to return, show does not wait
+    let error = patient
+        .show(Notification::new().summary("too early"))
+        .unwrap_err();
+    assert_eq!(
+        error.category(),
+        ErrorCategory::ServerUnavailable,
+        "{error}"
+    );
+
+    let (_server, shown) = start_server();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while shown.load(Ordering::SeqCst) < 2 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(shown.load(Ordering::SeqCst), 2);
+
+    // the server was seen, nothing is held back anymore
+    patient.send(Notification::new().summary("third")).unwrap();
+    patient.show(Notification::new().summary("fourth")).unwrap();
+    assert_eq!(shown.load(Ordering::SeqCst), 4);
+}